@@ -1,52 +1,316 @@
+use std::borrow::Cow;
+use std::io::Read;
 use std::path::Path;
+use std::sync::Arc;
 
-use crate::capture_file::{ReadFile, WriteFile};
+use crate::capture_file::{on_end_of_file, ReadFile, WriteFile};
 use crate::capture_header::{CaptureHeader, TimestampPrecision};
 use crate::endianness_aware_cursor::Endianness;
 use crate::error::Error;
 use crate::packet::Packet;
 use crate::packet_header::PacketHeader;
+use crate::packet_layer::LinkLayerType;
+use crate::pcapng::{
+    BlockOption, EnhancedPacketBlock, InterfaceDescriptionBlock, PcapngReader, SectionHeaderBlock,
+};
+use crate::spill::SpillFile;
 
 #[derive(Debug)]
 pub struct ReadOnlyCapture {
     file: ReadFile,
-    endianness: Endianness,
-    timestamp_precision: TimestampPrecision,
+    format: CaptureFormat,
+    link_layer_type: LinkLayerType,
+    packet: PacketBuffer,
+}
+
+/// Packet of the last record read
+#[derive(Debug)]
+enum PacketBuffer {
+    /// Location of the packet in a memory-mapped capture file
+    Mapped {
+        position: u64,
+        length: usize,
+    },
+    Owned(Packet<'static>),
+}
+
+#[derive(Debug)]
+enum CaptureFormat {
+    Pcap {
+        endianness: Endianness,
+        timestamp_precision: TimestampPrecision,
+    },
+    Pcapng(PcapngReader),
 }
 
 impl ReadOnlyCapture {
     pub fn open(path: impl AsRef<Path>) -> Result<(CaptureHeader, Self), Error> {
-        let mut file = ReadFile::open(path)?;
-        let header_buffer = file.read(CaptureHeader::LENGTH)?;
-        let header = CaptureHeader::parse(&header_buffer)?;
+        Self::from_file(ReadFile::open(path)?)
+    }
+
+    /// Reads a capture from `reader`, such as a [`crate::live::LiveCapture`], as it arrives
+    pub fn from_reader(reader: Box<dyn Read + Send>) -> Result<(CaptureHeader, Self), Error> {
+        Self::from_file(ReadFile::from_stream(reader)?)
+    }
+
+    fn from_file(mut file: ReadFile) -> Result<(CaptureHeader, Self), Error> {
+        let is_pcapng = PcapngReader::is_section_header_block(&file.read(4)?);
+        file.seek(0)?;
+
+        let (header, format) = if is_pcapng {
+            let (header, reader) = PcapngReader::open(&mut file)?;
+
+            (header, CaptureFormat::Pcapng(reader))
+        } else {
+            let header_buffer = file.read(CaptureHeader::LENGTH)?;
+            let header = CaptureHeader::parse(&header_buffer)?;
+
+            let format = CaptureFormat::Pcap {
+                endianness: header.endianness,
+                timestamp_precision: header.timestamp_precision,
+            };
+
+            (header, format)
+        };
 
         let capture = Self {
             file,
-            endianness: header.endianness,
-            timestamp_precision: header.timestamp_precision,
+            format,
+            link_layer_type: header.link_layer_type,
+            packet: PacketBuffer::Owned(Packet::from(Vec::new())),
         };
 
         Ok((header, capture))
     }
 
-    pub fn get(&mut self) -> Result<(PacketHeader, Packet), Error> {
-        let header_buffer = self.file.read(PacketHeader::LENGTH)?;
-        let packet_header =
-            PacketHeader::parse(&header_buffer, self.endianness, self.timestamp_precision);
+    /// Reads the next packet, returning `None` if the capture ends cleanly before it
+    ///
+    /// A capture ending in the middle of a record fails with [`Error::TruncatedRecordHeader`] or
+    /// [`Error::TruncatedRecord`].
+    pub fn get(&mut self) -> Result<Option<(PacketHeader, Packet<'static>)>, Error> {
+        let Some(packet_header) = self.next_record()? else {
+            return Ok(None);
+        };
+
+        Ok(Some((packet_header, self.packet().into_owned())))
+    }
+
+    /// Reads the header of the next record, returning `None` if the capture ends cleanly before
+    /// it
+    ///
+    /// The packet of the record is available from [`Self::packet`] until the next record is
+    /// read. Failures are the same as for [`Self::get`].
+    pub fn next_record(&mut self) -> Result<Option<PacketHeader>, Error> {
+        match &mut self.format {
+            CaptureFormat::Pcap {
+                endianness,
+                timestamp_precision,
+            } => {
+                let position = self.file.position();
+
+                let Some(header_buffer) = self
+                    .file
+                    .read_or_eof(PacketHeader::LENGTH)
+                    .map_err(|e| on_end_of_file(e, Error::TruncatedRecordHeader(position)))?
+                else {
+                    return Ok(None);
+                };
+                let packet_header =
+                    PacketHeader::parse(&header_buffer, *endianness, *timestamp_precision);
+
+                let packet_position = self.file.position();
+                let packet_length = packet_header.captured_length;
+                let packet_buffer = self
+                    .file
+                    .read(packet_length.into())
+                    .map_err(|e| on_end_of_file(e, Error::TruncatedRecord(position)))?;
+                self.packet = match packet_buffer {
+                    Cow::Borrowed(buffer) => PacketBuffer::Mapped {
+                        position: packet_position,
+                        length: buffer.len(),
+                    },
+                    Cow::Owned(buffer) => PacketBuffer::Owned(buffer.into()),
+                };
+
+                Ok(Some(packet_header))
+            }
+            CaptureFormat::Pcapng(reader) => {
+                let Some((packet_header, packet, link_layer_type)) =
+                    reader.read_packet(&mut self.file)?
+                else {
+                    return Ok(None);
+                };
+                self.link_layer_type = link_layer_type;
+                self.packet = PacketBuffer::Owned(packet);
+
+                Ok(Some(packet_header))
+            }
+        }
+    }
+
+    /// Packet of the last record read, borrowed from the capture file if it is memory-mapped
+    pub fn packet(&self) -> Packet<'_> {
+        match &self.packet {
+            PacketBuffer::Mapped { position, length } => self
+                .file
+                .mapped(*position, *length)
+                .expect("records read from a mapping lie within it")
+                .into(),
+            PacketBuffer::Owned(packet) => packet.as_slice().into(),
+        }
+    }
+
+    /// Link layer type of the last packet returned, which can differ between interfaces of a
+    /// pcapng capture
+    pub const fn link_layer_type(&self) -> LinkLayerType {
+        self.link_layer_type
+    }
+
+    pub const fn file_extension(&self) -> &'static str {
+        match self.format {
+            CaptureFormat::Pcap { .. } => "pcap",
+            CaptureFormat::Pcapng(_) => "pcapng",
+        }
+    }
+
+    /// Section, interface and options of the last packet returned, for pcapng captures
+    pub fn pcapng_context(
+        &self,
+    ) -> Option<(
+        &SectionHeaderBlock,
+        &InterfaceDescriptionBlock,
+        &[BlockOption],
+    )> {
+        match &self.format {
+            CaptureFormat::Pcap { .. } => None,
+            CaptureFormat::Pcapng(reader) => Some((
+                reader.section_header(),
+                reader.interface(),
+                reader.packet_options(),
+            )),
+        }
+    }
+
+    /// Offset of the next record in the capture file
+    pub const fn position(&self) -> u64 {
+        self.file.position()
+    }
+
+    /// Spool file holding what has been read of a capture that is not memory-mapped, such as
+    /// standard input or a named pipe, from which the capture can be opened again
+    pub fn spool(&self) -> Option<Arc<SpillFile>> {
+        self.file.spool().cloned()
+    }
+
+    /// Records of a memory-mapped pcap capture, which can be read from any position without
+    /// affecting the capture
+    pub fn mapped_records(&self) -> Option<MappedRecords<'_>> {
+        let CaptureFormat::Pcap {
+            endianness,
+            timestamp_precision,
+        } = self.format
+        else {
+            return None;
+        };
+
+        let mapped_records = MappedRecords {
+            buffer: self.file.mapping()?,
+            start: CaptureHeader::LENGTH as u64,
+            endianness,
+            timestamp_precision,
+        };
 
-        let packet_length = packet_header.captured_length;
-        let packet_buffer = self.file.read(packet_length.into())?;
-        let packet: Packet = packet_buffer.into();
+        Some(mapped_records)
+    }
+
+    /// Reads the header of the record starting at `position`, which should be a value previously
+    /// returned by [`Self::position`], leaving its packet available from [`Self::packet`]
+    pub fn record_at(&mut self, position: u64) -> Result<PacketHeader, Error> {
+        match &mut self.format {
+            CaptureFormat::Pcap { .. } => self.file.seek(position)?,
+            CaptureFormat::Pcapng(reader) => reader.seek(&mut self.file, position)?,
+        }
 
-        Ok((packet_header, packet))
+        self.next_record()?
+            .ok_or(Error::TruncatedRecordHeader(position))
+    }
+}
+
+/// Records of a memory-mapped pcap capture
+pub struct MappedRecords<'a> {
+    buffer: &'a [u8],
+    start: u64,
+    endianness: Endianness,
+    timestamp_precision: TimestampPrecision,
+}
+
+impl<'a> MappedRecords<'a> {
+    /// Length of the capture file
+    const fn end(&self) -> u64 {
+        self.buffer.len() as u64
+    }
+
+    /// Offsets of the records starting chunks of at least `chunk_length` bytes, followed by the
+    /// end of the capture
+    ///
+    /// Only the record headers are read, from one record to the next. A record the capture ends
+    /// in the middle of belongs to the last chunk, so that reading that chunk runs into it.
+    pub fn chunk_boundaries(&self, chunk_length: u64) -> Vec<u64> {
+        let mut boundaries = vec![self.start];
+        let mut chunk_start = self.start;
+        let mut position = self.start;
+        while let Some(header) = self.header_at(position) {
+            position += (PacketHeader::LENGTH as u64) + u64::from(header.captured_length.0);
+            if position >= self.end() {
+                break;
+            }
+
+            if position - chunk_start >= chunk_length {
+                boundaries.push(position);
+                chunk_start = position;
+            }
+        }
+        boundaries.push(self.end());
+
+        boundaries
+    }
+
+    fn header_at(&self, position: u64) -> Option<PacketHeader> {
+        let start = usize::try_from(position).ok()?;
+        let header_buffer = self.buffer.get(start..start + PacketHeader::LENGTH)?;
+
+        Some(PacketHeader::parse(
+            header_buffer,
+            self.endianness,
+            self.timestamp_precision,
+        ))
+    }
+
+    /// Reads the record at `position`, returning it with the position of the next record
+    ///
+    /// Fails with [`Error::TruncatedRecordHeader`] or [`Error::TruncatedRecord`] if the capture
+    /// ends in the middle of the record.
+    pub fn get(&self, position: u64) -> Result<(PacketHeader, Packet<'a>, u64), Error> {
+        let header = self
+            .header_at(position)
+            .ok_or(Error::TruncatedRecordHeader(position))?;
+
+        let packet_start = position as usize + PacketHeader::LENGTH;
+        let packet_end = packet_start + usize::from(header.captured_length);
+        let packet = self
+            .buffer
+            .get(packet_start..packet_end)
+            .ok_or(Error::TruncatedRecord(position))?;
+
+        Ok((header, packet.into(), packet_end as u64))
     }
 }
 
 impl Iterator for ReadOnlyCapture {
-    type Item = (PacketHeader, Packet);
+    type Item = Result<(PacketHeader, Packet<'static>), Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.get().ok()
+        self.get().transpose()
     }
 }
 
@@ -54,6 +318,7 @@ impl Iterator for ReadOnlyCapture {
 pub struct WriteOnlyCapture {
     file: WriteFile,
     endianness: Endianness,
+    timestamp_precision: TimestampPrecision,
 }
 
 impl WriteOnlyCapture {
@@ -65,16 +330,199 @@ impl WriteOnlyCapture {
         let capture = Self {
             file,
             endianness: header.endianness,
+            timestamp_precision: header.timestamp_precision,
         };
 
         Ok(capture)
     }
 
     pub fn put(&mut self, packet_header: PacketHeader, packet: &Packet) -> Result<(), Error> {
-        let header_buffer = packet_header.compose(self.endianness);
+        let header_buffer = packet_header.compose(self.endianness, self.timestamp_precision);
         self.file.write(header_buffer.as_slice())?;
         self.file.write(packet.as_slice())?;
 
         Ok(())
     }
+
+    pub fn finish(self) -> Result<(), Error> {
+        self.file.finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct WriteOnlyPcapngCapture {
+    file: WriteFile,
+    endianness: Endianness,
+    interfaces: Vec<InterfaceDescriptionBlock>,
+}
+
+impl WriteOnlyPcapngCapture {
+    pub fn create(
+        path: impl AsRef<Path>,
+        section_header: &SectionHeaderBlock,
+    ) -> Result<Self, Error> {
+        let mut file = WriteFile::create(path)?;
+        file.write(section_header.compose().as_slice())?;
+
+        let capture = Self {
+            file,
+            endianness: section_header.endianness,
+            interfaces: Vec::new(),
+        };
+
+        Ok(capture)
+    }
+
+    /// Writes `packet` as an enhanced packet block, writing an interface description block
+    /// before it if `interface` was not written before
+    pub fn put(
+        &mut self,
+        interface: &InterfaceDescriptionBlock,
+        packet_header: PacketHeader,
+        packet: &Packet,
+        options: Vec<BlockOption>,
+    ) -> Result<(), Error> {
+        let interface_id = match self.interfaces.iter().position(|i| i == interface) {
+            Some(interface_id) => interface_id,
+            None => {
+                self.file
+                    .write(interface.compose(self.endianness).as_slice())?;
+                self.interfaces.push(interface.clone());
+
+                self.interfaces.len() - 1
+            }
+        };
+
+        let block = EnhancedPacketBlock {
+            interface_id: interface_id as u32,
+            timestamp: interface.units(packet_header.timestamp),
+            original_length: packet_header.actual_length.0,
+            packet: packet.as_slice().to_vec(),
+            options,
+        };
+        self.file.write(block.compose(self.endianness).as_slice())?;
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        self.file.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::{ReadOnlyCapture, WriteOnlyCapture};
+    use crate::capture_header::CaptureHeader;
+    use crate::error::Error;
+    use crate::packet::Packet;
+    use crate::packet_header::PacketHeader;
+    use crate::test_util::{get_path_for_new_temp_file, write_temp_file};
+
+    const CAPTURE_HEADER: [u8; 24] = [
+        0xa1, 0xb2, 0xc3, 0xd4, 0x00, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+    ];
+
+    const RECORD: [u8; 20] = [
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
+        0x04, 0xde, 0xad, 0xbe, 0xef,
+    ];
+
+    #[test]
+    fn reading_until_end_of_capture_succeeds() {
+        let file_path = write_temp_file(&[&CAPTURE_HEADER[..], &RECORD, &RECORD].concat());
+        let (_, mut capture) = ReadOnlyCapture::open(&file_path).unwrap();
+
+        let (_, packet) = capture.get().unwrap().unwrap();
+        assert_eq!(packet.as_slice(), &RECORD[16..]);
+        capture.get().unwrap().unwrap();
+        assert!(capture.get().unwrap().is_none());
+    }
+
+    #[test]
+    fn reading_records_in_place_succeeds() {
+        let second_record = [&RECORD[..16], &[0xEE; 4]].concat();
+        let file_path = write_temp_file(&[&CAPTURE_HEADER[..], &RECORD, &second_record].concat());
+        let (_, mut capture) = ReadOnlyCapture::open(&file_path).unwrap();
+
+        let first_position = capture.position();
+        capture.next_record().unwrap().unwrap();
+        assert_eq!(capture.packet().as_slice(), &RECORD[16..]);
+        capture.next_record().unwrap().unwrap();
+        assert_eq!(capture.packet().as_slice(), [0xEE; 4]);
+        assert!(capture.next_record().unwrap().is_none());
+
+        capture.record_at(first_position).unwrap();
+        assert_eq!(capture.packet().as_slice(), &RECORD[16..]);
+    }
+
+    #[test]
+    fn finding_chunk_boundaries_succeeds() {
+        // The packet of the second record looks like a record header followed by its packet
+        let second_record = [
+            &[0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 20, 0, 0, 0, 20][..],
+            &RECORD,
+        ]
+        .concat();
+        let file_path = write_temp_file(
+            &[
+                &CAPTURE_HEADER[..],
+                &RECORD,
+                &second_record,
+                &RECORD,
+                &RECORD[..18],
+            ]
+            .concat(),
+        );
+        let (_, capture) = ReadOnlyCapture::open(&file_path).unwrap();
+        let records = capture.mapped_records().unwrap();
+
+        assert_eq!(records.chunk_boundaries(1), [24, 44, 80, 100, 118]);
+        assert_eq!(records.chunk_boundaries(30), [24, 80, 118]);
+        assert_eq!(records.chunk_boundaries(1000), [24, 118]);
+    }
+
+    #[test]
+    fn reading_truncated_record_header_fails() {
+        let file_path = write_temp_file(&[&CAPTURE_HEADER[..], &RECORD, &RECORD[..10]].concat());
+        let (_, mut capture) = ReadOnlyCapture::open(&file_path).unwrap();
+
+        capture.get().unwrap().unwrap();
+        assert!(matches!(
+            capture.get(),
+            Err(Error::TruncatedRecordHeader(44))
+        ));
+    }
+
+    #[test]
+    fn reading_truncated_record_fails() {
+        let file_path = write_temp_file(&[&CAPTURE_HEADER[..], &RECORD[..18]].concat());
+        let (_, mut capture) = ReadOnlyCapture::open(&file_path).unwrap();
+
+        assert!(matches!(capture.get(), Err(Error::TruncatedRecord(24))));
+    }
+
+    #[test]
+    fn reading_written_compressed_capture_succeeds() {
+        let header = CaptureHeader::parse(&CAPTURE_HEADER).unwrap();
+        let packet_header =
+            PacketHeader::parse(&RECORD, header.endianness, header.timestamp_precision);
+
+        for extension in ["gz", "zst", "xz"] {
+            let file_path = format!("{}.pcap.{extension}", get_path_for_new_temp_file());
+            let mut destination = WriteOnlyCapture::create(&file_path, header).unwrap();
+            destination
+                .put(packet_header, &Packet::from(RECORD[16..].to_vec()))
+                .unwrap();
+            destination.finish().unwrap();
+
+            let (read_header, mut capture) = ReadOnlyCapture::open(&file_path).unwrap();
+            assert_eq!(read_header.link_layer_type, header.link_layer_type);
+            let (read_packet_header, packet) = capture.get().unwrap().unwrap();
+            assert_eq!(read_packet_header, packet_header);
+            assert_eq!(packet.as_slice(), &RECORD[16..]);
+            assert!(capture.get().unwrap().is_none());
+        }
+    }
 }
@@ -1,43 +1,228 @@
+use std::borrow::Cow;
+use std::fmt::{Debug, Formatter};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{Cursor, ErrorKind, Read, Write};
 use std::path::Path;
+use std::sync::Arc;
 
+use memmap2::Mmap;
+
+use crate::compression::{self, Compression, FileWriter};
 use crate::error::Error;
+use crate::spill::SpillFile;
+
+/// Path that stands for standard input
+pub const STDIN_PATH: &str = "-";
 
+/// Capture file opened for reading
+///
+/// Regular files are memory-mapped, so that reads borrow from the mapping instead of copying.
+/// Other files, such as named pipes, standard input and compressed files, are read into owned
+/// buffers and copied into a spool file as they are read, so that they can be read again.
 #[derive(Debug)]
 pub struct ReadFile {
-    inner: File,
+    inner: Source,
+    position: u64,
+}
+
+enum Source {
+    Stream {
+        reader: Box<dyn Read + Send>,
+        spool: Arc<SpillFile>,
+        /// Number of bytes read from the stream, all of which are in the spool file
+        spooled: u64,
+    },
+    Mapped(Mmap),
+}
+
+impl Debug for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stream { spooled, .. } => write!(f, "Stream {{ spooled: {spooled} }}"),
+            Self::Mapped(mapping) => write!(f, "Mapped({mapping:?})"),
+        }
+    }
 }
 
+/// Capture file opened for writing, compressed if its path has the extension of a compression
+/// format
 #[derive(Debug)]
 pub struct WriteFile {
-    inner: File,
+    inner: FileWriter,
 }
 
 impl ReadFile {
+    /// Opens the file at `path`, or standard input if `path` is [`STDIN_PATH`]
     pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
-        let file = File::open(path).map_err(Error::OpenCaptureFile)?;
+        if path.as_ref() == Path::new(STDIN_PATH) {
+            return Self::from_stream(Box::new(std::io::stdin()));
+        }
+
+        let mut file = File::open(path).map_err(Error::OpenCaptureFile)?;
+        let metadata = file.metadata().map_err(Error::OpenCaptureFile)?;
+
+        if !metadata.is_file() || metadata.len() == 0 {
+            return Self::from_stream(Box::new(file));
+        }
 
-        let capture_file = Self { inner: file };
+        let mut magic = Vec::with_capacity(Compression::MAGIC_LENGTH);
+        Read::by_ref(&mut file)
+            .take(Compression::MAGIC_LENGTH as u64)
+            .read_to_end(&mut magic)
+            .map_err(Error::OpenCaptureFile)?;
+        if Compression::detect(&magic).is_some() {
+            return Self::from_stream(Box::new(Cursor::new(magic).chain(file)));
+        }
+
+        // SAFETY: the mapping is read-only. Captures are not expected to be modified while they
+        // are open, the same assumption the buffered reads of other tools make.
+        let mapping = unsafe { Mmap::map(&file) }.map_err(Error::OpenCaptureFile)?;
+        let inner = Source::Mapped(mapping);
+
+        let capture_file = Self { inner, position: 0 };
 
         Ok(capture_file)
     }
 
-    pub fn read(&mut self, size: usize) -> Result<Vec<u8>, Error> {
+    /// Reads the capture from `reader`, decompressing it if it is compressed, and copies what
+    /// has been read to a spool file
+    pub fn from_stream(reader: Box<dyn Read + Send>) -> Result<Self, Error> {
+        let inner = Source::Stream {
+            reader: compression::decompress(reader)?,
+            spool: Arc::new(SpillFile::create()?),
+            spooled: 0,
+        };
+
+        let capture_file = Self { inner, position: 0 };
+
+        Ok(capture_file)
+    }
+
+    /// Spool file holding what has been read of a file that is not memory-mapped
+    pub fn spool(&self) -> Option<&Arc<SpillFile>> {
+        match &self.inner {
+            Source::Stream { spool, .. } => Some(spool),
+            Source::Mapped(_) => None,
+        }
+    }
+
+    pub fn read(&mut self, size: usize) -> Result<Cow<'_, [u8]>, Error> {
+        self.read_or_eof(size)?.ok_or(Error::UnexpectedEndOfFile)
+    }
+
+    /// Reads `size` bytes, returning `None` if the file ends before any of them could be read
+    ///
+    /// Fails with [`Error::UnexpectedEndOfFile`] if the file ends after some of them were read.
+    /// Bytes of memory-mapped files are borrowed from the mapping. Bytes of streams are read back
+    /// from the spool file if they were read before.
+    pub fn read_or_eof(&mut self, size: usize) -> Result<Option<Cow<'_, [u8]>>, Error> {
+        let (reader, spool, spooled) = match &mut self.inner {
+            Source::Stream {
+                reader,
+                spool,
+                spooled,
+            } => (reader, spool, spooled),
+            Source::Mapped(mapping) => {
+                let start = usize::try_from(self.position)
+                    .unwrap_or(usize::MAX)
+                    .min(mapping.len());
+                let end = start.saturating_add(size).min(mapping.len());
+                self.position = end as u64;
+
+                return if end - start == size {
+                    Ok(Some(Cow::Borrowed(&mapping[start..end])))
+                } else if end == start {
+                    Ok(None)
+                } else {
+                    Err(Error::UnexpectedEndOfFile)
+                };
+            }
+        };
+
         let mut buffer: Vec<u8> = vec![0; size];
-        self.inner
-            .read_exact(buffer.as_mut_slice())
-            .map_err(Error::CaptureFileRead)?;
+        let mut filled = 0;
 
-        Ok(buffer)
+        if self.position < *spooled {
+            filled = size.min((*spooled - self.position) as usize);
+            buffer[..filled].copy_from_slice(&spool.read(self.position, filled)?);
+        }
+        let spooled_length = filled;
+
+        while filled < size {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(Error::CaptureFileRead(e)),
+            }
+        }
+
+        if filled > spooled_length {
+            spool.append(&buffer[spooled_length..filled])?;
+            *spooled += (filled - spooled_length) as u64;
+        }
+        self.position += filled as u64;
+
+        if filled == size {
+            Ok(Some(Cow::Owned(buffer)))
+        } else if filled == 0 {
+            Ok(None)
+        } else {
+            Err(Error::UnexpectedEndOfFile)
+        }
+    }
+
+    /// Bytes at `position` of a memory-mapped file, or `None` if the file is not mapped or too
+    /// short
+    pub fn mapped(&self, position: u64, size: usize) -> Option<&[u8]> {
+        let start = usize::try_from(position).ok()?;
+        self.mapping()?.get(start..start.checked_add(size)?)
+    }
+
+    /// Whole content of a memory-mapped file, or `None` if the file is not mapped
+    pub fn mapping(&self) -> Option<&[u8]> {
+        match &self.inner {
+            Source::Stream { .. } => None,
+            Source::Mapped(mapping) => Some(mapping),
+        }
+    }
+
+    pub const fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Moves to `position`, which for streams means reading up to it if it has not been read
+    /// yet
+    pub fn seek(&mut self, position: u64) -> Result<(), Error> {
+        if let Source::Stream { spooled, .. } = self.inner {
+            if position > spooled {
+                self.position = spooled;
+                let skipped = usize::try_from(position - spooled).unwrap_or(usize::MAX);
+                self.read(skipped)?;
+            }
+        }
+
+        self.position = position;
+
+        Ok(())
+    }
+}
+
+/// Replaces an unexpected end of file with `truncation`, keeping other errors as they are
+pub fn on_end_of_file(error: Error, truncation: Error) -> Error {
+    match error {
+        Error::UnexpectedEndOfFile => truncation,
+        error => error,
     }
 }
 
 impl WriteFile {
     pub fn create(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let compression = Compression::from_extension(&path);
         let file = File::create(path).map_err(Error::CaptureFileCreate)?;
+        let inner = FileWriter::new(file, compression).map_err(Error::CaptureFileCreate)?;
 
-        let capture_file = Self { inner: file };
+        let capture_file = Self { inner };
 
         Ok(capture_file)
     }
@@ -49,16 +234,24 @@ impl WriteFile {
 
         Ok(())
     }
+
+    /// Finishes writing the file, which completes compressed files
+    pub fn finish(self) -> Result<(), Error> {
+        self.inner.finish().map_err(Error::CaptureFileWrite)
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use std::borrow::Cow;
     use std::fs::File;
     use std::io::{Read, Write};
-
-    use rand::distr::{Alphanumeric, DistString};
+    use std::process::Command;
+    use std::thread;
 
     use crate::capture_file::{ReadFile, WriteFile};
+    use crate::error::Error;
+    use crate::test_util::get_path_for_new_temp_file;
 
     const PAYLOAD_LENGTH: usize = 32;
 
@@ -81,7 +274,99 @@ mod test {
         let mut capture_file = ReadFile::open(&file_path).unwrap();
         let buffer = capture_file.read(PAYLOAD_LENGTH).unwrap();
 
-        assert_eq!(buffer, payload);
+        assert_eq!(*buffer, payload);
+    }
+
+    #[test]
+    fn reading_past_end_of_file_fails() {
+        let file_path = get_path_for_new_temp_file();
+        let mut file = File::create(&file_path).unwrap();
+
+        let payload: [u8; PAYLOAD_LENGTH] = rand::random();
+        file.write_all(&payload).unwrap();
+
+        let mut capture_file = ReadFile::open(&file_path).unwrap();
+        capture_file.read(PAYLOAD_LENGTH - 8).unwrap();
+
+        assert!(matches!(
+            capture_file.read(16),
+            Err(Error::UnexpectedEndOfFile)
+        ));
+        assert!(capture_file.read_or_eof(16).unwrap().is_none());
+    }
+
+    #[test]
+    fn seek_succeeds() {
+        let file_path = get_path_for_new_temp_file();
+        let mut file = File::create(&file_path).unwrap();
+
+        let payload: [u8; PAYLOAD_LENGTH] = rand::random();
+        file.write_all(&payload).unwrap();
+
+        let mut capture_file = ReadFile::open(&file_path).unwrap();
+        capture_file.read(PAYLOAD_LENGTH).unwrap();
+        assert_eq!(capture_file.position(), PAYLOAD_LENGTH as u64);
+
+        capture_file.seek(8).unwrap();
+        let buffer = capture_file.read(PAYLOAD_LENGTH - 8).unwrap();
+
+        assert_eq!(*buffer, payload[8..]);
+        assert_eq!(capture_file.position(), PAYLOAD_LENGTH as u64);
+    }
+
+    #[test]
+    fn reading_named_pipe_succeeds() {
+        let file_path = get_path_for_new_temp_file();
+        let status = Command::new("mkfifo").arg(&file_path).status().unwrap();
+        assert!(status.success());
+
+        let payload: [u8; PAYLOAD_LENGTH] = rand::random();
+        let writer_path = file_path.clone();
+        let writer = thread::spawn(move || {
+            File::create(writer_path)
+                .unwrap()
+                .write_all(&payload)
+                .unwrap();
+        });
+
+        let mut capture_file = ReadFile::open(&file_path).unwrap();
+        let buffer = capture_file.read(PAYLOAD_LENGTH).unwrap();
+        writer.join().unwrap();
+
+        assert!(matches!(buffer, Cow::Owned(_)));
+        assert_eq!(*buffer, payload);
+        assert!(capture_file.read_or_eof(1).unwrap().is_none());
+        assert!(capture_file.mapped(0, 1).is_none());
+    }
+
+    #[test]
+    fn seeking_back_in_named_pipe_succeeds() {
+        let file_path = get_path_for_new_temp_file();
+        let status = Command::new("mkfifo").arg(&file_path).status().unwrap();
+        assert!(status.success());
+
+        let payload: [u8; PAYLOAD_LENGTH] = rand::random();
+        let writer_path = file_path.clone();
+        let writer = thread::spawn(move || {
+            File::create(writer_path)
+                .unwrap()
+                .write_all(&payload)
+                .unwrap();
+        });
+
+        let mut capture_file = ReadFile::open(&file_path).unwrap();
+        capture_file.read(8).unwrap();
+        capture_file.seek(4).unwrap();
+        let buffer = capture_file.read(8).unwrap();
+        assert_eq!(*buffer, payload[4..12]);
+
+        capture_file.seek(16).unwrap();
+        let buffer = capture_file.read(PAYLOAD_LENGTH - 16).unwrap();
+        assert_eq!(*buffer, payload[16..]);
+        writer.join().unwrap();
+
+        let spool = capture_file.spool().unwrap();
+        assert_eq!(spool.read(0, PAYLOAD_LENGTH).unwrap(), payload);
     }
 
     #[test]
@@ -108,11 +393,4 @@ mod test {
 
         assert_eq!(buffer, payload);
     }
-
-    fn get_path_for_new_temp_file() -> String {
-        format!(
-            "/tmp/{}",
-            Alphanumeric.sample_string(&mut rand::thread_rng(), 20)
-        )
-    }
 }
@@ -5,6 +5,7 @@ use crate::endianness_aware_cursor::{
     Endianness, ReadOnlyEndiannessAwareCursor, WriteOnlyEndiannessAwareCursor,
 };
 use crate::error::Error;
+use crate::flow_index::{ReadOnlyIndexCursor, WriteOnlyIndexCursor};
 use crate::packet_layer::LinkLayerType;
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
@@ -50,10 +51,8 @@ impl CaptureHeader {
 
         let fcs_link_layer_type = cursor.get_u32();
         let (frame_cyclic_sequence, link_layer_type) = {
-            let link_layer_type = match fcs_link_layer_type & Self::LINK_LAYER_TYPE_MASK {
-                1 => LinkLayerType::En10Mb,
-                link_layer_type => return Err(Error::UnknownLinkLayerType(link_layer_type)),
-            };
+            let link_layer_type =
+                LinkLayerType::try_from(fcs_link_layer_type & Self::LINK_LAYER_TYPE_MASK)?;
 
             let frame_cyclic_sequence =
                 if (fcs_link_layer_type & Self::FRAME_CYCLIC_SEQUENCE_FLAG_MASK) != 0 {
@@ -101,9 +100,7 @@ impl CaptureHeader {
 
         cursor.put_u32(self.maximum_packet_length.0);
 
-        let link_layer_type = match self.link_layer_type {
-            LinkLayerType::En10Mb => 1,
-        };
+        let link_layer_type = u32::from(self.link_layer_type);
         let frame_cyclic_sequence = self
             .frame_cyclic_sequence
             .map_or(0, |sequence| (sequence.0 as u32).shl(29) | 1u32.shl(28));
@@ -112,6 +109,49 @@ impl CaptureHeader {
 
         cursor.into_vec()
     }
+
+    /// Reads a capture header written by [`Self::write_index`] from a flow index
+    pub fn read_index(cursor: &mut ReadOnlyIndexCursor) -> Result<Self, Error> {
+        let endianness = if cursor.get_bool()? {
+            Endianness::Swapped
+        } else {
+            Endianness::Identical
+        };
+        let timestamp_precision = cursor.get_timestamp_precision()?;
+        let version = Version(cursor.get_u16()?, cursor.get_u16()?);
+        let maximum_packet_length = MaximumPacketLength(cursor.get_u32()?);
+        let frame_cyclic_sequence = if cursor.get_bool()? {
+            Some(FrameCyclicSequence(cursor.get_u8()?))
+        } else {
+            None
+        };
+        let link_layer_type =
+            LinkLayerType::try_from(cursor.get_u32()?).map_err(|_| Error::InvalidIndexFile)?;
+
+        let capture_header = Self {
+            endianness,
+            timestamp_precision,
+            version,
+            maximum_packet_length,
+            frame_cyclic_sequence,
+            link_layer_type,
+        };
+
+        Ok(capture_header)
+    }
+
+    pub fn write_index(&self, cursor: &mut WriteOnlyIndexCursor) {
+        cursor.put_bool(self.endianness == Endianness::Swapped);
+        cursor.put_timestamp_precision(self.timestamp_precision);
+        cursor.put_u16(self.version.0);
+        cursor.put_u16(self.version.1);
+        cursor.put_u32(self.maximum_packet_length.0);
+        cursor.put_bool(self.frame_cyclic_sequence.is_some());
+        if let Some(frame_cyclic_sequence) = self.frame_cyclic_sequence {
+            cursor.put_u8(frame_cyclic_sequence.0);
+        }
+        cursor.put_u32(u32::from(self.link_layer_type));
+    }
 }
 
 impl Display for CaptureHeader {
@@ -130,6 +170,16 @@ pub enum TimestampPrecision {
     Nano,
 }
 
+impl TimestampPrecision {
+    /// Number of nanoseconds in one unit of the fraction of a second of a record timestamp
+    pub const fn nanos_per_unit(self) -> u32 {
+        match self {
+            Self::Micro => 1_000,
+            Self::Nano => 1,
+        }
+    }
+}
+
 impl Display for TimestampPrecision {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -298,7 +348,7 @@ mod tests {
     fn parsing_capture_header_with_garbage_link_layer_type_fails() {
         const PCAP_BYTE_ARRAY: [u8; CaptureHeader::LENGTH] = [
             0xd4, 0xc3, 0xb2, 0xa1, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0xF0,
         ];
 
         let result = CaptureHeader::parse(&PCAP_BYTE_ARRAY);
@@ -306,6 +356,33 @@ mod tests {
         assert_err!(result);
     }
 
+    #[test]
+    fn composing_and_parsing_link_layer_types_succeeds() {
+        for link_layer_type in [
+            LinkLayerType::Null,
+            LinkLayerType::En10Mb,
+            LinkLayerType::Raw,
+            LinkLayerType::Ieee80211Radiotap,
+            LinkLayerType::LinuxSll,
+            LinkLayerType::Ipv4,
+            LinkLayerType::Ipv6,
+            LinkLayerType::LinuxSll2,
+        ] {
+            let header = CaptureHeader {
+                endianness: Endianness::Swapped,
+                timestamp_precision: TimestampPrecision::Micro,
+                version: Version(2, 4),
+                maximum_packet_length: MaximumPacketLength(262_144),
+                frame_cyclic_sequence: None,
+                link_layer_type,
+            };
+
+            let parsed_header = CaptureHeader::parse(&header.compose()).unwrap();
+
+            assert_eq!(parsed_header.link_layer_type, link_layer_type);
+        }
+    }
+
     #[test]
     fn composing_capture_header_with_identical_endianness_and_microsecond_timestamp_precision_succeeds(
     ) {
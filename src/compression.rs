@@ -0,0 +1,216 @@
+use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
+
+use crate::error::Error;
+
+/// Compression formats captures are read from and written in
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// Number of bytes needed to recognise every format by its magic bytes
+    pub const MAGIC_LENGTH: usize = 6;
+
+    const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+    const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+    const XZ_MAGIC: [u8; 6] = [0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00];
+
+    const ZSTD_LEVEL: i32 = 3;
+    const XZ_PRESET: u32 = 6;
+
+    /// Recognises the format of data starting with `magic`
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(&Self::GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if magic.starts_with(&Self::ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else if magic.starts_with(&Self::XZ_MAGIC) {
+            Some(Self::Xz)
+        } else {
+            None
+        }
+    }
+
+    /// Recognises the format from the extension of `path`
+    pub fn from_extension(path: impl AsRef<Path>) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "gz" => Some(Self::Gzip),
+            "zst" => Some(Self::Zstd),
+            "xz" => Some(Self::Xz),
+            _ => None,
+        }
+    }
+
+    fn decoder(self, reader: Box<dyn Read + Send>) -> Result<Box<dyn Read + Send>, Error> {
+        let decoder: Box<dyn Read + Send> = match self {
+            Self::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Self::Zstd => {
+                Box::new(zstd::stream::read::Decoder::new(reader).map_err(Error::OpenCaptureFile)?)
+            }
+            Self::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+        };
+
+        Ok(decoder)
+    }
+}
+
+/// Wraps `reader` in a decoder if what it reads starts with the magic bytes of a compression
+/// format, or returns a reader of the same data otherwise
+pub fn decompress(mut reader: Box<dyn Read + Send>) -> Result<Box<dyn Read + Send>, Error> {
+    let mut magic = Vec::with_capacity(Compression::MAGIC_LENGTH);
+    Read::by_ref(&mut reader)
+        .take(Compression::MAGIC_LENGTH as u64)
+        .read_to_end(&mut magic)
+        .map_err(Error::CaptureFileRead)?;
+
+    let compression = Compression::detect(&magic);
+    let reader = Box::new(Cursor::new(magic).chain(reader));
+
+    match compression {
+        Some(compression) => compression.decoder(reader),
+        None => Ok(reader),
+    }
+}
+
+/// Whether `file_name` has the extension of a capture, optionally followed by the extension of
+/// a compression format
+pub fn is_capture_file_name(file_name: impl AsRef<Path>) -> bool {
+    let file_name = file_name.as_ref();
+    let file_name = match Compression::from_extension(file_name) {
+        Some(_) => Path::new(file_name.file_stem().unwrap_or_default()),
+        None => file_name,
+    };
+
+    file_name
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| matches!(extension, "cap" | "pcap" | "pcapng"))
+}
+
+/// File written to as is, or compressed
+pub enum FileWriter {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::stream::write::Encoder<'static, File>),
+    Xz(XzEncoder<File>),
+}
+
+impl FileWriter {
+    pub fn new(file: File, compression: Option<Compression>) -> std::io::Result<Self> {
+        let writer = match compression {
+            None => Self::Plain(file),
+            Some(Compression::Gzip) => {
+                Self::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Some(Compression::Zstd) => Self::Zstd(zstd::stream::write::Encoder::new(
+                file,
+                Compression::ZSTD_LEVEL,
+            )?),
+            Some(Compression::Xz) => Self::Xz(XzEncoder::new(file, Compression::XZ_PRESET)),
+        };
+
+        Ok(writer)
+    }
+
+    /// Writes the end of the compressed data, which is left out if the writer is only dropped
+    pub fn finish(self) -> std::io::Result<()> {
+        let mut file = match self {
+            Self::Plain(file) => file,
+            Self::Gzip(encoder) => encoder.finish()?,
+            Self::Zstd(encoder) => encoder.finish()?,
+            Self::Xz(encoder) => encoder.finish()?,
+        };
+
+        file.flush()
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(file) => file.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+            Self::Xz(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(file) => file.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+            Self::Xz(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl Debug for FileWriter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plain(file) => write!(f, "Plain({file:?})"),
+            Self::Gzip(_) => write!(f, "Gzip"),
+            Self::Zstd(_) => write!(f, "Zstd"),
+            Self::Xz(_) => write!(f, "Xz"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{Read, Write};
+
+    use crate::compression::{decompress, is_capture_file_name, Compression, FileWriter};
+    use crate::test_util::get_path_for_new_temp_file;
+
+    #[test]
+    fn decompressing_compressed_files_succeeds() {
+        let payload = b"\xa1\xb2\xc3\xd4 captured packets".repeat(64);
+
+        for extension in ["gz", "zst", "xz"] {
+            let file_path = format!("{}.pcap.{extension}", get_path_for_new_temp_file());
+            let compression = Compression::from_extension(&file_path);
+            assert!(compression.is_some());
+
+            let mut writer =
+                FileWriter::new(File::create(&file_path).unwrap(), compression).unwrap();
+            writer.write_all(&payload).unwrap();
+            writer.finish().unwrap();
+
+            let mut compressed = Vec::new();
+            File::open(&file_path)
+                .unwrap()
+                .read_to_end(&mut compressed)
+                .unwrap();
+            assert_eq!(Compression::detect(&compressed), compression);
+
+            let mut decompressed = Vec::new();
+            decompress(Box::new(File::open(&file_path).unwrap()))
+                .unwrap()
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, payload);
+        }
+    }
+
+    #[test]
+    fn recognising_capture_file_names_succeeds() {
+        assert!(is_capture_file_name("http.pcap"));
+        assert!(is_capture_file_name("http.pcapng.zst"));
+        assert!(is_capture_file_name("http.cap.gz"));
+        assert!(!is_capture_file_name("http.gz"));
+        assert!(!is_capture_file_name("notes.txt"));
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt::{Display, Formatter};
 
 use bytes::{Buf, BufMut};
 
+#[derive(Clone)]
 pub struct ReadOnlyEndiannessAwareCursor<'a> {
     buffer: &'a [u8],
     endianness: Endianness,
@@ -20,6 +21,22 @@ impl<'a> ReadOnlyEndiannessAwareCursor<'a> {
         self.buffer.advance(advancement);
     }
 
+    pub fn remaining(&self) -> usize {
+        self.buffer.remaining()
+    }
+
+    pub fn get_slice(&mut self, length: usize) -> &'a [u8] {
+        let (slice, rest) = self.buffer.split_at(length);
+        self.buffer = rest;
+
+        slice
+    }
+
+    /// Returns the next byte without advancing past it
+    pub fn peek_u8(&self) -> u8 {
+        self.buffer[0]
+    }
+
     pub fn get_u8(&mut self) -> u8 {
         self.buffer.get_u8()
     }
@@ -76,6 +93,11 @@ impl WriteOnlyEndiannessAwareCursor {
         self.buffer
     }
 
+    pub fn put_slice(&mut self, value: &[u8]) {
+        self.buffer.put_slice(value);
+    }
+
+    #[allow(dead_code)]
     pub fn put_u8(&mut self, value: u8) {
         self.buffer.put_u8(value);
     }
@@ -101,6 +123,7 @@ impl WriteOnlyEndiannessAwareCursor {
         }
     }
 
+    #[allow(dead_code)]
     pub fn put_u128(&mut self, value: u128) {
         match self.endianness {
             Endianness::Identical => self.buffer.put_u128(value),
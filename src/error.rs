@@ -5,25 +5,115 @@ use std::io::Error as IoError;
 pub enum Error {
     OpenCaptureFile(IoError),
     CaptureFileRead(IoError),
+    UnexpectedEndOfFile,
+    TruncatedRecordHeader(u64),
+    TruncatedRecord(u64),
+    CaptureFileSeek(IoError),
     CaptureFileCreate(IoError),
     CaptureFileWrite(IoError),
+    CreateOutputDirectory(IoError),
+    WriteStream(IoError),
+    SpillFileCreate(IoError),
+    SpillFileWrite(IoError),
+    SpillFileRead(IoError),
+    IndexFileRead(IoError),
+    IndexFileWrite(IoError),
+    InvalidIndexFile,
+    UnknownNetworkInterface,
+    OpenNetworkInterface(IoError),
+    UnsupportedLiveCapture,
     UnknownMagicNumber(u32),
+    UnknownByteOrderMagic(u32),
+    InvalidBlockLength(u32),
+    UnknownInterface(u32),
     UnknownLinkLayerType(u32),
     TuiSetup(IoError),
     TuiDraw(IoError),
     TuiTeardown(IoError),
     UnknownNetworkLayerType(u16),
     UnknownTransportLayerType(u8),
-    UnknownIPv6AdditionalHeaderLength(u8),
+    TruncatedPacket,
+    UnknownAddressFamily(u32),
+    UnknownIpVersion(u8),
+    UnsupportedWirelessFrame,
+    UnsupportedGreVersion(u16),
+    UnreassembledFragment,
     ReadEnv(IoError),
     ReadDirContent(IoError),
     TuiReadInput(IoError),
     FileTypeConversion,
     NoFileNameInPath,
+    InvalidGlobPattern(String),
+    NoCaptureFiles,
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "DUMMY ERROR - YOU SHOULDN'T BE SEEING THIS")
+        match self {
+            Self::OpenCaptureFile(e) => write!(f, "could not open capture file: {e}"),
+            Self::CaptureFileRead(e) => write!(f, "could not read capture file: {e}"),
+            Self::UnexpectedEndOfFile => write!(f, "unexpected end of capture file"),
+            Self::TruncatedRecordHeader(offset) => {
+                write!(f, "record header at offset {offset} is truncated")
+            }
+            Self::TruncatedRecord(offset) => write!(f, "record at offset {offset} is truncated"),
+            Self::CaptureFileSeek(e) => write!(f, "could not seek in capture file: {e}"),
+            Self::CaptureFileCreate(e) => write!(f, "could not create capture file: {e}"),
+            Self::CaptureFileWrite(e) => write!(f, "could not write capture file: {e}"),
+            Self::CreateOutputDirectory(e) => write!(f, "could not create output directory: {e}"),
+            Self::WriteStream(e) => write!(f, "could not write stream file: {e}"),
+            Self::SpillFileCreate(e) => write!(f, "could not create spill file: {e}"),
+            Self::SpillFileWrite(e) => write!(f, "could not write spill file: {e}"),
+            Self::SpillFileRead(e) => write!(f, "could not read spill file: {e}"),
+            Self::IndexFileRead(e) => write!(f, "could not read flow index file: {e}"),
+            Self::IndexFileWrite(e) => write!(f, "could not write flow index file: {e}"),
+            Self::InvalidIndexFile => write!(f, "flow index file is corrupt"),
+            Self::UnknownNetworkInterface => write!(f, "no network interface with that name"),
+            Self::OpenNetworkInterface(e) => {
+                write!(f, "could not capture on network interface: {e}")
+            }
+            Self::UnsupportedLiveCapture => {
+                write!(f, "live capture is only supported on Linux")
+            }
+            Self::UnknownMagicNumber(magic_number) => {
+                write!(f, "unknown magic number {magic_number:#010X}")
+            }
+            Self::UnknownByteOrderMagic(byte_order_magic) => {
+                write!(f, "unknown byte order magic {byte_order_magic:#010X}")
+            }
+            Self::InvalidBlockLength(length) => write!(f, "invalid block length {length}"),
+            Self::UnknownInterface(interface_id) => write!(f, "unknown interface {interface_id}"),
+            Self::UnknownLinkLayerType(link_layer_type) => {
+                write!(f, "unknown link layer type {link_layer_type}")
+            }
+            Self::TuiSetup(e) => write!(f, "could not set up terminal: {e}"),
+            Self::TuiDraw(e) => write!(f, "could not draw to terminal: {e}"),
+            Self::TuiTeardown(e) => write!(f, "could not restore terminal: {e}"),
+            Self::UnknownNetworkLayerType(network_layer_type) => {
+                write!(f, "unknown network layer type {network_layer_type:#06X}")
+            }
+            Self::UnknownTransportLayerType(transport_layer_type) => {
+                write!(f, "unknown transport layer type {transport_layer_type}")
+            }
+            Self::TruncatedPacket => write!(f, "packet is too short for its headers"),
+            Self::UnknownAddressFamily(address_family) => {
+                write!(f, "unknown loopback address family {address_family}")
+            }
+            Self::UnknownIpVersion(version) => write!(f, "unknown IP version {version}"),
+            Self::UnsupportedWirelessFrame => {
+                write!(f, "802.11 frame is not an unencrypted data frame")
+            }
+            Self::UnsupportedGreVersion(version) => write!(f, "unsupported GRE version {version}"),
+            Self::UnreassembledFragment => {
+                write!(f, "fragment of a datagram that could not be reassembled")
+            }
+            Self::ReadEnv(e) => write!(f, "could not read environment: {e}"),
+            Self::ReadDirContent(e) => write!(f, "could not read directory: {e}"),
+            Self::TuiReadInput(e) => write!(f, "could not read terminal input: {e}"),
+            Self::FileTypeConversion => write!(f, "unsupported file type"),
+            Self::NoFileNameInPath => write!(f, "path does not contain a file name"),
+            Self::InvalidGlobPattern(pattern) => write!(f, "invalid glob pattern {pattern}"),
+            Self::NoCaptureFiles => write!(f, "no capture files found"),
+        }
     }
 }
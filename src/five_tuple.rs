@@ -1,77 +1,236 @@
 use std::fmt::{Display, Formatter};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
+use crate::error::Error;
+use crate::flow_index::{ReadOnlyIndexCursor, WriteOnlyIndexCursor};
 use crate::packet_dissection::{NetworkLayer, PacketDissection, TransportLayer};
+use crate::packet_layer::TransportLayerType;
 
+/// Key of a flow, identical for packets of both directions
+///
+/// TCP and UDP flows are keyed by their addresses and ports, ICMP flows by their addresses,
+/// message type, code and identifier. VLAN IDs are part of the key only if requested.
 #[derive(Eq, PartialEq, Hash, Clone)]
 pub struct FiveTuple {
     lower_addr: IpAddr,
-    lower_port: u16,
     higher_addr: IpAddr,
-    higher_port: u16,
-    is_tcp: bool,
+    protocol: TransportLayerType,
+    identifiers: FlowIdentifiers,
+    vlan_ids: Vec<u16>,
+}
+
+#[derive(Eq, PartialEq, Hash, Clone)]
+enum FlowIdentifiers {
+    Ports {
+        lower_port: u16,
+        higher_port: u16,
+    },
+    Icmp {
+        message_type: u8,
+        code: u8,
+        identifier: u16,
+    },
 }
 
 impl FiveTuple {
-    pub fn from_packet_dissection(dissection: &PacketDissection) -> Self {
-        let (is_source_lower, lower_addr, higher_addr) = match dissection.network_layer {
-            NetworkLayer::IPv4(source, destination, _) => {
-                let (lower_addr, higher_addr) = if source < destination {
-                    (
-                        IpAddr::V4(Ipv4Addr::from(source)),
-                        IpAddr::V4(Ipv4Addr::from(destination)),
-                    )
-                } else {
-                    (
-                        IpAddr::V4(Ipv4Addr::from(destination)),
-                        IpAddr::V4(Ipv4Addr::from(source)),
-                    )
-                };
+    pub fn from_packet_dissection(dissection: &PacketDissection, include_vlan_ids: bool) -> Self {
+        let (source, destination, protocol) = match dissection.network_layer {
+            NetworkLayer::IPv4(source, destination, protocol) => (
+                IpAddr::V4(Ipv4Addr::from(source)),
+                IpAddr::V4(Ipv4Addr::from(destination)),
+                protocol,
+            ),
+            NetworkLayer::IPv6(source, destination, protocol) => (
+                IpAddr::V6(Ipv6Addr::from(source)),
+                IpAddr::V6(Ipv6Addr::from(destination)),
+                protocol,
+            ),
+        };
+        let (source_port, destination_port) = match &dissection.transport_layer {
+            TransportLayer::Udp(source, destination, _)
+            | TransportLayer::Tcp(source, destination, ..) => (*source, *destination),
+            TransportLayer::Icmp(_) => (0, 0),
+        };
 
-                (source < destination, lower_addr, higher_addr)
-            }
-            NetworkLayer::IPv6(source, destination, _) => {
-                let (lower_addr, higher_addr) = if source < destination {
-                    (
-                        IpAddr::V6(Ipv6Addr::from(source)),
-                        IpAddr::V6(Ipv6Addr::from(destination)),
-                    )
+        // Where both ends share an address, as on loopback, their ports tell them apart
+        let is_source_lower = (source, source_port) < (destination, destination_port);
+        let (lower_addr, higher_addr) = if is_source_lower {
+            (source, destination)
+        } else {
+            (destination, source)
+        };
+
+        let identifiers = match &dissection.transport_layer {
+            TransportLayer::Udp(..) | TransportLayer::Tcp(..) => {
+                let (lower_port, higher_port) = if is_source_lower {
+                    (source_port, destination_port)
                 } else {
-                    (
-                        IpAddr::V6(Ipv6Addr::from(destination)),
-                        IpAddr::V6(Ipv6Addr::from(source)),
-                    )
+                    (destination_port, source_port)
                 };
 
-                (source < destination, lower_addr, higher_addr)
+                FlowIdentifiers::Ports {
+                    lower_port,
+                    higher_port,
+                }
             }
+            TransportLayer::Icmp(message) => FlowIdentifiers::Icmp {
+                message_type: message.flow_message_type(),
+                code: message.code,
+                identifier: message.identifier,
+            },
         };
 
-        let (is_tcp, lower_port, higher_port) = match (is_source_lower, &dissection.transport_layer)
-        {
-            (true, TransportLayer::Udp(source, destination, _)) => (false, source, destination),
-            (false, TransportLayer::Udp(source, destination, _)) => (false, destination, source),
-            (true, TransportLayer::Tcp(source, destination, _)) => (true, source, destination),
-            (false, TransportLayer::Tcp(source, destination, _)) => (true, destination, source),
+        let vlan_ids = if include_vlan_ids {
+            dissection.link_layer.vlan_ids().to_vec()
+        } else {
+            Vec::new()
         };
 
         Self {
             lower_addr,
-            lower_port: *lower_port,
             higher_addr,
-            higher_port: *higher_port,
-            is_tcp,
+            protocol,
+            identifiers,
+            vlan_ids,
+        }
+    }
+
+    /// Reads a five tuple written by [`Self::write_index`] from a flow index
+    pub fn read_index(cursor: &mut ReadOnlyIndexCursor) -> Result<Self, Error> {
+        let lower_addr = cursor.get_ip_addr()?;
+        let higher_addr = cursor.get_ip_addr()?;
+        let protocol = cursor.get_transport_layer_type()?;
+
+        let identifiers = if protocol.has_ports() {
+            FlowIdentifiers::Ports {
+                lower_port: cursor.get_u16()?,
+                higher_port: cursor.get_u16()?,
+            }
+        } else {
+            FlowIdentifiers::Icmp {
+                message_type: cursor.get_u8()?,
+                code: cursor.get_u8()?,
+                identifier: cursor.get_u16()?,
+            }
+        };
+
+        let vlan_id_count = cursor.get_usize()?;
+        let vlan_ids = (0..vlan_id_count)
+            .map(|_| cursor.get_u16())
+            .collect::<Result<Vec<u16>, Error>>()?;
+
+        let five_tuple = Self {
+            lower_addr,
+            higher_addr,
+            protocol,
+            identifiers,
+            vlan_ids,
+        };
+
+        Ok(five_tuple)
+    }
+
+    pub fn write_index(&self, cursor: &mut WriteOnlyIndexCursor) {
+        cursor.put_ip_addr(self.lower_addr);
+        cursor.put_ip_addr(self.higher_addr);
+        cursor.put_transport_layer_type(self.protocol);
+
+        match self.identifiers {
+            FlowIdentifiers::Ports {
+                lower_port,
+                higher_port,
+            } => {
+                cursor.put_u16(lower_port);
+                cursor.put_u16(higher_port);
+            }
+            FlowIdentifiers::Icmp {
+                message_type,
+                code,
+                identifier,
+            } => {
+                cursor.put_u8(message_type);
+                cursor.put_u8(code);
+                cursor.put_u16(identifier);
+            }
+        }
+
+        cursor.put_usize(self.vlan_ids.len());
+        for vlan_id in &self.vlan_ids {
+            cursor.put_u16(*vlan_id);
         }
     }
 }
 
 impl Display for FiveTuple {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let protocol = if self.is_tcp { "TCP" } else { "UDP" };
-        write!(
-            f,
-            "[{protocol}] {}:{} ↔ {}:{}",
-            self.lower_addr, self.lower_port, self.higher_addr, self.higher_port
-        )
+        let protocol = self.protocol;
+        match self.identifiers {
+            FlowIdentifiers::Ports {
+                lower_port,
+                higher_port,
+            } => write!(
+                f,
+                "[{protocol}] {}:{lower_port} ↔ {}:{higher_port}",
+                self.lower_addr, self.higher_addr
+            ),
+            FlowIdentifiers::Icmp {
+                message_type,
+                code,
+                identifier,
+            } => write!(
+                f,
+                "[{protocol}] {} ↔ {} type {message_type} code {code} id {identifier}",
+                self.lower_addr, self.higher_addr
+            ),
+        }?;
+
+        if !self.vlan_ids.is_empty() {
+            let vlan_ids = self
+                .vlan_ids
+                .iter()
+                .map(u16::to_string)
+                .collect::<Vec<String>>();
+            write!(f, " on VLAN {}", vlan_ids.join("."))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::five_tuple::FiveTuple;
+    use crate::packet_dissection::{
+        LinkLayer, NetworkLayer, PacketDissection, TcpHeader, TransportLayer,
+    };
+    use crate::packet_layer::{ApplicationLayerType, NetworkLayerType, TransportLayerType};
+
+    #[test]
+    fn keying_both_directions_of_loopback_flows_succeeds() {
+        let dissection = |source_port, destination_port| PacketDissection {
+            link_layer: LinkLayer::Raw(NetworkLayerType::IPv4),
+            tunnel: None,
+            network_layer: NetworkLayer::IPv4(
+                0x7F_00_00_01,
+                0x7F_00_00_01,
+                TransportLayerType::Tcp,
+            ),
+            transport_layer: TransportLayer::Tcp(
+                source_port,
+                destination_port,
+                ApplicationLayerType::OctetArray,
+                TcpHeader::default(),
+            ),
+            payload_length: 0,
+            attributed: false,
+        };
+
+        let request = FiveTuple::from_packet_dissection(&dissection(40000, 80), false);
+        let reply = FiveTuple::from_packet_dissection(&dissection(80, 40000), false);
+        let other = FiveTuple::from_packet_dissection(&dissection(40001, 80), false);
+
+        assert!(request == reply);
+        assert!(request != other);
+        assert_eq!(request.to_string(), reply.to_string());
     }
 }
@@ -0,0 +1,2453 @@
+use std::borrow::Cow;
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::slice::Iter;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use clap::Args;
+
+use crate::capture::{ReadOnlyCapture, WriteOnlyCapture, WriteOnlyPcapngCapture};
+use crate::capture_file::STDIN_PATH;
+use crate::capture_header::CaptureHeader;
+use crate::error::Error;
+use crate::five_tuple::FiveTuple;
+use crate::flow_index::{self, ReadOnlyIndexCursor, WriteOnlyIndexCursor};
+use crate::live::LiveCapture;
+use crate::merge::{MergedCapture, SourceMap};
+use crate::packet_dissection::{Dissection, PacketDissection, TcpFlags, TransportLayer, Tunnel};
+use crate::packet_header::{PacketHeader, Timestamp};
+use crate::packet_layer::TransportLayerType;
+use crate::parallel::{self, DissectedRecord};
+use crate::pcapng::{BlockOption, SectionHeaderBlock};
+use crate::reassembly::{OverlapPolicy, Reassembler};
+use crate::spill::SpillFile;
+use crate::statistics::DirectionStatistics;
+use crate::stream::TcpStream;
+use crate::tcp::{ConnectionEnd, TcpAnalysis, TcpConnection, TcpMetrics};
+
+/// Options controlling how packets are grouped into flows
+#[derive(Args, Copy, Clone, Debug)]
+pub struct FlowOptions {
+    /// Group ICMP error messages into the TCP or UDP flow of the packet they quote
+    #[arg(long, global = true)]
+    pub attribute_icmp_errors: bool,
+    /// Keep packets of otherwise identical flows on different VLANs in separate flows
+    #[arg(long, global = true)]
+    pub vlan_in_flow_key: bool,
+    /// Group GRE, VXLAN, GENEVE, IPIP and 6in4 packets by their encapsulated packets
+    #[arg(long, global = true)]
+    pub decapsulate_tunnels: bool,
+    /// Seconds to wait for the missing fragments of an IP datagram before leaving its
+    /// fragments unclassified
+    #[arg(long, global = true, default_value_t = 30)]
+    pub fragment_timeout: u32,
+    /// Which fragment's data to keep where fragments of an IP datagram overlap
+    #[arg(long, global = true, value_enum, default_value_t = OverlapPolicy::First)]
+    pub fragment_overlap: OverlapPolicy,
+    /// Seconds without packets after which a flow ends, so that later packets with the same five
+    /// tuple start a new flow
+    #[arg(long, global = true)]
+    pub idle_timeout: Option<u32>,
+    /// Seconds a TCP connection must be idle before a new SYN with the same five tuple starts a
+    /// new flow, when the connection was not seen closing
+    #[arg(long, global = true, default_value_t = 60)]
+    pub tcp_reuse_timeout: u32,
+    /// Memory in MiB the packet lists of flows may take up before they are moved to a temporary
+    /// file
+    #[arg(long, global = true)]
+    pub memory_limit: Option<usize>,
+    /// Threads dissecting pcap captures, defaults to the number of cores
+    #[arg(long, global = true)]
+    pub threads: Option<usize>,
+    /// Neither read nor write the flow index file kept next to captures opened in the TUI
+    #[arg(long, global = true)]
+    pub no_index: bool,
+}
+
+impl Default for FlowOptions {
+    fn default() -> Self {
+        Self {
+            attribute_icmp_errors: false,
+            vlan_in_flow_key: false,
+            decapsulate_tunnels: false,
+            fragment_timeout: 30,
+            fragment_overlap: OverlapPolicy::First,
+            idle_timeout: None,
+            tcp_reuse_timeout: 60,
+            memory_limit: None,
+            threads: None,
+            no_index: false,
+        }
+    }
+}
+
+impl FlowOptions {
+    /// Writes the options that change which flows are extracted, so that a flow index is only
+    /// used with the options it was written with
+    fn write_index(&self, cursor: &mut WriteOnlyIndexCursor) {
+        cursor.put_bool(self.attribute_icmp_errors);
+        cursor.put_bool(self.vlan_in_flow_key);
+        cursor.put_bool(self.decapsulate_tunnels);
+        cursor.put_u32(self.fragment_timeout);
+        cursor.put_u8(match self.fragment_overlap {
+            OverlapPolicy::First => 0,
+            OverlapPolicy::Last => 1,
+        });
+        cursor.put_optional_u32(self.idle_timeout);
+        cursor.put_u32(self.tcp_reuse_timeout);
+    }
+}
+
+/// Loads the flows of the capture at `file_path` from its index file, or extracts them and
+/// writes the index file if there is no valid one
+///
+/// The index only saves extracting flows again, so failing to read or write it is not an error.
+/// Captures that are not regular files, such as standard input or named pipes, are read in the
+/// background with [`Flows::stream`] instead.
+pub fn open_flows(file_path: impl AsRef<Path>, options: FlowOptions) -> Result<Flows, Error> {
+    let is_regular_file = file_path.as_ref() != Path::new(STDIN_PATH)
+        && std::fs::metadata(&file_path).is_ok_and(|metadata| metadata.is_file());
+    if !is_regular_file {
+        return Flows::stream(file_path, options);
+    }
+
+    if options.no_index {
+        return extract_flows(file_path, options);
+    }
+
+    if let Ok(Some(flows)) = Flows::read_index(file_path.as_ref(), options) {
+        return Ok(flows);
+    }
+
+    let flows = extract_flows(file_path, options)?;
+    let _ = flows.write_index(options);
+
+    Ok(flows)
+}
+
+pub fn extract_flows(file_path: impl AsRef<Path>, options: FlowOptions) -> Result<Flows, Error> {
+    extract_flows_in_chunks(file_path, options, parallel::CHUNK_LENGTH)
+}
+
+fn extract_flows_in_chunks(
+    file_path: impl AsRef<Path>,
+    options: FlowOptions,
+    chunk_length: u64,
+) -> Result<Flows, Error> {
+    let (capture_header, mut capture) = ReadOnlyCapture::open(&file_path)?;
+
+    let threads = options
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get))
+        .max(1);
+    // Records read one at a time are flushed one at a time, too few to update flows in parallel
+    let (flows, unclassified, truncated_after) = match capture.mapped_records() {
+        Some(records) if threads > 1 => {
+            let mut extraction = Extraction::partitioned(options, threads);
+            let truncated_after = parallel::dissect_records(
+                &records,
+                capture.link_layer_type(),
+                options.decapsulate_tunnels,
+                threads,
+                chunk_length,
+                |batch| {
+                    for record in batch {
+                        extraction.push(record.header, record.offset, record.dissection);
+                    }
+                    extraction.flush()
+                },
+            )?;
+            let (flows, unclassified) = extraction.finish()?;
+
+            (flows, unclassified, truncated_after)
+        }
+        _ => {
+            let mut extraction = Extraction::new(options);
+            let truncated_after =
+                read_records(&mut capture, options.decapsulate_tunnels, |record| {
+                    extraction.push(record.header, record.offset, record.dissection);
+                    extraction.flush()?;
+
+                    Ok(true)
+                })?;
+            let (flows, unclassified) = extraction.finish()?;
+
+            (flows, unclassified, truncated_after)
+        }
+    };
+
+    Ok(Flows {
+        inner: flows,
+        file_path: file_path.as_ref().to_path_buf(),
+        file_extension: capture.file_extension(),
+        capture_header,
+        truncated_after,
+        unclassified,
+        spool: capture.spool(),
+        stream: None,
+        sources: None,
+    })
+}
+
+/// Reads and dissects the records of `capture` one after the other, passing each to `handle`
+/// until it returns `false`, and returns the number of records read before the capture ended in
+/// the middle of a record, if it did
+fn read_records(
+    capture: &mut ReadOnlyCapture,
+    decapsulate_tunnels: bool,
+    mut handle: impl FnMut(DissectedRecord) -> Result<bool, Error>,
+) -> Result<Option<usize>, Error> {
+    let mut packet_count = 0;
+    loop {
+        // `ReadOnlyCapture` is also an iterator, whose `position` the method call would find
+        let offset = ReadOnlyCapture::position(capture);
+        let header = match capture.next_record() {
+            Ok(Some(header)) => header,
+            Ok(None) => return Ok(None),
+            Err(Error::TruncatedRecordHeader(_) | Error::TruncatedRecord(_)) => {
+                return Ok(Some(packet_count));
+            }
+            Err(e) => return Err(e),
+        };
+        packet_count += 1;
+
+        let dissection = PacketDissection::from_packet(
+            &capture.packet(),
+            capture.link_layer_type(),
+            decapsulate_tunnels,
+        );
+        let record = DissectedRecord {
+            header,
+            offset,
+            dissection,
+        };
+        if !handle(record)? {
+            return Ok(None);
+        }
+    }
+}
+
+/// Dissected record queued for the flow of its five tuple
+type QueuedRecord = (FiveTuple, PacketHeader, PacketDissection, u64);
+
+/// Dissected records on their way into flows, kept in `tables`
+///
+/// Records are pushed in capture order. Fragments are reassembled and records that do not belong
+/// to a flow are set aside as they are pushed, while the others are queued until the next flush.
+/// A single [`FlowTable`] can be read while flows are being extracted. Flows can also be
+/// partitioned by their five tuple over several tables, so that the tables can be updated in
+/// parallel while each flow still sees its packets in capture order.
+struct Extraction<T> {
+    tables: T,
+    queue: Vec<QueuedRecord>,
+    unclassified: Unclassified,
+    reassembler: Reassembler<(PacketHeader, u64)>,
+    options: FlowOptions,
+    /// Latest capture time of the records pushed so far, in seconds
+    latest_time: i64,
+}
+
+impl<T> Extraction<T> {
+    fn with_tables(options: FlowOptions, tables: T) -> Self {
+        Self {
+            tables,
+            queue: Vec::new(),
+            unclassified: Unclassified::default(),
+            reassembler: Reassembler::new(options.fragment_timeout, options.fragment_overlap),
+            options,
+            latest_time: i64::MIN,
+        }
+    }
+
+    fn push(&mut self, header: PacketHeader, offset: u64, dissection: Result<Dissection, Error>) {
+        self.latest_time = self.latest_time.max(header.timestamp.seconds());
+        if !self.reassembler.is_empty() {
+            for (header, offset) in self.reassembler.expire(header.timestamp.seconds()) {
+                self.unclassified
+                    .insert_packet(&header, &Error::UnreassembledFragment, offset);
+            }
+        }
+
+        match dissection {
+            Ok(Dissection::Complete(dissection)) => self.enqueue(header, dissection, offset),
+            Ok(Dissection::Fragment(fragment)) => {
+                let time = header.timestamp.seconds();
+                let Some(reassembled) = self.reassembler.insert(fragment, (header, offset), time)
+                else {
+                    return;
+                };
+
+                // Every fragment of the datagram belongs to the flow of the reassembled packet
+                match PacketDissection::from_reassembled(reassembled.fragment, &reassembled.payload)
+                {
+                    Ok(dissection) => {
+                        for (header, offset) in reassembled.records {
+                            self.enqueue(header, dissection.clone(), offset);
+                        }
+                    }
+                    Err(e) => {
+                        for (header, offset) in reassembled.records {
+                            self.unclassified.insert_packet(&header, &e, offset);
+                        }
+                    }
+                }
+            }
+            Err(e) => self.unclassified.insert_packet(&header, &e, offset),
+        }
+    }
+
+    fn enqueue(&mut self, header: PacketHeader, dissection: PacketDissection, offset: u64) {
+        let dissection = match dissection.quoted_flow_dissection() {
+            Some(quoted_flow_dissection) if self.options.attribute_icmp_errors => {
+                quoted_flow_dissection
+            }
+            _ => dissection,
+        };
+        let five_tuple =
+            FiveTuple::from_packet_dissection(&dissection, self.options.vlan_in_flow_key);
+
+        self.queue.push((five_tuple, header, dissection, offset));
+    }
+
+    /// Sets aside the fragments of datagrams that were never completed, before the last flush
+    fn drain_reassembler(&mut self) {
+        let mut unreassembled = self.reassembler.drain();
+        unreassembled.sort_by_key(|(_, offset)| *offset);
+        for (header, offset) in unreassembled {
+            self.unclassified
+                .insert_packet(&header, &Error::UnreassembledFragment, offset);
+        }
+    }
+
+    /// All flows of the tables, which `into_tables` takes apart, ordered by their first packet,
+    /// and the records that do not belong to any
+    fn finish_tables(
+        self,
+        into_tables: impl FnOnce(T) -> Result<Vec<FlowTable>, Error>,
+    ) -> Result<(Vec<Flow>, Unclassified), Error> {
+        let mut flows = into_tables(self.tables)?
+            .into_iter()
+            .flat_map(|mut flows| {
+                // Tables only finish idle flows when they get a record, which depends on the
+                // partitioning
+                if let Some(idle_timeout) = self.options.idle_timeout {
+                    flows.finish_idle_flows(self.latest_time, idle_timeout);
+                }
+
+                flows.into_flows()
+            })
+            .collect::<Vec<Flow>>();
+        flows.sort_by_key(|flow| (flow.first_timestamp, flow.first_offset));
+
+        Ok((flows, self.unclassified))
+    }
+}
+
+impl Extraction<FlowTable> {
+    fn new(options: FlowOptions) -> Self {
+        Self::with_tables(options, FlowTable::new(options, 1))
+    }
+
+    /// Adds the queued records to their flows
+    fn flush(&mut self) -> Result<(), Error> {
+        self.tables.insert_all(self.queue.drain(..))
+    }
+
+    /// Flows extracted so far, in the order they were started in
+    fn flows(&self) -> &[Flow] {
+        &self.tables.flows
+    }
+
+    /// All flows ordered by their first packet, and the records that do not belong to any
+    fn finish(mut self) -> Result<(Vec<Flow>, Unclassified), Error> {
+        self.drain_reassembler();
+        self.flush()?;
+
+        self.finish_tables(|flows| Ok(vec![flows]))
+    }
+}
+
+impl Extraction<FlowTableWorkers> {
+    fn partitioned(options: FlowOptions, partition_count: usize) -> Self {
+        Self::with_tables(options, FlowTableWorkers::new(options, partition_count))
+    }
+
+    /// Sends the queued records to the threads updating the tables of their flows
+    fn flush(&mut self) -> Result<(), Error> {
+        let mut batches = (0..self.tables.batch_senders.len())
+            .map(|_| Vec::new())
+            .collect::<Vec<Vec<QueuedRecord>>>();
+        for record in self.queue.drain(..) {
+            let mut hasher = DefaultHasher::new();
+            record.0.hash(&mut hasher);
+            let partition = (hasher.finish() % batches.len() as u64) as usize;
+
+            batches[partition].push(record);
+        }
+
+        for (batch_sender, batch) in self.tables.batch_senders.iter().zip(batches) {
+            batch_sender
+                .send(batch)
+                .expect("updating flows does not panic");
+        }
+
+        Ok(())
+    }
+
+    /// All flows ordered by their first packet, and the records that do not belong to any
+    fn finish(mut self) -> Result<(Vec<Flow>, Unclassified), Error> {
+        self.drain_reassembler();
+        self.flush()?;
+
+        self.finish_tables(FlowTableWorkers::join)
+    }
+}
+
+/// Flow tables each updated on a thread of its own with the records of one partition of the flows
+struct FlowTableWorkers {
+    batch_senders: Vec<SyncSender<Vec<QueuedRecord>>>,
+    workers: Vec<JoinHandle<Result<FlowTable, Error>>>,
+}
+
+impl FlowTableWorkers {
+    /// Number of batches of records a thread may fall behind by
+    const BATCHES_BEHIND: usize = 2;
+
+    fn new(options: FlowOptions, table_count: usize) -> Self {
+        let (batch_senders, workers) = (0..table_count)
+            .map(|_| {
+                let (batch_sender, batches) =
+                    mpsc::sync_channel::<Vec<QueuedRecord>>(Self::BATCHES_BEHIND);
+                let mut flows = FlowTable::new(options, table_count);
+                let worker = thread::spawn(move || {
+                    // Batches are still received after a failure, so that sending them never
+                    // fails, and the error is returned once they run out
+                    let mut result = Ok(());
+                    for batch in batches {
+                        if result.is_ok() {
+                            result = flows.insert_all(batch.into_iter());
+                        }
+                    }
+
+                    result.map(|()| flows)
+                });
+
+                (batch_sender, worker)
+            })
+            .unzip();
+
+        Self {
+            batch_senders,
+            workers,
+        }
+    }
+
+    /// Waits for the threads to add all records sent to them, and returns their tables, or the
+    /// first error updating one of them failed with
+    fn join(self) -> Result<Vec<FlowTable>, Error> {
+        drop(self.batch_senders);
+
+        self.workers
+            .into_iter()
+            .map(|worker| worker.join().expect("updating flows does not panic"))
+            .collect()
+    }
+}
+
+/// Flows being extracted from a capture, updated as each packet is read
+///
+/// Flows are kept in the order they were started in. Flows idle for longer than the idle timeout
+/// are finished, so that later packets with the same five tuple start a new flow, and so are TCP
+/// flows whose five tuple is reused by a new connection. Finished flows only keep their record,
+/// while the state needed to follow a flow's packets stays with the active flows. Packet lists
+/// are moved to a spill file whenever they and the state of the active flows take up more memory
+/// than the table's share of the memory limit.
+struct FlowTable {
+    /// Records of the flows, finished or not
+    flows: Vec<Flow>,
+    /// Flows that have not been finished, by their five tuple
+    active: HashMap<FiveTuple, ActiveFlow>,
+    idle_timeout: Option<u32>,
+    tcp_reuse_timeout: u32,
+    /// Memory in bytes the packet lists and the active flows of the table may take up
+    memory_limit: Option<usize>,
+    last_sweep: i64,
+    buffered_packets: usize,
+    /// Indices of the flows with packets that have not been moved to the spill file
+    buffered_flows: Vec<usize>,
+    spill_file: Option<Arc<SpillFile>>,
+}
+
+impl FlowTable {
+    /// Creates one of `table_count` tables sharing the memory limit of `options`
+    fn new(options: FlowOptions, table_count: usize) -> Self {
+        Self {
+            flows: Vec::new(),
+            active: HashMap::new(),
+            idle_timeout: options.idle_timeout,
+            tcp_reuse_timeout: options.tcp_reuse_timeout,
+            memory_limit: options
+                .memory_limit
+                .map(|memory_limit| memory_limit * 1024 * 1024 / table_count),
+            last_sweep: i64::MIN,
+            buffered_packets: 0,
+            buffered_flows: Vec::new(),
+            spill_file: None,
+        }
+    }
+
+    fn insert_all(
+        &mut self,
+        records: impl Iterator<Item = (FiveTuple, PacketHeader, PacketDissection, u64)>,
+    ) -> Result<(), Error> {
+        for (five_tuple, header, dissection, offset) in records {
+            self.insert(five_tuple, header, &dissection, offset)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a dissected record to the flow of `five_tuple`
+    fn insert(
+        &mut self,
+        five_tuple: FiveTuple,
+        header: PacketHeader,
+        dissection: &PacketDissection,
+        offset: u64,
+    ) -> Result<(), Error> {
+        let time = header.timestamp.seconds();
+        if let Some(idle_timeout) = self.idle_timeout {
+            if time > self.last_sweep {
+                self.finish_idle_flows(time, idle_timeout);
+                self.last_sweep = time;
+            }
+        }
+
+        let active = match self.active.entry(five_tuple) {
+            Entry::Occupied(mut o) => {
+                let flow = &mut self.flows[o.get().index];
+                if flow.is_reopened_by(dissection, &header, self.tcp_reuse_timeout) {
+                    flow.finish(ConnectionEnd::Superseded);
+
+                    let flow = Flow::new(o.key().clone(), dissection, &header, offset);
+                    o.insert(ActiveFlow::new(self.flows.len(), &flow));
+                    self.flows.push(flow);
+                } else {
+                    flow.insert_packet(dissection, &header, offset);
+                }
+
+                o.into_mut()
+            }
+            Entry::Vacant(v) => {
+                let flow = Flow::new(v.key().clone(), dissection, &header, offset);
+                let active = v.insert(ActiveFlow::new(self.flows.len(), &flow));
+                self.flows.push(flow);
+
+                active
+            }
+        };
+
+        let flow = &mut self.flows[active.index];
+        if let Some(tcp_analysis) = &mut active.tcp_analysis {
+            flow.analyse(tcp_analysis, dissection, &header);
+        }
+        if flow.packets.buffered.len() == 1 {
+            self.buffered_flows.push(active.index);
+        }
+
+        self.buffered_packets += 1;
+        if let Some(memory_limit) = self.memory_limit {
+            let memory_size = self.buffered_packets * size_of::<FlowPacket>()
+                + self.active.len() * ActiveFlow::MEMORY_SIZE;
+            if memory_size > memory_limit {
+                self.spill()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish_idle_flows(&mut self, time: i64, idle_timeout: u32) {
+        let flows = &mut self.flows;
+        self.active.retain(|_, active| {
+            let flow = &mut flows[active.index];
+            let is_active =
+                time.saturating_sub(flow.last_timestamp.seconds()) <= i64::from(idle_timeout);
+            if !is_active {
+                flow.finish(ConnectionEnd::TimedOut);
+            }
+
+            is_active
+        });
+    }
+
+    /// Moves the packets buffered in memory to the spill file
+    fn spill(&mut self) -> Result<(), Error> {
+        let spill_file = match &self.spill_file {
+            Some(spill_file) => Arc::clone(spill_file),
+            None => Arc::clone(self.spill_file.insert(Arc::new(SpillFile::create()?))),
+        };
+
+        for index in self.buffered_flows.drain(..) {
+            self.flows[index].packets.spill(&spill_file)?;
+        }
+        self.buffered_packets = 0;
+
+        Ok(())
+    }
+
+    fn into_flows(self) -> Vec<Flow> {
+        self.flows
+    }
+}
+
+/// State of a flow that is only needed while packets are added to it
+struct ActiveFlow {
+    /// Index of the record of the flow in its table
+    index: usize,
+    tcp_analysis: Option<TcpAnalysis>,
+}
+
+impl ActiveFlow {
+    /// Memory taken up by an active flow besides its record, counting its five tuple key
+    const MEMORY_SIZE: usize = size_of::<FiveTuple>() + size_of::<Self>();
+
+    fn new(index: usize, flow: &Flow) -> Self {
+        Self {
+            index,
+            tcp_analysis: (flow.protocol == TransportLayerType::Tcp).then(TcpAnalysis::default),
+        }
+    }
+}
+
+pub struct Flows {
+    inner: Vec<Flow>,
+    file_path: PathBuf,
+    file_extension: &'static str,
+    capture_header: CaptureHeader,
+    truncated_after: Option<usize>,
+    unclassified: Unclassified,
+    /// Copy of a capture that is not a regular file, which records are read back from
+    spool: Option<Arc<SpillFile>>,
+    /// Capture still being read, which holds the flows until it has been read to the end
+    stream: Option<Box<FlowStream>>,
+    /// Files the records of a merged capture were read from
+    sources: Option<Arc<SourceMap>>,
+}
+
+/// Flows of a capture being read and dissected on a background thread
+struct FlowStream {
+    records: Receiver<DissectedRecord>,
+    reader: JoinHandle<Result<Option<usize>, Error>>,
+    extraction: Extraction<FlowTable>,
+}
+
+impl Flows {
+    /// Number of dissected records the background thread of a stream may get ahead of the flows
+    const STREAM_BUFFER_LENGTH: usize = 4096;
+    /// Largest number of records added to the flows of a stream by one update
+    const STREAM_UPDATE_LENGTH: usize = 65536;
+
+    /// Starts reading the capture at `file_path` in the background, returning as soon as its
+    /// header has been read
+    ///
+    /// Flows are added as records arrive, each time [`Self::update`] is called. This suits
+    /// standard input and named pipes, whose records arrive over time.
+    pub fn stream(file_path: impl AsRef<Path>, options: FlowOptions) -> Result<Self, Error> {
+        let (capture_header, capture) = ReadOnlyCapture::open(&file_path)?;
+
+        Self::stream_capture(file_path, capture_header, capture, options)
+    }
+
+    /// Starts capturing packets on the network interface named `interface`, adding them to the
+    /// flows as they arrive like [`Self::stream`] does
+    pub fn live(interface: &str, options: FlowOptions) -> Result<Self, Error> {
+        let live_capture = LiveCapture::open(interface)?;
+        let (capture_header, capture) = ReadOnlyCapture::from_reader(Box::new(live_capture))?;
+
+        Self::stream_capture(interface, capture_header, capture, options)
+    }
+
+    /// Starts reading the captures at `file_paths` in the background, merged into one capture
+    /// in the order of their timestamps, adding their records to the flows like [`Self::stream`]
+    /// does
+    pub fn merge(file_paths: &[PathBuf], options: FlowOptions) -> Result<Self, Error> {
+        let (merged_capture, sources) = MergedCapture::open(file_paths)?;
+        let (capture_header, capture) = ReadOnlyCapture::from_reader(Box::new(merged_capture))?;
+
+        let mut flows = Self::stream_capture("merged", capture_header, capture, options)?;
+        flows.sources = Some(sources);
+
+        Ok(flows)
+    }
+
+    fn stream_capture(
+        file_path: impl AsRef<Path>,
+        capture_header: CaptureHeader,
+        mut capture: ReadOnlyCapture,
+        options: FlowOptions,
+    ) -> Result<Self, Error> {
+        let file_extension = capture.file_extension();
+        let spool = capture.spool();
+
+        let (sender, records) = mpsc::sync_channel(Self::STREAM_BUFFER_LENGTH);
+        let reader = thread::spawn(move || {
+            read_records(&mut capture, options.decapsulate_tunnels, |record| {
+                Ok(sender.send(record).is_ok())
+            })
+        });
+
+        let stream = FlowStream {
+            records,
+            reader,
+            extraction: Extraction::new(options),
+        };
+
+        Ok(Self {
+            inner: Vec::new(),
+            file_path: file_path.as_ref().to_path_buf(),
+            file_extension,
+            capture_header,
+            truncated_after: None,
+            unclassified: Unclassified::default(),
+            spool,
+            stream: Some(Box::new(stream)),
+            sources: None,
+        })
+    }
+
+    /// Adds the records read in the background since the last update to the flows
+    ///
+    /// Does nothing unless the flows were created by [`Self::stream`] and the capture has not
+    /// been read to the end yet. Fails with the error reading the capture ended with, if any.
+    pub fn update(&mut self) -> Result<(), Error> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(());
+        };
+
+        for _ in 0..Self::STREAM_UPDATE_LENGTH {
+            match stream.records.try_recv() {
+                Ok(record) => {
+                    stream
+                        .extraction
+                        .push(record.header, record.offset, record.dissection);
+                }
+                Err(TryRecvError::Empty) => return stream.extraction.flush(),
+                Err(TryRecvError::Disconnected) => {
+                    let stream = self.stream.take().expect("the stream was just read from");
+                    let (flows, unclassified) = stream.extraction.finish()?;
+                    self.inner = flows;
+                    self.unclassified = unclassified;
+                    self.truncated_after = stream
+                        .reader
+                        .join()
+                        .expect("reading records does not panic")?;
+
+                    return Ok(());
+                }
+            }
+        }
+
+        stream.extraction.flush()
+    }
+
+    /// Blocks until the capture has been read to the end and all of its records were added to
+    /// the flows
+    pub fn wait(&mut self) -> Result<(), Error> {
+        if let Some(stream) = &mut self.stream {
+            while let Ok(record) = stream.records.recv() {
+                stream
+                    .extraction
+                    .push(record.header, record.offset, record.dissection);
+                stream.extraction.flush()?;
+            }
+        }
+
+        self.update()
+    }
+
+    /// Whether the capture is still being read in the background
+    pub const fn is_streaming(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Whether the flows were extracted from several captures by [`Self::merge`]
+    pub const fn is_merged(&self) -> bool {
+        self.sources.is_some()
+    }
+
+    /// Name of the capture `packet` was read from, if the flows were merged from several
+    pub fn source(&self, packet: &FlowPacket) -> Option<&str> {
+        self.sources.as_ref()?.name(packet.offset)
+    }
+
+    /// Name of the capture the unclassified `packet` was read from, if the flows were merged
+    /// from several
+    pub fn unclassified_source(&self, packet: &UnclassifiedPacket) -> Option<&str> {
+        self.sources.as_ref()?.name(packet.offset)
+    }
+
+    fn flows(&self) -> &[Flow] {
+        match &self.stream {
+            Some(stream) => stream.extraction.flows(),
+            None => &self.inner,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> &Flow {
+        self.flows()
+            .get(index)
+            .expect("we ensure index is within 0..flows.len()")
+    }
+
+    pub fn values(&self) -> Iter<'_, Flow> {
+        self.flows().iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.flows().len()
+    }
+
+    /// Number of flows, plus one for the packets that could not be classified into a flow if
+    /// there are any
+    pub fn tab_count(&self) -> usize {
+        self.len() + usize::from(!self.unclassified().is_empty())
+    }
+
+    pub fn unclassified(&self) -> &Unclassified {
+        match &self.stream {
+            Some(stream) => &stream.extraction.unclassified,
+            None => &self.unclassified,
+        }
+    }
+
+    pub const fn capture_header(&self) -> CaptureHeader {
+        self.capture_header
+    }
+
+    /// Number of packets read before the capture ended in the middle of a record, if it did
+    pub const fn truncated_after(&self) -> Option<usize> {
+        self.truncated_after
+    }
+
+    /// Extension of the files flows are exported to, matching the format of the source capture
+    pub const fn file_extension(&self) -> &'static str {
+        self.file_extension
+    }
+
+    /// Whether the records are only kept in a temporary spool file, which is the case for live
+    /// captures, standard input and named pipes
+    pub const fn is_temporary(&self) -> bool {
+        self.spool.is_some()
+    }
+
+    /// Copies every complete record read so far into a new capture file at `path`, returning the
+    /// number of packets written
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<usize, Error> {
+        let mut source = self.open_source()?;
+
+        let mut offsets = Vec::new();
+        loop {
+            let offset = source.position();
+            match source.next_record() {
+                Ok(Some(_)) => offsets.push(offset),
+                // The last record may still be being written to the spool file.
+                Ok(None) | Err(Error::TruncatedRecordHeader(_) | Error::TruncatedRecord(_)) => {
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        export_records(
+            &mut source,
+            self.capture_header,
+            offsets.into_iter(),
+            None,
+            path,
+        )
+    }
+
+    /// Opens the capture file the flows were extracted from, to be passed to [`Flow::export`]
+    ///
+    /// Captures that are not regular files are opened from their spool file.
+    pub fn open_source(&self) -> Result<ReadOnlyCapture, Error> {
+        let path = self
+            .spool
+            .as_ref()
+            .map_or(self.file_path.as_path(), |spool| spool.path());
+
+        ReadOnlyCapture::open(path).map(|(_, capture)| capture)
+    }
+
+    /// Reassembles the TCP connection of the flow at `index` from the capture file
+    pub fn reassemble(&self, index: usize) -> Result<TcpStream, Error> {
+        let mut source = self.open_source()?;
+
+        TcpStream::reassemble(self.get(index), &mut source)
+    }
+
+    /// Reads the flows of the capture at `file_path` from its index file, returning `None` if
+    /// there is no index file matching the capture and `options`
+    ///
+    /// Packet lists are left in the index file and read from it when needed.
+    fn read_index(file_path: &Path, options: FlowOptions) -> Result<Option<Self>, Error> {
+        let mut options_cursor = WriteOnlyIndexCursor::new();
+        options.write_index(&mut options_cursor);
+        let Some(index) = flow_index::read(file_path, &options_cursor.into_vec())? else {
+            return Ok(None);
+        };
+
+        let mut cursor = ReadOnlyIndexCursor::new(&index.metadata);
+        let file_extension = match cursor.get_u8()? {
+            0 => "pcap",
+            1 => "pcapng",
+            _ => return Err(Error::InvalidIndexFile),
+        };
+        let capture_header = CaptureHeader::read_index(&mut cursor)?;
+        let truncated_after = if cursor.get_bool()? {
+            Some(cursor.get_usize()?)
+        } else {
+            None
+        };
+        let unclassified = Unclassified::read_index(&mut cursor)?;
+
+        let flow_count = cursor.get_usize()?;
+        let flows = (0..flow_count)
+            .map(|_| Flow::read_index(&mut cursor, &index.packet_file, index.packets_start))
+            .collect::<Result<Vec<Flow>, Error>>()?;
+
+        let flows = Self {
+            inner: flows,
+            file_path: file_path.to_path_buf(),
+            file_extension,
+            capture_header,
+            truncated_after,
+            unclassified,
+            spool: None,
+            stream: None,
+            sources: None,
+        };
+
+        Ok(Some(flows))
+    }
+
+    /// Writes the flows into the index file of their capture, replacing any previous one
+    fn write_index(&self, options: FlowOptions) -> Result<(), Error> {
+        let mut options_cursor = WriteOnlyIndexCursor::new();
+        options.write_index(&mut options_cursor);
+
+        let mut cursor = WriteOnlyIndexCursor::new();
+        cursor.put_u8(u8::from(self.file_extension == "pcapng"));
+        self.capture_header.write_index(&mut cursor);
+        cursor.put_bool(self.truncated_after.is_some());
+        if let Some(truncated_after) = self.truncated_after {
+            cursor.put_usize(truncated_after);
+        }
+        self.unclassified.write_index(&mut cursor);
+
+        cursor.put_usize(self.inner.len());
+        let mut packets_position = 0;
+        for flow in &self.inner {
+            flow.write_index(&mut cursor, packets_position);
+            packets_position += (flow.packet_count * FlowPacket::LENGTH) as u64;
+        }
+
+        let packet_lists = self.inner.iter().map(|flow| {
+            let packets = flow.packets()?;
+            let mut buffer = Vec::with_capacity(packets.len() * FlowPacket::LENGTH);
+            for packet in packets.iter() {
+                packet.compose(&mut buffer);
+            }
+
+            Ok(buffer)
+        });
+
+        flow_index::write(
+            &self.file_path,
+            &options_cursor.into_vec(),
+            &cursor.into_vec(),
+            packet_lists,
+        )
+    }
+
+    /// Writes the original records of the flow at `index` into a new capture file at `path`,
+    /// returning the number of packets written
+    ///
+    /// An `index` of [`Self::len`] exports the unclassified packets.
+    pub fn export(&self, index: usize, path: impl AsRef<Path>) -> Result<usize, Error> {
+        let mut source = self.open_source()?;
+
+        if index == self.len() {
+            return self
+                .unclassified()
+                .export(&mut source, self.capture_header, path);
+        }
+
+        self.get(index)
+            .export(&mut source, self.capture_header, path)
+    }
+}
+
+pub struct Flow {
+    pub five_tuple: FiveTuple,
+    pub initiator: SocketAddr,
+    pub respondent: SocketAddr,
+    pub protocol: TransportLayerType,
+    /// Tunnel the first packet of the flow was decapsulated from
+    pub tunnel: Option<Tunnel>,
+    /// Whether the start of the flow was captured, so that its initiator is known rather than
+    /// guessed from its ports
+    pub start_observed: bool,
+    pub first_timestamp: Timestamp,
+    pub packet_count: usize,
+    pub total_size: usize,
+    pub average_size: usize,
+    pub minimum_size: usize,
+    pub maximum_size: usize,
+    pub flow_duration: u64,
+    pub average_interarrival_time: u64,
+    pub minimum_interarrival_time: u64,
+    pub maximum_interarrival_time: u64,
+    /// Capture time of the latest packet of the flow
+    pub last_timestamp: Timestamp,
+    /// Statistics of the packets sent by the respondent and by the initiator, in that order
+    directions: [DirectionStatistics; 2],
+    packets: PacketList,
+    /// Offset of the record of the first packet, ordering flows that start at the same time
+    first_offset: u64,
+    /// Offset from the first packet of the latest packet of the flow, in nanoseconds
+    previous_timestamp: i64,
+    /// Lifecycle of the connection of TCP flows
+    connection: Option<TcpConnection>,
+    tcp_metrics: Option<TcpMetrics>,
+}
+
+impl Flow {
+    /// Ports below this one are well-known ports, used by servers
+    const WELL_KNOWN_PORT_END: u16 = 1024;
+
+    pub fn new(
+        five_tuple: FiveTuple,
+        dissection: &PacketDissection,
+        header: &PacketHeader,
+        offset: u64,
+    ) -> Self {
+        let (source, destination) = dissection.socket_addrs().unwrap();
+        let protocol = dissection.network_layer.get_transport_layer_type();
+
+        let (from_initiator_to_respondent, start_observed) = Self::roles(dissection);
+        let (initiator, respondent) = if from_initiator_to_respondent {
+            (source, destination)
+        } else {
+            (destination, source)
+        };
+
+        let size = header.actual_length.as_usize();
+
+        // A TCP flow may start with an ICMP error message attributed to it, which has no header
+        let is_tcp = protocol == TransportLayerType::Tcp;
+        let mut connection = is_tcp.then(TcpConnection::default);
+        if let (Some(connection), Some(tcp_header)) = (&mut connection, dissection.tcp_header()) {
+            connection.update(tcp_header, from_initiator_to_respondent);
+        }
+
+        let mut directions: [DirectionStatistics; 2] = Default::default();
+        directions[usize::from(from_initiator_to_respondent)].update(
+            size,
+            dissection.payload_length,
+            0,
+        );
+
+        let flow_packet = FlowPacket {
+            from_initiator_to_respondent,
+            timestamp: 0,
+            size,
+            offset,
+        };
+
+        Self {
+            five_tuple,
+            initiator,
+            respondent,
+            protocol,
+            tunnel: dissection.tunnel.clone(),
+            start_observed,
+            first_timestamp: header.timestamp,
+            packet_count: 1,
+            total_size: size,
+            average_size: size,
+            minimum_size: size,
+            maximum_size: size,
+            flow_duration: 0,
+            average_interarrival_time: 0,
+            minimum_interarrival_time: u64::MAX,
+            maximum_interarrival_time: 0,
+            last_timestamp: header.timestamp,
+            directions,
+            packets: PacketList::from(flow_packet),
+            first_offset: offset,
+            previous_timestamp: 0,
+            connection,
+            tcp_metrics: is_tcp.then(TcpMetrics::default),
+        }
+    }
+
+    /// Adds a packet to the flow, updating its statistics
+    ///
+    /// Packets are expected in capture order. A packet captured before the latest packet of the
+    /// flow counts as arriving at the same time as it, though it keeps its own, possibly
+    /// negative, offset from the first packet.
+    pub fn insert_packet(
+        &mut self,
+        dissection: &PacketDissection,
+        header: &PacketHeader,
+        offset: u64,
+    ) {
+        let (source, destination) = dissection.socket_addrs().unwrap();
+        let timestamp = header.timestamp.nanos_since(self.first_timestamp);
+
+        self.packet_count += 1;
+
+        let size = header.actual_length.as_usize();
+        self.total_size += size;
+        self.average_size = self.total_size / self.packet_count;
+        self.maximum_size = self.maximum_size.max(size);
+        self.minimum_size = self.minimum_size.min(size);
+
+        let interarrival_time = timestamp.saturating_sub(self.previous_timestamp).max(0) as u64;
+        self.maximum_interarrival_time = self.maximum_interarrival_time.max(interarrival_time);
+        self.minimum_interarrival_time = self.minimum_interarrival_time.min(interarrival_time);
+        self.previous_timestamp = self.previous_timestamp.max(timestamp);
+        self.last_timestamp = self.last_timestamp.max(header.timestamp);
+
+        self.flow_duration = self.previous_timestamp as u64;
+        self.average_interarrival_time = self.flow_duration / (self.packet_count as u64 - 1);
+
+        let from_initiator_to_respondent =
+            source == self.initiator && destination == self.respondent;
+        self.directions[usize::from(from_initiator_to_respondent)].update(
+            size,
+            dissection.payload_length,
+            timestamp,
+        );
+        if let (Some(connection), Some(tcp_header)) =
+            (&mut self.connection, dissection.tcp_header())
+        {
+            connection.update(tcp_header, from_initiator_to_respondent);
+            // The SYN of a connection may be captured after the SYN-ACK answering it
+            if tcp_header.flags.is_syn() && from_initiator_to_respondent {
+                self.start_observed = true;
+            }
+        }
+        let packet = FlowPacket {
+            from_initiator_to_respondent,
+            timestamp,
+            size: header.actual_length.as_usize(),
+            offset,
+        };
+
+        self.packets.push(packet);
+    }
+
+    /// Follows the TCP segment of the packet last added to the flow with `tcp_analysis`, which
+    /// keeps what it needs to know about the earlier segments, and updates the metrics of the flow
+    fn analyse(
+        &mut self,
+        tcp_analysis: &mut TcpAnalysis,
+        dissection: &PacketDissection,
+        header: &PacketHeader,
+    ) {
+        let Some(tcp_header) = dissection.tcp_header() else {
+            return;
+        };
+        let (source, destination) = dissection.socket_addrs().unwrap();
+        let from_initiator_to_respondent =
+            source == self.initiator && destination == self.respondent;
+        let timestamp = header.timestamp.nanos_since(self.first_timestamp);
+
+        tcp_analysis.update(tcp_header, from_initiator_to_respondent, timestamp);
+        self.tcp_metrics = Some(*tcp_analysis.metrics());
+    }
+
+    /// Tells whether the sender of the first packet of a flow is its initiator, and whether that
+    /// packet is the start of the flow
+    ///
+    /// TCP flows are initiated by the sender of the SYN, and answered by the sender of the
+    /// SYN-ACK. Where neither was captured, and for UDP flows, the endpoint using a well-known
+    /// port is taken to be the respondent. Flows of other protocols are initiated by the sender of
+    /// their first packet.
+    fn roles(dissection: &PacketDissection) -> (bool, bool) {
+        if let Some(tcp_header) = dissection.tcp_header() {
+            if tcp_header.flags.is_syn() {
+                return (true, true);
+            }
+            if tcp_header.flags.contains(TcpFlags::SYN) {
+                return (false, false);
+            }
+        }
+
+        let (source_port, destination_port) = match &dissection.transport_layer {
+            TransportLayer::Tcp(source, destination, ..)
+            | TransportLayer::Udp(source, destination, _) => (*source, *destination),
+            TransportLayer::Icmp(_) => return (true, true),
+        };
+
+        let is_sent_by_initiator = !(source_port < Self::WELL_KNOWN_PORT_END
+            && destination_port >= Self::WELL_KNOWN_PORT_END);
+        // Without its handshake, a TCP connection may have started long before the capture
+        let start_observed =
+            is_sent_by_initiator && !matches!(dissection.transport_layer, TransportLayer::Tcp(..));
+
+        (is_sent_by_initiator, start_observed)
+    }
+
+    /// Whether a packet with the five tuple of the flow opens a new TCP connection instead of
+    /// belonging to the flow's, see [`TcpConnection::is_reopened_by`]
+    fn is_reopened_by(
+        &self,
+        dissection: &PacketDissection,
+        header: &PacketHeader,
+        reuse_timeout: u32,
+    ) -> bool {
+        let (Some(connection), Some(tcp_header)) = (&self.connection, dissection.tcp_header())
+        else {
+            return false;
+        };
+
+        let idle_seconds = header
+            .timestamp
+            .seconds()
+            .saturating_sub(self.last_timestamp.seconds());
+
+        connection.is_reopened_by(tcp_header, idle_seconds, reuse_timeout)
+    }
+
+    /// Records why the flow ended, for TCP flows whose connection had not been closed
+    fn finish(&mut self, end: ConnectionEnd) {
+        if let Some(connection) = &mut self.connection {
+            connection.finish(end);
+        }
+    }
+
+    /// How the connection of a TCP flow ended, `None` for other protocols
+    pub fn connection_end(&self) -> Option<ConnectionEnd> {
+        self.connection.as_ref().map(TcpConnection::end)
+    }
+
+    /// Health of the connection of a TCP flow, `None` for other protocols
+    pub fn tcp_metrics(&self) -> Option<&TcpMetrics> {
+        self.tcp_metrics.as_ref()
+    }
+
+    /// Statistics of the packets sent by the initiator, or by the respondent
+    pub fn direction_statistics(&self, from_initiator: bool) -> &DirectionStatistics {
+        &self.directions[usize::from(from_initiator)]
+    }
+
+    /// Reads a flow written by [`Self::write_index`] from a flow index, leaving its packet list in
+    /// `packet_file`, where packet lists start at `packets_start`
+    fn read_index(
+        cursor: &mut ReadOnlyIndexCursor,
+        packet_file: &Arc<SpillFile>,
+        packets_start: u64,
+    ) -> Result<Self, Error> {
+        let five_tuple = FiveTuple::read_index(cursor)?;
+        let initiator = cursor.get_socket_addr()?;
+        let respondent = cursor.get_socket_addr()?;
+        let protocol = cursor.get_transport_layer_type()?;
+        let tunnel = if cursor.get_bool()? {
+            Some(Tunnel::read_index(cursor)?)
+        } else {
+            None
+        };
+        let start_observed = cursor.get_bool()?;
+        let first_timestamp = cursor.get_timestamp()?;
+        let packet_count = cursor.get_usize()?;
+
+        let flow = Self {
+            five_tuple,
+            initiator,
+            respondent,
+            protocol,
+            tunnel,
+            start_observed,
+            first_timestamp,
+            packet_count,
+            total_size: cursor.get_usize()?,
+            average_size: cursor.get_usize()?,
+            minimum_size: cursor.get_usize()?,
+            maximum_size: cursor.get_usize()?,
+            flow_duration: cursor.get_u64()?,
+            average_interarrival_time: cursor.get_u64()?,
+            minimum_interarrival_time: cursor.get_u64()?,
+            maximum_interarrival_time: cursor.get_u64()?,
+            last_timestamp: cursor.get_timestamp()?,
+            directions: [
+                DirectionStatistics::read_index(cursor)?,
+                DirectionStatistics::read_index(cursor)?,
+            ],
+            first_offset: cursor.get_u64()?,
+            previous_timestamp: cursor.get_i64()?,
+            connection: if cursor.get_bool()? {
+                Some(TcpConnection::read_index(cursor)?)
+            } else {
+                None
+            },
+            tcp_metrics: if cursor.get_bool()? {
+                Some(TcpMetrics::read_index(cursor)?)
+            } else {
+                None
+            },
+            packets: PacketList {
+                buffered: Vec::new(),
+                spilled: vec![(packets_start + cursor.get_u64()?, packet_count)],
+                spill_file: Some(Arc::clone(packet_file)),
+            },
+        };
+
+        Ok(flow)
+    }
+
+    /// Writes the flow into a flow index, with its packet list at `packets_position` of the
+    /// packet lists
+    fn write_index(&self, cursor: &mut WriteOnlyIndexCursor, packets_position: u64) {
+        self.five_tuple.write_index(cursor);
+        cursor.put_socket_addr(self.initiator);
+        cursor.put_socket_addr(self.respondent);
+        cursor.put_transport_layer_type(self.protocol);
+        cursor.put_bool(self.tunnel.is_some());
+        if let Some(tunnel) = &self.tunnel {
+            tunnel.write_index(cursor);
+        }
+        cursor.put_bool(self.start_observed);
+        cursor.put_timestamp(self.first_timestamp);
+        cursor.put_usize(self.packet_count);
+        cursor.put_usize(self.total_size);
+        cursor.put_usize(self.average_size);
+        cursor.put_usize(self.minimum_size);
+        cursor.put_usize(self.maximum_size);
+        cursor.put_u64(self.flow_duration);
+        cursor.put_u64(self.average_interarrival_time);
+        cursor.put_u64(self.minimum_interarrival_time);
+        cursor.put_u64(self.maximum_interarrival_time);
+        cursor.put_timestamp(self.last_timestamp);
+        for direction in &self.directions {
+            direction.write_index(cursor);
+        }
+        cursor.put_u64(self.first_offset);
+        cursor.put_i64(self.previous_timestamp);
+        cursor.put_bool(self.connection.is_some());
+        if let Some(connection) = &self.connection {
+            connection.write_index(cursor);
+        }
+        cursor.put_bool(self.tcp_metrics.is_some());
+        if let Some(tcp_metrics) = &self.tcp_metrics {
+            tcp_metrics.write_index(cursor);
+        }
+        cursor.put_u64(packets_position);
+    }
+
+    /// Packets of the flow, read back from the spill file if they were moved there
+    pub fn packets(&self) -> Result<Cow<'_, [FlowPacket]>, Error> {
+        self.packets.get()
+    }
+
+    /// Copies the records of the flow from `source` into a new capture file at `path`,
+    /// returning the number of packets written
+    ///
+    /// Flows of pcapng captures are written as pcapng, keeping the interfaces and comments of
+    /// the source and commenting every packet with the five tuple of the flow.
+    pub fn export(
+        &self,
+        source: &mut ReadOnlyCapture,
+        capture_header: CaptureHeader,
+        path: impl AsRef<Path>,
+    ) -> Result<usize, Error> {
+        let packets = self.packets()?;
+        let offsets = packets.iter().map(|p| p.offset);
+        let comment = BlockOption::comment(&self.five_tuple.to_string());
+
+        export_records(source, capture_header, offsets, Some(comment), path)
+    }
+}
+
+/// Copies the records at `offsets` from `source` into a new capture file at `path`, returning
+/// the number of packets written
+///
+/// Records of pcapng captures are written as pcapng, keeping the interfaces and comments of the
+/// source and adding `comment` to every packet.
+fn export_records(
+    source: &mut ReadOnlyCapture,
+    capture_header: CaptureHeader,
+    offsets: impl Iterator<Item = u64>,
+    comment: Option<BlockOption>,
+    path: impl AsRef<Path>,
+) -> Result<usize, Error> {
+    if let Some((section_header, _, _)) = source.pcapng_context() {
+        let section_header = section_header.clone();
+        return export_pcapng_records(source, &section_header, offsets, comment, path);
+    }
+
+    let mut destination = WriteOnlyCapture::create(path, capture_header)?;
+
+    let mut packet_count = 0;
+    for offset in offsets {
+        let packet_header = source.record_at(offset)?;
+        destination.put(packet_header, &source.packet())?;
+        packet_count += 1;
+    }
+    destination.finish()?;
+
+    Ok(packet_count)
+}
+
+fn export_pcapng_records(
+    source: &mut ReadOnlyCapture,
+    section_header: &SectionHeaderBlock,
+    offsets: impl Iterator<Item = u64>,
+    comment: Option<BlockOption>,
+    path: impl AsRef<Path>,
+) -> Result<usize, Error> {
+    let mut destination = WriteOnlyPcapngCapture::create(path, section_header)?;
+
+    let mut packet_count = 0;
+    for offset in offsets {
+        let packet_header = source.record_at(offset)?;
+        let Some((_, interface, options)) = source.pcapng_context() else {
+            unreachable!("a pcapng source stays pcapng after seeking");
+        };
+
+        let mut options = options.to_vec();
+        if let Some(comment) = &comment {
+            if !options.contains(comment) {
+                options.push(comment.clone());
+            }
+        }
+
+        destination.put(interface, packet_header, &source.packet(), options)?;
+        packet_count += 1;
+    }
+    destination.finish()?;
+
+    Ok(packet_count)
+}
+
+#[derive(Copy, Clone)]
+pub struct FlowPacket {
+    pub from_initiator_to_respondent: bool,
+    /// Offset from the first packet of the flow in nanoseconds, negative for packets captured
+    /// before it
+    pub timestamp: i64,
+    pub size: usize,
+    pub offset: u64,
+}
+
+impl FlowPacket {
+    const LENGTH: usize = 25;
+
+    fn parse(buffer: &[u8]) -> Self {
+        let u64_at = |index: usize| {
+            let bytes = buffer[index..index + 8]
+                .try_into()
+                .expect("slice is eight bytes long");
+            u64::from_le_bytes(bytes)
+        };
+
+        Self {
+            from_initiator_to_respondent: buffer[0] != 0,
+            timestamp: u64_at(1) as i64,
+            size: u64_at(9) as usize,
+            offset: u64_at(17),
+        }
+    }
+
+    fn compose(&self, buffer: &mut Vec<u8>) {
+        buffer.push(u8::from(self.from_initiator_to_respondent));
+        buffer.extend(self.timestamp.to_le_bytes());
+        buffer.extend((self.size as u64).to_le_bytes());
+        buffer.extend(self.offset.to_le_bytes());
+    }
+}
+
+/// Packets of a flow, kept in memory until they are moved to a spill file
+struct PacketList {
+    buffered: Vec<FlowPacket>,
+    /// Positions and packet counts of the parts of the list in the spill file, in order
+    spilled: Vec<(u64, usize)>,
+    spill_file: Option<Arc<SpillFile>>,
+}
+
+impl PacketList {
+    fn push(&mut self, packet: FlowPacket) {
+        self.buffered.push(packet);
+    }
+
+    fn spill(&mut self, spill_file: &Arc<SpillFile>) -> Result<(), Error> {
+        if self.buffered.is_empty() {
+            return Ok(());
+        }
+
+        let mut buffer = Vec::with_capacity(self.buffered.len() * FlowPacket::LENGTH);
+        for packet in &self.buffered {
+            packet.compose(&mut buffer);
+        }
+        let position = spill_file.append(&buffer)?;
+
+        self.spilled.push((position, self.buffered.len()));
+        self.buffered = Vec::new();
+        self.spill_file = Some(Arc::clone(spill_file));
+
+        Ok(())
+    }
+
+    fn get(&self) -> Result<Cow<'_, [FlowPacket]>, Error> {
+        let Some(spill_file) = &self.spill_file else {
+            return Ok(Cow::Borrowed(self.buffered.as_slice()));
+        };
+
+        let mut packets = Vec::new();
+        for (position, packet_count) in &self.spilled {
+            let buffer = spill_file.read(*position, packet_count * FlowPacket::LENGTH)?;
+            packets.extend(
+                buffer
+                    .chunks_exact(FlowPacket::LENGTH)
+                    .map(FlowPacket::parse),
+            );
+        }
+        packets.extend_from_slice(&self.buffered);
+
+        Ok(Cow::Owned(packets))
+    }
+}
+
+impl From<FlowPacket> for PacketList {
+    fn from(packet: FlowPacket) -> Self {
+        Self {
+            buffered: vec![packet],
+            spilled: Vec::new(),
+            spill_file: None,
+        }
+    }
+}
+
+/// Packets that could not be dissected into a flow, with the number of packets per reason
+#[derive(Default)]
+pub struct Unclassified {
+    packets: Vec<UnclassifiedPacket>,
+    reasons: Vec<(String, usize)>,
+}
+
+impl Unclassified {
+    fn insert_packet(&mut self, header: &PacketHeader, error: &Error, offset: u64) {
+        let reason = error.to_string();
+        let reason_index = match self.reasons.iter().position(|(r, _)| *r == reason) {
+            Some(reason_index) => reason_index,
+            None => {
+                self.reasons.push((reason, 0));
+                self.reasons.len() - 1
+            }
+        };
+        self.reasons[reason_index].1 += 1;
+
+        let packet = UnclassifiedPacket {
+            timestamp: header.timestamp,
+            size: header.actual_length.as_usize(),
+            reason: reason_index,
+            offset,
+        };
+
+        self.packets.push(packet);
+    }
+
+    /// Reads unclassified packets written by [`Self::write_index`] from a flow index
+    fn read_index(cursor: &mut ReadOnlyIndexCursor) -> Result<Self, Error> {
+        let reason_count = cursor.get_usize()?;
+        let reasons = (0..reason_count)
+            .map(|_| Ok((cursor.get_string()?, cursor.get_usize()?)))
+            .collect::<Result<Vec<(String, usize)>, Error>>()?;
+
+        let packet_count = cursor.get_usize()?;
+        let packets = (0..packet_count)
+            .map(|_| {
+                let packet = UnclassifiedPacket {
+                    timestamp: cursor.get_timestamp()?,
+                    size: cursor.get_usize()?,
+                    reason: cursor.get_usize()?,
+                    offset: cursor.get_u64()?,
+                };
+
+                if packet.reason < reasons.len() {
+                    Ok(packet)
+                } else {
+                    Err(Error::InvalidIndexFile)
+                }
+            })
+            .collect::<Result<Vec<UnclassifiedPacket>, Error>>()?;
+
+        Ok(Self { packets, reasons })
+    }
+
+    fn write_index(&self, cursor: &mut WriteOnlyIndexCursor) {
+        cursor.put_usize(self.reasons.len());
+        for (reason, packet_count) in &self.reasons {
+            cursor.put_string(reason);
+            cursor.put_usize(*packet_count);
+        }
+
+        cursor.put_usize(self.packets.len());
+        for packet in &self.packets {
+            cursor.put_timestamp(packet.timestamp);
+            cursor.put_usize(packet.size);
+            cursor.put_usize(packet.reason);
+            cursor.put_u64(packet.offset);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub fn packets(&self) -> &[UnclassifiedPacket] {
+        self.packets.as_slice()
+    }
+
+    /// Reasons packets could not be classified, with the number of packets for each
+    pub fn reasons(&self) -> &[(String, usize)] {
+        self.reasons.as_slice()
+    }
+
+    pub fn reason(&self, packet: &UnclassifiedPacket) -> &str {
+        &self.reasons[packet.reason].0
+    }
+
+    /// Copies the unclassified records from `source` into a new capture file at `path`,
+    /// returning the number of packets written
+    pub fn export(
+        &self,
+        source: &mut ReadOnlyCapture,
+        capture_header: CaptureHeader,
+        path: impl AsRef<Path>,
+    ) -> Result<usize, Error> {
+        let offsets = self.packets.iter().map(|p| p.offset);
+
+        export_records(source, capture_header, offsets, None, path)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct UnclassifiedPacket {
+    pub timestamp: Timestamp,
+    pub size: usize,
+    reason: usize,
+    offset: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+    use std::process::Command;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::compression::{Compression, FileWriter};
+    use crate::five_tuple::FiveTuple;
+    use crate::flow::{
+        extract_flows, extract_flows_in_chunks, open_flows, FlowOptions, FlowTable, Flows,
+    };
+    use crate::flow_index;
+    use crate::packet::Packet;
+    use crate::packet_dissection::{Dissection, PacketDissection};
+    use crate::packet_header::{PacketHeader, PacketLength, Timestamp};
+    use crate::packet_layer::{LinkLayerType, TransportLayerType};
+    use crate::parallel;
+    use crate::stream::{StreamChunk, StreamData};
+    use crate::tcp::ConnectionEnd;
+    use crate::test_util::{get_path_for_new_temp_file, write_temp_file};
+
+    const CAPTURE_HEADER: [u8; 24] = [
+        0xa1, 0xb2, 0xc3, 0xd4, 0x00, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+    ];
+
+    const UDP_PACKET: [u8; 42] = [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01,
+        0x0a, 0x00, 0x00, 0x02, 0x14, 0xe9, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00,
+    ];
+
+    const ARP_PACKET: [u8; 42] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00,
+        0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x0a, 0x00,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x02,
+    ];
+
+    #[test]
+    fn extracting_flows_with_unclassified_packets_succeeds() {
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for packet in [&UDP_PACKET[..], &ARP_PACKET, &UDP_PACKET[..20], &ARP_PACKET] {
+            buffer.extend(record(packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let flows = extract_flows(&file_path, FlowOptions::default()).unwrap();
+
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows.tab_count(), 2);
+        assert_eq!(flows.unclassified().packets().len(), 3);
+        assert_eq!(
+            flows.unclassified().reasons(),
+            [
+                ("unknown network layer type 0x0806".to_string(), 2),
+                ("packet is too short for its headers".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn extracting_icmp_flows_succeeds() {
+        let echo_request = ipv4_packet([10, 0, 0, 1], [10, 0, 0, 2], 1, &[8, 0, 0, 0, 0, 7, 0, 1]);
+        let echo_reply = ipv4_packet([10, 0, 0, 2], [10, 0, 0, 1], 1, &[0, 0, 0, 0, 0, 7, 0, 1]);
+        let other_echo_request =
+            ipv4_packet([10, 0, 0, 1], [10, 0, 0, 2], 1, &[8, 0, 0, 0, 0, 8, 0, 1]);
+        let port_unreachable = ipv4_packet(
+            [10, 0, 0, 2],
+            [10, 0, 0, 1],
+            1,
+            &[&[3, 3, 0, 0, 0, 0, 0, 0], &UDP_PACKET[14..]].concat(),
+        );
+
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for packet in [
+            &UDP_PACKET[..],
+            &echo_request,
+            &echo_reply,
+            &other_echo_request,
+            &port_unreachable,
+        ] {
+            buffer.extend(record(packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let flows = extract_flows(&file_path, FlowOptions::default()).unwrap();
+        let mut packet_counts = flows.values().map(|f| f.packet_count).collect::<Vec<_>>();
+        packet_counts.sort_unstable();
+        assert_eq!(packet_counts, [1, 1, 1, 2]);
+        assert!(flows
+            .values()
+            .any(|f| f.five_tuple.to_string() == "[ICMP] 10.0.0.1 ↔ 10.0.0.2 type 8 code 0 id 7"));
+
+        let options = FlowOptions {
+            attribute_icmp_errors: true,
+            ..FlowOptions::default()
+        };
+        let flows = extract_flows(&file_path, options).unwrap();
+        let udp_flow = flows
+            .values()
+            .find(|f| f.protocol == TransportLayerType::Udp)
+            .unwrap();
+        assert_eq!(flows.len(), 3);
+        assert_eq!(udp_flow.packet_count, 2);
+        assert!(!udp_flow.packets().unwrap()[1].from_initiator_to_respondent);
+    }
+
+    #[test]
+    fn attributing_icmp_errors_to_tcp_flows_succeeds() {
+        let client = [10, 0, 0, 1];
+        let server = [10, 0, 0, 2];
+        let syn = ipv4_packet(client, server, 6, &tcp_segment(1234, 80, 100, 0x02));
+        let syn_ack = ipv4_packet(server, client, 6, &tcp_segment(80, 1234, 500, 0x12));
+        // Errors quote the IP header and the first eight bytes of the segment
+        let icmp_error = |segment: &[u8]| {
+            ipv4_packet(
+                server,
+                client,
+                1,
+                &[&[3, 3, 0, 0, 0, 0, 0, 0], &segment[14..42]].concat(),
+            )
+        };
+        let other_syn = ipv4_packet(client, server, 6, &tcp_segment(1235, 80, 100, 0x02));
+
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for packet in [&syn, &syn_ack, &icmp_error(&syn), &icmp_error(&other_syn)] {
+            buffer.extend(record(packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let options = FlowOptions {
+            attribute_icmp_errors: true,
+            ..FlowOptions::default()
+        };
+        let flows = extract_flows(&file_path, options).unwrap();
+        assert_eq!(flows.len(), 2);
+        for flow in flows.values() {
+            assert_eq!(flow.connection_end(), Some(ConnectionEnd::Open));
+            let metrics = flow.tcp_metrics().unwrap();
+            assert_eq!(metrics.zero_windows, 0);
+            assert_eq!(metrics.duplicate_acks, 0);
+            assert_eq!(metrics.out_of_order, 0);
+        }
+        assert_eq!(flows.get(0).packet_count, 3);
+        assert!(flows.get(0).start_observed);
+        assert!(!flows.get(1).start_observed);
+    }
+
+    #[test]
+    fn extracting_flows_of_loopback_captures_succeeds() {
+        let localhost = [127, 0, 0, 1];
+        let loopback_packet = |source_port, destination_port, flags| {
+            let segment = tcp_segment(source_port, destination_port, 100, flags);
+            let packet = ipv4_packet(localhost, localhost, 6, &segment);
+            // BSD loopback header with the address family instead of an Ethernet header
+            [&[0x00, 0x00, 0x00, 0x02][..], &packet[14..]].concat()
+        };
+
+        let mut buffer = [&CAPTURE_HEADER[..20], &[0x00, 0x00, 0x00, 0x00]].concat();
+        for packet in [
+            loopback_packet(40000, 80, 0x02),
+            loopback_packet(80, 40000, 0x12),
+            loopback_packet(40000, 80, 0x10),
+        ] {
+            buffer.extend(record(&packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let flows = extract_flows(&file_path, FlowOptions::default()).unwrap();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows.get(0).packet_count, 3);
+        assert_eq!(flows.get(0).initiator.port(), 40000);
+    }
+
+    #[test]
+    fn extracting_flows_of_vlan_and_mpls_packets_succeeds() {
+        let tagged = |tags: &[u8]| [&UDP_PACKET[..12], tags, &UDP_PACKET[12..]].concat();
+        let vlan_packet = tagged(&[0x81, 0x00, 0x00, 0x0a]);
+        let qinq_packet = tagged(&[0x88, 0xa8, 0x00, 0x14, 0x81, 0x00, 0x00, 0x1e]);
+        let mpls_packet = [
+            &UDP_PACKET[..12],
+            &[0x88, 0x47, 0x00, 0x01, 0x00, 0x40, 0x00, 0x02, 0x01, 0x40],
+            &UDP_PACKET[14..],
+        ]
+        .concat();
+
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for packet in [&UDP_PACKET[..], &vlan_packet, &qinq_packet, &mpls_packet] {
+            buffer.extend(record(packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let flows = extract_flows(&file_path, FlowOptions::default()).unwrap();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows.get(0).packet_count, 4);
+
+        let options = FlowOptions {
+            vlan_in_flow_key: true,
+            ..FlowOptions::default()
+        };
+        let flows = extract_flows(&file_path, options).unwrap();
+        let mut names = flows
+            .values()
+            .map(|flow| flow.five_tuple.to_string())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "[UDP] 10.0.0.1:5353 ↔ 10.0.0.2:53",
+                "[UDP] 10.0.0.1:5353 ↔ 10.0.0.2:53 on VLAN 10",
+                "[UDP] 10.0.0.1:5353 ↔ 10.0.0.2:53 on VLAN 20.30",
+            ]
+        );
+    }
+
+    #[test]
+    fn extracting_flows_of_fragmented_packets_succeeds() {
+        let datagram = [
+            &UDP_PACKET[34..38],
+            &[0x00, 0x10, 0x00, 0x00],
+            &[1, 2, 3, 4, 5, 6, 7, 8],
+        ]
+        .concat();
+        let ipv4_fragment = |flags_offset: u16, payload: &[u8]| {
+            let length = (20 + payload.len() as u16).to_be_bytes();
+            [
+                &UDP_PACKET[..14],
+                &[0x45, 0x00],
+                &length,
+                &[0x12, 0x34],
+                &flags_offset.to_be_bytes(),
+                &UDP_PACKET[22..34],
+                payload,
+            ]
+            .concat()
+        };
+        let ipv6_fragment = |offset_flags: u16, payload: &[u8]| {
+            let length = (8 + payload.len() as u16).to_be_bytes();
+            [
+                &UDP_PACKET[..12],
+                &[0x86, 0xdd, 0x60, 0x00, 0x00, 0x00],
+                &length,
+                &[44, 64],
+                &[0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+                &[0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+                &[17, 0],
+                &offset_flags.to_be_bytes(),
+                &[0x00, 0x00, 0x56, 0x78],
+                payload,
+            ]
+            .concat()
+        };
+
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for packet in [
+            ipv4_fragment(0x0001, &datagram[8..]),
+            ipv6_fragment(0x0001, &datagram[..8]),
+            ipv4_fragment(0x2000, &datagram[..8]),
+            ipv4_fragment(0x2000, &datagram[..8]),
+            ipv6_fragment(0x0008, &datagram[8..]),
+        ] {
+            buffer.extend(record(&packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let flows = extract_flows(&file_path, FlowOptions::default()).unwrap();
+        let mut names = flows
+            .values()
+            .map(|flow| flow.five_tuple.to_string())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "[UDP] 10.0.0.1:5353 ↔ 10.0.0.2:53",
+                "[UDP] fe80::1:5353 ↔ fe80::2:53",
+            ]
+        );
+        assert!(flows.values().all(|flow| flow.packet_count == 2));
+        assert_eq!(
+            flows.unclassified().reasons(),
+            [(
+                "fragment of a datagram that could not be reassembled".to_string(),
+                1
+            )]
+        );
+    }
+
+    #[test]
+    fn extracting_flows_with_idle_timeout_succeeds() {
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for seconds in [1, 5, 20, 21, 40] {
+            buffer.extend(record_at(seconds, &UDP_PACKET));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let options = FlowOptions {
+            idle_timeout: Some(10),
+            ..FlowOptions::default()
+        };
+        let flows = extract_flows(&file_path, options).unwrap();
+
+        let packet_counts = flows.values().map(|f| f.packet_count).collect::<Vec<_>>();
+        assert_eq!(packet_counts, [2, 2, 1]);
+        assert_eq!(flows.get(1).first_timestamp.seconds(), 20);
+    }
+
+    #[test]
+    fn extracting_flows_of_reused_tcp_connections_succeeds() {
+        let client = [10, 0, 0, 1];
+        let server = [10, 0, 0, 2];
+        let to_server = |sequence_number: u32, flags: u8| {
+            ipv4_packet(
+                client,
+                server,
+                6,
+                &tcp_segment(1234, 80, sequence_number, flags),
+            )
+        };
+        let to_client = |sequence_number: u32, flags: u8| {
+            ipv4_packet(
+                server,
+                client,
+                6,
+                &tcp_segment(80, 1234, sequence_number, flags),
+            )
+        };
+
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for (seconds, packet) in [
+            (1, to_server(100, 0x02)),
+            (1, to_client(500, 0x12)),
+            (2, to_server(101, 0x11)),
+            (2, to_client(501, 0x11)),
+            (3, to_server(200, 0x02)),
+            (3, to_client(0, 0x04)),
+            (4, to_server(200, 0x02)),
+            (100, to_server(300, 0x02)),
+            (200, to_server(400, 0x02)),
+        ] {
+            buffer.extend(record_at(seconds, &packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let flows = extract_flows(&file_path, FlowOptions::default()).unwrap();
+
+        let summary = flows
+            .values()
+            .map(|f| (f.packet_count, f.connection_end().unwrap().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (4, "closed".to_string()),
+                (3, "reset".to_string()),
+                (1, "superseded".to_string()),
+                (1, "open".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn timing_out_flows_in_parallel_matches_sequential_extraction() {
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        let to_server = |segment: &[u8]| ipv4_packet([10, 0, 0, 1], [10, 0, 0, 2], 6, segment);
+        for port in 1000..1020 {
+            buffer.extend(record_at(0, &to_server(&tcp_segment(port, 80, 100, 0x02))));
+        }
+        // Only reaches one partition, after every flow has been idle for longer than the timeout
+        let ack = to_server(&tcp_segment(1000, 80, 101, 0x10));
+        buffer.extend(record_at(100, &ack));
+        let file_path = write_temp_file(&buffer);
+
+        for threads in [1, 4] {
+            let options = FlowOptions {
+                threads: Some(threads),
+                idle_timeout: Some(60),
+                ..FlowOptions::default()
+            };
+            let flows = extract_flows_in_chunks(&file_path, options, 100).unwrap();
+            let ends = flows
+                .values()
+                .map(|flow| flow.connection_end().unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(ends.len(), 21);
+            assert!(ends[..20].iter().all(|end| *end == ConnectionEnd::TimedOut));
+            assert_eq!(ends[20], ConnectionEnd::Open);
+        }
+    }
+
+    #[test]
+    fn detecting_initiators_of_flows_succeeds() {
+        let client = [10, 0, 0, 1];
+        let server = [10, 0, 0, 2];
+        let udp_datagram = |source_port: u16, destination_port: u16| {
+            [
+                &source_port.to_be_bytes()[..],
+                &destination_port.to_be_bytes(),
+                &[0x00, 0x08, 0x00, 0x00],
+            ]
+            .concat()
+        };
+
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for packet in [
+            // SYN-ACK captured before the SYN it answers
+            ipv4_packet(server, client, 6, &tcp_segment(80, 1234, 500, 0x12)),
+            ipv4_packet(client, server, 6, &tcp_segment(1234, 80, 100, 0x02)),
+            // Capture started in the middle of the connection
+            ipv4_packet(server, client, 6, &tcp_segment(443, 4321, 700, 0x10)),
+            // Capture started between a DNS query and its response
+            ipv4_packet(server, client, 17, &udp_datagram(53, 5353)),
+        ] {
+            buffer.extend(record(&packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let flows = extract_flows(&file_path, FlowOptions::default()).unwrap();
+
+        let summary = flows
+            .values()
+            .map(|f| {
+                let directions = f
+                    .packets()
+                    .unwrap()
+                    .iter()
+                    .map(|p| p.from_initiator_to_respondent)
+                    .collect::<Vec<_>>();
+
+                (f.initiator.to_string(), f.start_observed, directions)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("10.0.0.1:1234".to_string(), true, vec![false, true]),
+                ("10.0.0.1:4321".to_string(), false, vec![false]),
+                ("10.0.0.1:5353".to_string(), false, vec![false]),
+            ]
+        );
+    }
+
+    #[test]
+    fn reassembling_tcp_streams_succeeds() {
+        let client = [10, 0, 0, 1];
+        let server = [10, 0, 0, 2];
+        let to_server = |sequence_number: u32, flags: u8, data: &[u8]| {
+            let segment = [&tcp_segment(1234, 80, sequence_number, flags)[..], data].concat();
+            ipv4_packet(client, server, 6, &segment)
+        };
+        let to_client = |sequence_number: u32, flags: u8, data: &[u8]| {
+            let segment = [&tcp_segment(80, 1234, sequence_number, flags)[..], data].concat();
+            ipv4_packet(server, client, 6, &segment)
+        };
+
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for packet in [
+            to_server(100, 0x02, b""),
+            to_client(500, 0x12, b""),
+            to_server(104, 0x18, b" /"),
+            // Ethernet padding is not part of the payload
+            [to_server(101, 0x18, b"GET"), vec![0; 6]].concat(),
+            to_client(501, 0x18, b"OK"),
+            to_server(101, 0x18, b"GET"),
+        ] {
+            buffer.extend(record(&packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let flows = extract_flows(&file_path, FlowOptions::default()).unwrap();
+        let stream = flows.reassemble(0).unwrap();
+
+        assert_eq!(
+            stream.chunks,
+            [
+                StreamChunk {
+                    from_initiator: true,
+                    data: StreamData::Bytes(b"GET /".to_vec()),
+                },
+                StreamChunk {
+                    from_initiator: false,
+                    data: StreamData::Bytes(b"OK".to_vec()),
+                },
+            ]
+        );
+        assert_eq!(stream.retransmitted_bytes, 3);
+
+        let flow = flows.get(0);
+        assert_eq!(flow.direction_statistics(true).packet_count, 4);
+        assert_eq!(flow.direction_statistics(true).payload_size, 8);
+        assert_eq!(flow.direction_statistics(false).packet_count, 2);
+        assert_eq!(flow.direction_statistics(false).payload_size, 2);
+    }
+
+    #[test]
+    fn extracting_flows_with_memory_limit_succeeds() {
+        let reply = [
+            &UDP_PACKET[..26],
+            &UDP_PACKET[30..34],
+            &UDP_PACKET[26..30],
+            &UDP_PACKET[36..38],
+            &UDP_PACKET[34..36],
+            &UDP_PACKET[38..],
+        ]
+        .concat();
+
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for (seconds, packet) in [(1, &UDP_PACKET[..]), (2, &reply), (3, &UDP_PACKET)] {
+            buffer.extend(record_at(seconds, packet));
+        }
+        let file_path = write_temp_file(&buffer);
+
+        let options = FlowOptions {
+            memory_limit: Some(0),
+            ..FlowOptions::default()
+        };
+        let flows = extract_flows(&file_path, options).unwrap();
+        let flow = flows.get(0);
+        let packets = flow.packets().unwrap();
+
+        assert_eq!(flow.packet_count, 3);
+        assert_eq!(
+            packets
+                .iter()
+                .map(|p| (p.from_initiator_to_respondent, p.offset))
+                .collect::<Vec<_>>(),
+            [(true, 24), (false, 82), (true, 140)]
+        );
+
+        let export_path = write_temp_file(&[]);
+        assert_eq!(flows.export(0, &export_path).unwrap(), 3);
+    }
+
+    #[test]
+    fn finishing_idle_flows_succeeds() {
+        let options = FlowOptions {
+            idle_timeout: Some(2),
+            memory_limit: Some(0),
+            ..FlowOptions::default()
+        };
+        let mut flows = FlowTable::new(options, 1);
+
+        for index in 0..1000 {
+            let segment = tcp_segment(40000 + index, 80, 0, 0x02);
+            let packet = Packet::from(ipv4_packet([10, 0, 0, 1], [10, 0, 0, 2], 6, &segment));
+            let Dissection::Complete(dissection) =
+                PacketDissection::from_packet(&packet, LinkLayerType::En10Mb, false).unwrap()
+            else {
+                panic!("unfragmented packet is dissected completely");
+            };
+            let length = PacketLength(packet.as_slice().len() as u32);
+            let header = PacketHeader {
+                timestamp: Timestamp::new(i64::from(index), 0),
+                captured_length: length,
+                actual_length: length,
+            };
+            let five_tuple = FiveTuple::from_packet_dissection(&dissection, false);
+
+            flows.insert(five_tuple, header, &dissection, 24).unwrap();
+
+            // Flows idle for longer than the timeout only keep their record
+            assert!(flows.active.len() <= 3);
+            assert_eq!(flows.buffered_packets, 0);
+        }
+
+        let flows = flows.into_flows();
+        assert_eq!(flows.len(), 1000);
+        assert_eq!(flows[0].connection_end(), Some(ConnectionEnd::TimedOut));
+        assert_eq!(flows[0].packets().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn extracting_flows_in_parallel_matches_sequential_extraction() {
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for index in 0..200u16 {
+            let port = (1000 + index % 7).to_be_bytes();
+            let udp = [&port, &UDP_PACKET[36..38], &[0x00, 0x08, 0x00, 0x00]].concat();
+            let packet = match index % 5 {
+                0 => ARP_PACKET.to_vec(),
+                1 => ipv4_packet([10, 0, 0, 2], [10, 0, 0, 1], 17, &udp),
+                _ => ipv4_packet([10, 0, 0, 1], [10, 0, 0, 2], 17, &udp),
+            };
+            buffer.extend(record_at(u32::from(index / 10), &packet));
+        }
+        buffer.extend(&record(&UDP_PACKET)[..30]);
+        let file_path = write_temp_file(&buffer);
+
+        let extract = |file_path: &str, threads, chunk_length| {
+            let options = FlowOptions {
+                threads: Some(threads),
+                idle_timeout: Some(5),
+                ..FlowOptions::default()
+            };
+            let flows = extract_flows_in_chunks(file_path, options, chunk_length).unwrap();
+            let summary = flows
+                .values()
+                .map(|flow| {
+                    let offsets = flow
+                        .packets()
+                        .unwrap()
+                        .iter()
+                        .map(|packet| packet.offset)
+                        .collect::<Vec<_>>();
+                    (
+                        flow.five_tuple.to_string(),
+                        flow.initiator,
+                        flow.packet_count,
+                        flow.total_size,
+                        flow.flow_duration,
+                        flow.average_interarrival_time,
+                        offsets,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let unclassified = flows
+                .unclassified()
+                .packets()
+                .iter()
+                .map(|packet| packet.offset)
+                .collect::<Vec<_>>();
+
+            (summary, unclassified, flows.truncated_after())
+        };
+
+        let sequential = extract(&file_path, 1, parallel::CHUNK_LENGTH);
+        assert_eq!(sequential.1.len(), 40);
+        assert_eq!(sequential.2, Some(200));
+        for chunk_length in [1, 100, 1000] {
+            assert_eq!(extract(&file_path, 4, chunk_length), sequential);
+        }
+
+        // Compressed captures are read one record at a time whatever the number of threads
+        let compressed_path = format!("{file_path}.gz");
+        let mut writer = FileWriter::new(
+            File::create(&compressed_path).unwrap(),
+            Some(Compression::Gzip),
+        )
+        .unwrap();
+        writer.write_all(&buffer).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            extract(&compressed_path, 4, parallel::CHUNK_LENGTH),
+            sequential
+        );
+    }
+
+    #[test]
+    fn reopening_flows_from_index_succeeds() {
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        let reply_udp = [&UDP_PACKET[36..38], &UDP_PACKET[34..36], &UDP_PACKET[38..]].concat();
+        let reply = ipv4_packet([10, 0, 0, 2], [10, 0, 0, 1], 17, &reply_udp);
+        for packet in [&UDP_PACKET[..], &ARP_PACKET, &reply, &UDP_PACKET[..20]] {
+            buffer.extend(record(packet));
+        }
+        let file_path = write_temp_file(&buffer);
+        let index_path = flow_index::path(file_path.as_ref());
+
+        let summary = |flows: &Flows| {
+            let flow_summary = flows
+                .values()
+                .map(|flow| {
+                    let directions = flow
+                        .packets()
+                        .unwrap()
+                        .iter()
+                        .map(|packet| (packet.from_initiator_to_respondent, packet.offset))
+                        .collect::<Vec<_>>();
+                    (
+                        flow.five_tuple.to_string(),
+                        flow.initiator,
+                        flow.first_timestamp,
+                        flow.packet_count,
+                        flow.total_size,
+                        flow.direction_statistics(false).payload_size,
+                        flow.direction_statistics(false).average_interarrival_time(),
+                        directions,
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            (
+                flow_summary,
+                flows.unclassified().reasons().to_vec(),
+                flows.file_extension(),
+                flows.capture_header(),
+            )
+        };
+
+        let extracted = open_flows(&file_path, FlowOptions::default()).unwrap();
+        assert!(index_path.exists());
+
+        let reopened = open_flows(&file_path, FlowOptions::default()).unwrap();
+        assert!(reopened.get(0).packets.spill_file.is_some());
+        assert_eq!(summary(&reopened), summary(&extracted));
+
+        let export_path = format!("{file_path}.flow");
+        assert_eq!(reopened.export(0, &export_path).unwrap(), 2);
+        assert_eq!(reopened.export(1, &export_path).unwrap(), 2);
+
+        let options = FlowOptions {
+            no_index: true,
+            ..FlowOptions::default()
+        };
+        std::fs::remove_file(&index_path).unwrap();
+        open_flows(&file_path, options).unwrap();
+        assert!(!index_path.exists());
+    }
+
+    #[test]
+    fn streaming_flows_from_named_pipe_succeeds() {
+        let mut buffer = CAPTURE_HEADER.to_vec();
+        for packet in [&UDP_PACKET[..], &ARP_PACKET, &UDP_PACKET[..]] {
+            buffer.extend(record(packet));
+        }
+        buffer.extend(&record(&UDP_PACKET)[..30]);
+
+        let file_path = get_path_for_new_temp_file();
+        let status = Command::new("mkfifo").arg(&file_path).status().unwrap();
+        assert!(status.success());
+        let writer_path = file_path.clone();
+        let writer = thread::spawn(move || {
+            File::create(writer_path)
+                .unwrap()
+                .write_all(&buffer)
+                .unwrap();
+        });
+
+        let mut flows = open_flows(&file_path, FlowOptions::default()).unwrap();
+        while flows.is_streaming() {
+            flows.update().unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        writer.join().unwrap();
+
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows.get(0).packet_count, 2);
+        assert_eq!(flows.unclassified().packets().len(), 1);
+        assert_eq!(flows.truncated_after(), Some(3));
+        assert!(!flow_index::path(file_path.as_ref()).exists());
+
+        let export_path = format!("{file_path}.flow");
+        assert_eq!(flows.export(0, &export_path).unwrap(), 2);
+        assert_eq!(flows.export(1, &export_path).unwrap(), 1);
+
+        assert!(flows.is_temporary());
+        let save_path = format!("{file_path}.pcap");
+        assert_eq!(flows.save(&save_path).unwrap(), 3);
+        let saved_flows = extract_flows(&save_path, FlowOptions::default()).unwrap();
+        assert_eq!(saved_flows.len(), 1);
+        assert_eq!(saved_flows.get(0).packet_count, 2);
+        assert_eq!(saved_flows.truncated_after(), None);
+    }
+
+    fn ipv4_packet(source: [u8; 4], destination: [u8; 4], protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let length = (20 + payload.len() as u16).to_be_bytes();
+        [
+            &UDP_PACKET[..14],
+            &[0x45, 0x00],
+            &length,
+            &[0x00, 0x00, 0x00, 0x00, 0x40, protocol, 0x00, 0x00],
+            &source,
+            &destination,
+            payload,
+        ]
+        .concat()
+    }
+
+    fn tcp_segment(
+        source_port: u16,
+        destination_port: u16,
+        sequence_number: u32,
+        flags: u8,
+    ) -> Vec<u8> {
+        [
+            &source_port.to_be_bytes()[..],
+            &destination_port.to_be_bytes(),
+            &sequence_number.to_be_bytes(),
+            &[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0],
+        ]
+        .concat()
+    }
+
+    fn record(packet: &[u8]) -> Vec<u8> {
+        record_at(1, packet)
+    }
+
+    fn record_at(seconds: u32, packet: &[u8]) -> Vec<u8> {
+        let length = (packet.len() as u32).to_be_bytes();
+        [
+            &seconds.to_be_bytes(),
+            &[0, 0, 0, 0],
+            &length[..],
+            &length,
+            packet,
+        ]
+        .concat()
+    }
+}
@@ -0,0 +1,451 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use crate::capture_header::TimestampPrecision;
+use crate::error::Error;
+use crate::packet_header::Timestamp;
+use crate::packet_layer::TransportLayerType;
+use crate::spill::SpillFile;
+
+/// Extension appended to the name of a capture file to name its index file
+pub const INDEX_EXTENSION: &str = "flowidx";
+
+const MAGIC: &[u8; 8] = b"PFSINDEX";
+const VERSION: u32 = 6;
+
+/// Length of the parts at the start and the end of a capture file hashed into its stamp
+const HASHED_LENGTH: u64 = 64 * 1024;
+
+/// Contents of an index file matching its capture
+pub struct FlowIndex {
+    /// Flows and other results of extraction, as written by [`write`]
+    pub metadata: Vec<u8>,
+    /// Index file the packet lists of the flows are read back from
+    pub packet_file: Arc<SpillFile>,
+    /// Position of the packet lists in the index file
+    pub packets_start: u64,
+}
+
+/// Path of the index file of the capture at `capture_path`, next to it
+pub fn path(capture_path: &Path) -> PathBuf {
+    let mut file_name = OsString::from(capture_path.as_os_str());
+    file_name.push(".");
+    file_name.push(INDEX_EXTENSION);
+
+    PathBuf::from(file_name)
+}
+
+/// Reads the index file of the capture at `capture_path`, returning `None` if there is none, or
+/// if it was written for another version of the capture or with other `options`
+///
+/// Index files start with a magic number, the format version, the stamp of the capture and the
+/// options, followed by the length of the metadata, the metadata and the packet lists.
+pub fn read(capture_path: &Path, options: &[u8]) -> Result<Option<FlowIndex>, Error> {
+    let Some(stamp) = CaptureStamp::of(capture_path)? else {
+        return Ok(None);
+    };
+
+    let index_path = path(capture_path);
+    let mut file = match File::open(&index_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::IndexFileRead(e)),
+    };
+
+    let mut expected_header = WriteOnlyIndexCursor::new();
+    compose_header(&mut expected_header, stamp, options);
+    let expected_header = expected_header.into_vec();
+
+    let mut header = vec![0u8; expected_header.len() + 8];
+    match file.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(Error::IndexFileRead(e)),
+    }
+    if header[..expected_header.len()] != expected_header {
+        return Ok(None);
+    }
+
+    let mut cursor = ReadOnlyIndexCursor::new(&header[expected_header.len()..]);
+    let metadata_length = cursor.get_usize()?;
+    let mut metadata = Vec::new();
+    Read::by_ref(&mut file)
+        .take(metadata_length as u64)
+        .read_to_end(&mut metadata)
+        .map_err(Error::IndexFileRead)?;
+    if metadata.len() != metadata_length {
+        return Err(Error::InvalidIndexFile);
+    }
+
+    let index = FlowIndex {
+        metadata,
+        packet_file: Arc::new(SpillFile::open(index_path)?),
+        packets_start: (header.len() + metadata_length) as u64,
+    };
+
+    Ok(Some(index))
+}
+
+/// Writes the index file of the capture at `capture_path`, replacing any previous one
+///
+/// `packet_lists` are written one after the other, in the order positions in `metadata` refer to
+/// them. Nothing is written if the capture is not a regular file.
+pub fn write(
+    capture_path: &Path,
+    options: &[u8],
+    metadata: &[u8],
+    packet_lists: impl Iterator<Item = Result<Vec<u8>, Error>>,
+) -> Result<(), Error> {
+    let Some(stamp) = CaptureStamp::of(capture_path)? else {
+        return Ok(());
+    };
+
+    let mut cursor = WriteOnlyIndexCursor::new();
+    compose_header(&mut cursor, stamp, options);
+    cursor.put_u64(metadata.len() as u64);
+
+    // The index is written next to its final path and renamed, so that an interrupted write
+    // never leaves an index that looks valid behind
+    let index_path = path(capture_path);
+    let mut temporary_path = index_path.clone().into_os_string();
+    temporary_path.push(".tmp");
+
+    let result = (|| {
+        let file = File::create(&temporary_path).map_err(Error::IndexFileWrite)?;
+        let mut file = BufWriter::new(file);
+
+        file.write_all(&cursor.into_vec())
+            .and_then(|()| file.write_all(metadata))
+            .map_err(Error::IndexFileWrite)?;
+        for packet_list in packet_lists {
+            file.write_all(&packet_list?)
+                .map_err(Error::IndexFileWrite)?;
+        }
+        file.flush().map_err(Error::IndexFileWrite)?;
+
+        std::fs::rename(&temporary_path, &index_path).map_err(Error::IndexFileWrite)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary_path);
+    }
+
+    result
+}
+
+fn compose_header(cursor: &mut WriteOnlyIndexCursor, stamp: CaptureStamp, options: &[u8]) {
+    cursor.put_slice(MAGIC);
+    cursor.put_u32(VERSION);
+    cursor.put_u64(stamp.length);
+    cursor.put_u64(stamp.modified_seconds);
+    cursor.put_u32(stamp.modified_nanos);
+    cursor.put_u64(stamp.hash);
+    cursor.put_u64(options.len() as u64);
+    cursor.put_slice(options);
+}
+
+/// Identifies the version of a capture file an index was written for
+///
+/// Besides the length and modification time of the file, the start and the end of its content are
+/// hashed, which catches captures replaced by a copy with the same length and modification time.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct CaptureStamp {
+    length: u64,
+    modified_seconds: u64,
+    modified_nanos: u32,
+    hash: u64,
+}
+
+impl CaptureStamp {
+    /// Stamp of the capture file at `path`, or `None` if it is not a regular file or its
+    /// modification time is not available
+    fn of(path: &Path) -> Result<Option<Self>, Error> {
+        // Named pipes are not opened, which would wait for a writer and consume their data
+        let metadata = std::fs::metadata(path).map_err(Error::OpenCaptureFile)?;
+        if !metadata.is_file() {
+            return Ok(None);
+        }
+        let mut file = File::open(path).map_err(Error::OpenCaptureFile)?;
+        let Some(modified) = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        else {
+            return Ok(None);
+        };
+
+        let length = metadata.len();
+        let mut hash = Fnv1a::default();
+        let mut buffer = Vec::new();
+        for start in [0, length.saturating_sub(HASHED_LENGTH).max(HASHED_LENGTH)] {
+            buffer.clear();
+            file.seek(SeekFrom::Start(start))
+                .map_err(Error::CaptureFileSeek)?;
+            Read::by_ref(&mut file)
+                .take(HASHED_LENGTH)
+                .read_to_end(&mut buffer)
+                .map_err(Error::CaptureFileRead)?;
+            hash.write(&buffer);
+        }
+
+        let stamp = Self {
+            length,
+            modified_seconds: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            hash: hash.0,
+        };
+
+        Ok(Some(stamp))
+    }
+}
+
+/// 64-bit FNV-1a hash, which unlike the hasher of the standard library is stable across builds
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+}
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xCBF2_9CE4_8422_2325)
+    }
+}
+
+/// Reads the little-endian values of index files, failing with [`Error::InvalidIndexFile`] if
+/// the buffer ends before them
+pub struct ReadOnlyIndexCursor<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> ReadOnlyIndexCursor<'a> {
+    pub const fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer }
+    }
+
+    pub fn get_slice(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if self.buffer.len() < length {
+            return Err(Error::InvalidIndexFile);
+        }
+        let (slice, rest) = self.buffer.split_at(length);
+        self.buffer = rest;
+
+        Ok(slice)
+    }
+
+    fn get_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let array = self
+            .get_slice(N)?
+            .try_into()
+            .expect("slice is as long as the array");
+
+        Ok(array)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.get_slice(1)?[0])
+    }
+
+    pub fn get_bool(&mut self) -> Result<bool, Error> {
+        Ok(self.get_u8()? != 0)
+    }
+
+    pub fn get_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.get_array()?))
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.get_array()?))
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.get_array()?))
+    }
+
+    pub fn get_i64(&mut self) -> Result<i64, Error> {
+        Ok(i64::from_le_bytes(self.get_array()?))
+    }
+
+    pub fn get_usize(&mut self) -> Result<usize, Error> {
+        usize::try_from(self.get_u64()?).map_err(|_| Error::InvalidIndexFile)
+    }
+
+    pub fn get_optional_u32(&mut self) -> Result<Option<u32>, Error> {
+        if self.get_bool()? {
+            Ok(Some(self.get_u32()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn get_string(&mut self) -> Result<String, Error> {
+        let length = self.get_usize()?;
+        let bytes = self.get_slice(length)?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidIndexFile)
+    }
+
+    pub fn get_ip_addr(&mut self) -> Result<IpAddr, Error> {
+        let ip_addr = match self.get_u8()? {
+            4 => IpAddr::V4(Ipv4Addr::from(self.get_array::<4>()?)),
+            6 => IpAddr::V6(Ipv6Addr::from(self.get_array::<16>()?)),
+            _ => return Err(Error::InvalidIndexFile),
+        };
+
+        Ok(ip_addr)
+    }
+
+    pub fn get_socket_addr(&mut self) -> Result<SocketAddr, Error> {
+        Ok(SocketAddr::new(self.get_ip_addr()?, self.get_u16()?))
+    }
+
+    pub fn get_timestamp_precision(&mut self) -> Result<TimestampPrecision, Error> {
+        match self.get_u8()? {
+            0 => Ok(TimestampPrecision::Micro),
+            1 => Ok(TimestampPrecision::Nano),
+            _ => Err(Error::InvalidIndexFile),
+        }
+    }
+
+    pub fn get_timestamp(&mut self) -> Result<Timestamp, Error> {
+        Ok(Timestamp::new(self.get_i64()?, self.get_u32()?))
+    }
+
+    pub fn get_transport_layer_type(&mut self) -> Result<TransportLayerType, Error> {
+        TransportLayerType::try_from(self.get_u8()?).map_err(|_| Error::InvalidIndexFile)
+    }
+}
+
+/// Writes the little-endian values of index files
+pub struct WriteOnlyIndexCursor {
+    buffer: Vec<u8>,
+}
+
+impl WriteOnlyIndexCursor {
+    pub const fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    pub fn put_slice(&mut self, value: &[u8]) {
+        self.buffer.extend_from_slice(value);
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn put_bool(&mut self, value: bool) {
+        self.put_u8(u8::from(value));
+    }
+
+    pub fn put_u16(&mut self, value: u16) {
+        self.put_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.put_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u64(&mut self, value: u64) {
+        self.put_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_i64(&mut self, value: i64) {
+        self.put_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_usize(&mut self, value: usize) {
+        self.put_u64(value as u64);
+    }
+
+    pub fn put_optional_u32(&mut self, value: Option<u32>) {
+        self.put_bool(value.is_some());
+        if let Some(value) = value {
+            self.put_u32(value);
+        }
+    }
+
+    pub fn put_string(&mut self, value: &str) {
+        self.put_usize(value.len());
+        self.put_slice(value.as_bytes());
+    }
+
+    pub fn put_ip_addr(&mut self, value: IpAddr) {
+        match value {
+            IpAddr::V4(addr) => {
+                self.put_u8(4);
+                self.put_slice(&addr.octets());
+            }
+            IpAddr::V6(addr) => {
+                self.put_u8(6);
+                self.put_slice(&addr.octets());
+            }
+        }
+    }
+
+    pub fn put_socket_addr(&mut self, value: SocketAddr) {
+        self.put_ip_addr(value.ip());
+        self.put_u16(value.port());
+    }
+
+    pub fn put_timestamp_precision(&mut self, value: TimestampPrecision) {
+        self.put_u8(match value {
+            TimestampPrecision::Micro => 0,
+            TimestampPrecision::Nano => 1,
+        });
+    }
+
+    pub fn put_timestamp(&mut self, value: Timestamp) {
+        self.put_i64(value.seconds());
+        self.put_u32(value.subsec_nanos());
+    }
+
+    pub fn put_transport_layer_type(&mut self, value: TransportLayerType) {
+        self.put_u8(u8::from(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::Path;
+
+    use crate::flow_index::{path, read, write};
+    use crate::test_util::write_temp_file;
+
+    #[test]
+    fn reading_index_of_changed_capture_fails() {
+        let capture_path = write_temp_file(&[1, 2, 3]);
+        let capture_path = Path::new(&capture_path);
+
+        write(capture_path, &[7], &[4, 5], [Ok(vec![6])].into_iter()).unwrap();
+        let index = read(capture_path, &[7]).unwrap().unwrap();
+        assert_eq!(index.metadata, [4, 5]);
+        assert_eq!(index.packet_file.read(index.packets_start, 1).unwrap(), [6]);
+        drop(index);
+
+        assert!(read(capture_path, &[8]).unwrap().is_none());
+
+        File::create(capture_path)
+            .unwrap()
+            .write_all(&[1, 2, 4])
+            .unwrap();
+        assert!(read(capture_path, &[7]).unwrap().is_none());
+
+        std::fs::remove_file(path(capture_path)).unwrap();
+        assert!(read(capture_path, &[7]).unwrap().is_none());
+    }
+}
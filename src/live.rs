@@ -0,0 +1,282 @@
+use std::io::{Cursor, Read};
+
+use crate::capture_header::{CaptureHeader, MaximumPacketLength, TimestampPrecision, Version};
+use crate::endianness_aware_cursor::Endianness;
+use crate::error::Error;
+use crate::packet_header::{PacketHeader, PacketLength, Timestamp};
+use crate::packet_layer::LinkLayerType;
+
+/// Packets captured from a network interface, read as a pcap capture
+///
+/// The capture starts with a capture header, followed by a record for every packet sent or
+/// received on the interface. Packets are captured without their link-layer header, which is
+/// replaced with a Linux cooked capture header, the way `tcpdump -i any` captures them.
+pub struct LiveCapture {
+    #[cfg(target_os = "linux")]
+    socket: std::os::fd::OwnedFd,
+    #[cfg(target_os = "linux")]
+    packet: Vec<u8>,
+    pending: Cursor<Vec<u8>>,
+}
+
+impl LiveCapture {
+    /// Longest packet captured, longer packets are cut short
+    const MAXIMUM_PACKET_LENGTH: u32 = 262_144;
+
+    /// Length of the Linux cooked capture header put in front of every packet
+    const COOKED_HEADER_LENGTH: usize = 16;
+
+    /// Packet type of packets sent by the capturing host, which `libc` does not define
+    #[cfg(target_os = "linux")]
+    const PACKET_OUTGOING: u8 = 4;
+
+    fn capture_header() -> CaptureHeader {
+        CaptureHeader {
+            endianness: Endianness::Identical,
+            timestamp_precision: TimestampPrecision::Nano,
+            version: Version(2, 4),
+            maximum_packet_length: MaximumPacketLength(Self::MAXIMUM_PACKET_LENGTH),
+            frame_cyclic_sequence: None,
+            link_layer_type: LinkLayerType::LinuxSll,
+        }
+    }
+
+    /// Composes the record of a packet of `actual_length` bytes whose first `packet.len()` bytes
+    /// were captured, prefixed with `cooked_header`
+    fn compose_record(
+        timestamp: Timestamp,
+        cooked_header: &[u8],
+        packet: &[u8],
+        actual_length: usize,
+    ) -> Vec<u8> {
+        let packet_header = PacketHeader {
+            timestamp,
+            captured_length: PacketLength((cooked_header.len() + packet.len()) as u32),
+            actual_length: PacketLength((cooked_header.len() + actual_length) as u32),
+        };
+
+        let mut record = packet_header.compose(Endianness::Identical, TimestampPrecision::Nano);
+        record.extend_from_slice(cooked_header);
+        record.extend_from_slice(packet);
+
+        record
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl LiveCapture {
+    /// Opens a packet socket capturing every packet sent or received on the interface named
+    /// `interface`
+    ///
+    /// Capturing requires the `CAP_NET_RAW` capability, which usually means running as root.
+    pub fn open(interface: &str) -> Result<Self, Error> {
+        use std::ffi::CString;
+        use std::io::Error as IoError;
+        use std::os::fd::{FromRawFd, OwnedFd};
+
+        let name = CString::new(interface).map_err(|_| Error::UnknownNetworkInterface)?;
+        // SAFETY: `name` is a valid NUL-terminated string.
+        let interface_index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if interface_index == 0 {
+            return Err(Error::UnknownNetworkInterface);
+        }
+
+        let protocol = (libc::ETH_P_ALL as u16).to_be();
+        // SAFETY: creating a socket has no memory safety requirements.
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                i32::from(protocol),
+            )
+        };
+        if fd < 0 {
+            return Err(Error::OpenNetworkInterface(IoError::last_os_error()));
+        }
+        // SAFETY: `fd` is a socket that was just opened and is owned by nothing else.
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: `sockaddr_ll` is plain data, for which all zeroes is a valid value.
+        let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as u16;
+        address.sll_protocol = protocol;
+        address.sll_ifindex = interface_index as i32;
+
+        // SAFETY: `address` is a valid `sockaddr_ll` and its length is passed along with it.
+        let result = unsafe {
+            libc::bind(
+                fd,
+                std::ptr::from_ref(&address).cast(),
+                std::mem::size_of::<libc::sockaddr_ll>() as u32,
+            )
+        };
+        if result < 0 {
+            return Err(Error::OpenNetworkInterface(IoError::last_os_error()));
+        }
+
+        let live_capture = Self {
+            socket,
+            packet: vec![0; Self::MAXIMUM_PACKET_LENGTH as usize - Self::COOKED_HEADER_LENGTH],
+            pending: Cursor::new(Self::capture_header().compose()),
+        };
+
+        Ok(live_capture)
+    }
+
+    /// Waits for the next packet on the interface and composes its record
+    fn receive(&mut self) -> std::io::Result<Vec<u8>> {
+        use std::io::Error as IoError;
+        use std::io::ErrorKind;
+        use std::os::fd::AsRawFd;
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        loop {
+            // SAFETY: `sockaddr_ll` is plain data, for which all zeroes is a valid value.
+            let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+            let mut address_length = std::mem::size_of::<libc::sockaddr_ll>() as u32;
+
+            // SAFETY: the buffer and address are valid for writes of the lengths passed along
+            // with them.
+            let length = unsafe {
+                libc::recvfrom(
+                    self.socket.as_raw_fd(),
+                    self.packet.as_mut_ptr().cast(),
+                    self.packet.len(),
+                    libc::MSG_TRUNC,
+                    std::ptr::from_mut(&mut address).cast(),
+                    &mut address_length,
+                )
+            };
+            if length < 0 {
+                let e = IoError::last_os_error();
+                if e.kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+
+                return Err(e);
+            }
+
+            // Packets sent on a loopback interface are also received on it, so keep only one
+            // of the two copies.
+            if address.sll_hatype == libc::ARPHRD_LOOPBACK
+                && address.sll_pkttype == Self::PACKET_OUTGOING
+            {
+                continue;
+            }
+
+            let since_epoch = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let timestamp =
+                Timestamp::new(since_epoch.as_secs() as i64, since_epoch.subsec_nanos());
+
+            let actual_length = length as usize;
+            let captured_length = actual_length.min(self.packet.len());
+
+            return Ok(Self::compose_record(
+                timestamp,
+                &compose_cooked_header(&address),
+                &self.packet[..captured_length],
+                actual_length,
+            ));
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl LiveCapture {
+    pub fn open(_interface: &str) -> Result<Self, Error> {
+        Err(Error::UnsupportedLiveCapture)
+    }
+
+    fn receive(&mut self) -> std::io::Result<Vec<u8>> {
+        unreachable!("live captures cannot be opened on this platform")
+    }
+}
+
+impl Read for LiveCapture {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pending.position() == self.pending.get_ref().len() as u64 {
+            self.pending = Cursor::new(self.receive()?);
+        }
+
+        self.pending.read(buf)
+    }
+}
+
+/// Composes the Linux cooked capture header of a packet received from `address`
+#[cfg(target_os = "linux")]
+fn compose_cooked_header(address: &libc::sockaddr_ll) -> Vec<u8> {
+    let address_length = usize::from(address.sll_halen).min(address.sll_addr.len());
+
+    let mut header = Vec::with_capacity(LiveCapture::COOKED_HEADER_LENGTH);
+    header.extend_from_slice(&u16::from(address.sll_pkttype).to_be_bytes());
+    header.extend_from_slice(&address.sll_hatype.to_be_bytes());
+    header.extend_from_slice(&(address_length as u16).to_be_bytes());
+    header.extend_from_slice(&address.sll_addr[..address_length]);
+    header.resize(14, 0);
+    header.extend_from_slice(&u16::from_be(address.sll_protocol).to_be_bytes());
+
+    header
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::io::ErrorKind;
+    use std::net::UdpSocket;
+
+    use crate::capture::ReadOnlyCapture;
+    use crate::error::Error;
+    use crate::live::LiveCapture;
+    use crate::packet_dissection::{Dissection, PacketDissection, TransportLayer};
+    use crate::packet_layer::LinkLayerType;
+
+    #[test]
+    fn capturing_loopback_packets_succeeds() {
+        let live_capture = match LiveCapture::open("lo") {
+            Ok(live_capture) => live_capture,
+            // Capturing needs privileges the tests may not run with.
+            Err(Error::OpenNetworkInterface(e)) if e.kind() == ErrorKind::PermissionDenied => {
+                return;
+            }
+            Err(e) => panic!("opening loopback interface failed: {e}"),
+        };
+        let (capture_header, mut capture) =
+            ReadOnlyCapture::from_reader(Box::new(live_capture)).unwrap();
+        assert_eq!(capture_header.link_layer_type, LinkLayerType::LinuxSll);
+
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let payload = b"pcap_flow_splitter live capture test";
+        sender
+            .send_to(payload, receiver.local_addr().unwrap())
+            .unwrap();
+
+        let expected_ports = (
+            sender.local_addr().unwrap().port(),
+            receiver.local_addr().unwrap().port(),
+        );
+        loop {
+            let packet_header = capture.next_record().unwrap().unwrap();
+            let packet = capture.packet();
+            assert_eq!(
+                packet.as_slice().len(),
+                packet_header.captured_length.0 as usize
+            );
+
+            let Ok(Dissection::Complete(dissection)) =
+                PacketDissection::from_packet(&packet, LinkLayerType::LinuxSll, false)
+            else {
+                continue;
+            };
+            let TransportLayer::Udp(source_port, destination_port, _) = dissection.transport_layer
+            else {
+                continue;
+            };
+            if (source_port, destination_port) == expected_ports {
+                assert!(packet.as_slice().ends_with(payload));
+                break;
+            }
+        }
+    }
+}
@@ -1,28 +1,106 @@
 use std::ffi::OsString;
+use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+use crate::flow::FlowOptions;
 
 mod capture;
 mod capture_file;
 mod capture_header;
+mod compression;
 mod endianness_aware_cursor;
 mod error;
 mod five_tuple;
+mod flow;
+mod flow_index;
+mod live;
+mod merge;
 mod packet;
 mod packet_dissection;
 mod packet_header;
 mod packet_layer;
+mod parallel;
+mod pcapng;
+mod reassembly;
+mod spill;
+mod split;
+mod statistics;
+mod stream;
+mod tcp;
+#[cfg(test)]
+mod test_util;
 mod ui;
 
 fn main() {
     let args = Args::parse();
 
-    ui::run(args.file_path).unwrap();
+    let result = match args.command {
+        None => ui::run(args.file_path, args.interface, args.flow_options),
+        Some(Command::Split {
+            file_paths,
+            output_directory,
+            template,
+        }) => split::run(&file_paths, output_directory, &template, args.flow_options),
+        Some(Command::Streams {
+            file_paths,
+            output_directory,
+            template,
+        }) => split::dump_streams(&file_paths, output_directory, &template, args.flow_options),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
 }
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    #[arg(short, long, default_value = None)]
-    file_path: Option<OsString>,
+    /// Capture files to open in the flow viewer, or - for standard input; several files,
+    /// directories and glob patterns are merged into one capture
+    #[arg(short, long, num_args = 1..)]
+    file_path: Vec<OsString>,
+    /// Network interface to capture packets from in the flow viewer, Linux only
+    #[arg(short, long, conflicts_with = "file_path")]
+    interface: Option<String>,
+    #[command(flatten)]
+    flow_options: FlowOptions,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Writes every flow in a capture file into its own capture file, without launching the TUI
+    Split {
+        /// Capture files to split, or - for standard input; several files, directories and glob
+        /// patterns are merged into one capture
+        #[arg(required = true, num_args = 1..)]
+        file_paths: Vec<OsString>,
+        /// Directory to write the flow capture files into
+        #[arg(short, long, default_value = ".")]
+        output_directory: PathBuf,
+        /// Name of each flow capture file; {index}, {protocol}, {src_addr}, {src_port},
+        /// {dst_addr}, {dst_port}, {timestamp} and {extension} are replaced with the properties
+        /// of the flow
+        #[arg(short, long, default_value = split::DEFAULT_FILE_NAME_TEMPLATE)]
+        template: String,
+    },
+    /// Writes the reassembled data each endpoint of every TCP flow in a capture file sent into
+    /// its own file, without launching the TUI
+    Streams {
+        /// Capture files to read, or - for standard input; several files, directories and glob
+        /// patterns are merged into one capture
+        #[arg(required = true, num_args = 1..)]
+        file_paths: Vec<OsString>,
+        /// Directory to write the stream files into
+        #[arg(short, long, default_value = ".")]
+        output_directory: PathBuf,
+        /// Name of each stream file; {direction} is replaced with client or server, the other
+        /// placeholders as for split
+        #[arg(short, long, default_value = split::DEFAULT_STREAM_FILE_NAME_TEMPLATE)]
+        template: String,
+    },
 }
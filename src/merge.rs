@@ -0,0 +1,327 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ffi::OsString;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::capture::ReadOnlyCapture;
+use crate::capture_header::Version;
+use crate::compression::is_capture_file_name;
+use crate::endianness_aware_cursor::Endianness;
+use crate::error::Error;
+use crate::packet_header::{PacketHeader, Timestamp};
+use crate::pcapng::{
+    BlockOption, EnhancedPacketBlock, InterfaceDescriptionBlock, SectionHeaderBlock,
+    TimestampResolution,
+};
+
+/// Expands the capture paths given on the command line, replacing directories with the captures
+/// they contain and glob patterns with the files they match, both in alphabetical order
+pub fn expand_paths(paths: &[OsString]) -> Result<Vec<PathBuf>, Error> {
+    let mut expanded = Vec::new();
+    for path in paths.iter().map(PathBuf::from) {
+        if path.is_dir() {
+            let mut captures = std::fs::read_dir(&path)
+                .map_err(Error::ReadDirContent)?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file() && is_capture_file_name(path))
+                .collect::<Vec<PathBuf>>();
+            captures.sort();
+
+            expanded.extend(captures);
+        } else if !path.exists() && is_glob_pattern(&path) {
+            let pattern = path.to_string_lossy().to_string();
+            let matches = glob::glob(&pattern)
+                .map_err(|_| Error::InvalidGlobPattern(pattern))?
+                .filter_map(Result::ok)
+                .filter(|path| path.is_file());
+
+            expanded.extend(matches);
+        } else {
+            expanded.push(path);
+        }
+    }
+
+    if expanded.is_empty() {
+        return Err(Error::NoCaptureFiles);
+    }
+
+    Ok(expanded)
+}
+
+fn is_glob_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Source files of the records of a merged capture
+#[derive(Debug)]
+pub struct SourceMap {
+    names: Vec<String>,
+    /// Offsets in the merged capture from which on records come from another source, with the
+    /// index of that source, in order
+    runs: Mutex<Vec<(u64, usize)>>,
+}
+
+impl SourceMap {
+    /// Name of the file the record at `offset` in the merged capture was read from
+    pub fn name(&self, offset: u64) -> Option<&str> {
+        let runs = self.runs.lock().unwrap_or_else(PoisonError::into_inner);
+        let run = runs
+            .partition_point(|(start, _)| *start <= offset)
+            .checked_sub(1)?;
+
+        self.names.get(runs[run].1).map(String::as_str)
+    }
+
+    fn push_run(&self, start: u64, source: usize) {
+        self.runs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((start, source));
+    }
+}
+
+/// Records of several captures merged in the order of their timestamps, read as a pcapng capture
+///
+/// Every interface of every source becomes an interface of the merged capture, commented with
+/// the name of its source, so that sources of different link-layer types can be merged.
+/// Timestamps of every precision are written with nanosecond resolution. Sources that end in the
+/// middle of a record are read up to their last complete record.
+pub struct MergedCapture {
+    sources: Vec<ReadOnlyCapture>,
+    /// Interface of each source, used for the records of pcap sources
+    source_interfaces: Vec<InterfaceDescriptionBlock>,
+    /// Next record of every source that has not ended, earliest first
+    next_records: BinaryHeap<Reverse<(Timestamp, usize, PacketHeader)>>,
+    /// Interfaces of the sources written to the merged capture so far, with the source they
+    /// belong to and the interface as it was written
+    interfaces: Vec<(usize, InterfaceDescriptionBlock, InterfaceDescriptionBlock)>,
+    source_map: Arc<SourceMap>,
+    last_source: Option<usize>,
+    /// Number of bytes of the merged capture composed so far
+    composed: u64,
+    pending: Cursor<Vec<u8>>,
+}
+
+impl MergedCapture {
+    const ENDIANNESS: Endianness = Endianness::Identical;
+    const TIMESTAMP_RESOLUTION: TimestampResolution = TimestampResolution::Decimal(9);
+
+    /// Opens the captures at `paths`, returning the merged capture along with the map telling
+    /// which of them each of its records comes from
+    pub fn open(paths: &[PathBuf]) -> Result<(Self, Arc<SourceMap>), Error> {
+        let source_map = Arc::new(SourceMap {
+            names: paths
+                .iter()
+                .map(|path| {
+                    path.file_name()
+                        .unwrap_or(path.as_os_str())
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect(),
+            runs: Mutex::new(Vec::new()),
+        });
+
+        let section_header = SectionHeaderBlock {
+            endianness: Self::ENDIANNESS,
+            version: Version(1, 0),
+            options: Vec::new(),
+        }
+        .compose();
+
+        let mut merged_capture = Self {
+            sources: Vec::new(),
+            source_interfaces: Vec::new(),
+            next_records: BinaryHeap::new(),
+            interfaces: Vec::new(),
+            source_map: Arc::clone(&source_map),
+            last_source: None,
+            composed: section_header.len() as u64,
+            pending: Cursor::new(section_header),
+        };
+
+        for (source, path) in paths.iter().enumerate() {
+            let (capture_header, capture) = ReadOnlyCapture::open(path)?;
+            merged_capture.sources.push(capture);
+            merged_capture
+                .source_interfaces
+                .push(InterfaceDescriptionBlock::from(capture_header));
+            merged_capture.advance(source)?;
+        }
+
+        Ok((merged_capture, source_map))
+    }
+
+    /// Reads the next record of `source`, if it has one
+    fn advance(&mut self, source: usize) -> Result<(), Error> {
+        match self.sources[source].next_record() {
+            Ok(Some(header)) => {
+                self.next_records
+                    .push(Reverse((header.timestamp, source, header)));
+
+                Ok(())
+            }
+            Ok(None) | Err(Error::TruncatedRecordHeader(_) | Error::TruncatedRecord(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Composes the earliest record of all sources, preceded by the description of its interface
+    /// if it was not written before
+    fn compose_next(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let Some(Reverse((_, source, header))) = self.next_records.pop() else {
+            return Ok(None);
+        };
+
+        if self.last_source != Some(source) {
+            self.source_map.push_run(self.composed, source);
+            self.last_source = Some(source);
+        }
+
+        let capture = &self.sources[source];
+        let (interface, options) = match capture.pcapng_context() {
+            Some((_, interface, options)) => (interface, options.to_vec()),
+            None => (&self.source_interfaces[source], Vec::new()),
+        };
+
+        let mut buffer = Vec::new();
+        let interface_id = match self
+            .interfaces
+            .iter()
+            .position(|(s, i, _)| *s == source && i == interface)
+        {
+            Some(interface_id) => interface_id,
+            None => {
+                let mut merged_interface =
+                    interface.with_timestamp_resolution(Self::TIMESTAMP_RESOLUTION);
+                merged_interface
+                    .options
+                    .push(BlockOption::comment(&self.source_map.names[source]));
+                buffer.extend(merged_interface.compose(Self::ENDIANNESS));
+                self.interfaces
+                    .push((source, interface.clone(), merged_interface));
+
+                self.interfaces.len() - 1
+            }
+        };
+        let merged_interface = &self.interfaces[interface_id].2;
+
+        let block = EnhancedPacketBlock {
+            interface_id: interface_id as u32,
+            timestamp: merged_interface.units(header.timestamp),
+            original_length: header.actual_length.0,
+            packet: capture.packet().as_slice().to_vec(),
+            options,
+        };
+        buffer.extend(block.compose(Self::ENDIANNESS));
+        self.composed += buffer.len() as u64;
+
+        self.advance(source)?;
+
+        Ok(Some(buffer))
+    }
+}
+
+impl Read for MergedCapture {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pending.position() == self.pending.get_ref().len() as u64 {
+            match self.compose_next() {
+                Ok(Some(buffer)) => self.pending = Cursor::new(buffer),
+                Ok(None) => return Ok(0),
+                Err(e) => return Err(std::io::Error::other(e.to_string())),
+            }
+        }
+
+        self.pending.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::ReadOnlyCapture;
+    use crate::merge::MergedCapture;
+    use crate::packet_header::Timestamp;
+    use crate::test_util::write_temp_file;
+
+    const MICRO_CAPTURE_HEADER: [u8; 24] = [
+        0xa1, 0xb2, 0xc3, 0xd4, 0x00, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+    ];
+
+    const NANO_CAPTURE_HEADER: [u8; 24] = [
+        0x4d, 0x3c, 0xb2, 0xa1, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0xff, 0xff, 0x00, 0x00, 0x65, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn merging_captures_orders_records_by_timestamp() {
+        let micro_path = write_temp_file(
+            &[
+                &MICRO_CAPTURE_HEADER[..],
+                &big_endian_record(1, 500_000, 0xAA),
+                &big_endian_record(3, 0, 0xCC),
+            ]
+            .concat(),
+        );
+        let nano_path = write_temp_file(
+            &[
+                &NANO_CAPTURE_HEADER[..],
+                &little_endian_record(2, 250_000_000, 0xBB),
+                &little_endian_record(1, 400_000_000, 0xDD),
+            ]
+            .concat(),
+        );
+
+        let (merged_capture, source_map) =
+            MergedCapture::open(&[micro_path.clone().into(), nano_path.clone().into()]).unwrap();
+        let (_, mut capture) = ReadOnlyCapture::from_reader(Box::new(merged_capture)).unwrap();
+
+        let mut records = Vec::new();
+        loop {
+            let offset = capture.position();
+            let Some(header) = capture.next_record().unwrap() else {
+                break;
+            };
+            let source = source_map.name(offset).unwrap().to_string();
+            records.push((header.timestamp, capture.packet().as_slice()[0], source));
+        }
+
+        let micro_name = micro_path.rsplit('/').next().unwrap().to_string();
+        let nano_name = nano_path.rsplit('/').next().unwrap().to_string();
+        // Records of one source stay in their order even where their timestamps are not
+        assert_eq!(
+            records,
+            [
+                (Timestamp::new(1, 500_000_000), 0xAA, micro_name.clone()),
+                (Timestamp::new(2, 250_000_000), 0xBB, nano_name.clone()),
+                (Timestamp::new(1, 400_000_000), 0xDD, nano_name),
+                (Timestamp::new(3, 0), 0xCC, micro_name),
+            ]
+        );
+    }
+
+    fn big_endian_record(seconds: u32, fraction: u32, byte: u8) -> Vec<u8> {
+        [
+            &seconds.to_be_bytes()[..],
+            &fraction.to_be_bytes(),
+            &4u32.to_be_bytes(),
+            &4u32.to_be_bytes(),
+            &[byte; 4],
+        ]
+        .concat()
+    }
+
+    fn little_endian_record(seconds: u32, fraction: u32, byte: u8) -> Vec<u8> {
+        [
+            &seconds.to_le_bytes()[..],
+            &fraction.to_le_bytes(),
+            &4u32.to_le_bytes(),
+            &4u32.to_le_bytes(),
+            &[byte; 4],
+        ]
+        .concat()
+    }
+}
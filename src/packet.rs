@@ -1,23 +1,48 @@
+use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
 
+/// Bytes of a captured packet, either owned or borrowed from a memory-mapped capture file
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
-pub struct Packet {
-    buffer: Vec<u8>,
+pub struct Packet<'a> {
+    buffer: Cow<'a, [u8]>,
 }
 
-impl From<Vec<u8>> for Packet {
+impl From<Vec<u8>> for Packet<'static> {
     fn from(buffer: Vec<u8>) -> Self {
+        Self {
+            buffer: Cow::Owned(buffer),
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for Packet<'a> {
+    fn from(buffer: &'a [u8]) -> Self {
+        Self {
+            buffer: Cow::Borrowed(buffer),
+        }
+    }
+}
+
+impl<'a> From<Cow<'a, [u8]>> for Packet<'a> {
+    fn from(buffer: Cow<'a, [u8]>) -> Self {
         Self { buffer }
     }
 }
 
-impl Packet {
+impl Packet<'_> {
     pub fn as_slice(&self) -> &[u8] {
-        self.buffer.as_slice()
+        &self.buffer
+    }
+
+    /// Copies the bytes of the packet if they are borrowed
+    pub fn into_owned(self) -> Packet<'static> {
+        Packet {
+            buffer: Cow::Owned(self.buffer.into_owned()),
+        }
     }
 }
 
-impl Display for Packet {
+impl Display for Packet<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "Packet: [ Length: {} ]", self.buffer.len())
     }
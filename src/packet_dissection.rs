@@ -1,8 +1,12 @@
-use std::net::{AddrParseError, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::fmt::{Display, Formatter};
+use std::net::{
+    AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6,
+};
 use std::ops::{BitAnd, Mul, Shr};
 
 use crate::endianness_aware_cursor::{Endianness, ReadOnlyEndiannessAwareCursor};
 use crate::error::Error;
+use crate::flow_index::{ReadOnlyIndexCursor, WriteOnlyIndexCursor};
 use crate::packet::Packet;
 use crate::packet_layer::{
     ApplicationLayerType, LinkLayerType, NetworkLayerType, TransportLayerType,
@@ -11,47 +15,225 @@ use crate::packet_layer::{
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct PacketDissection {
     pub link_layer: LinkLayer,
+    /// Tunnel the packet was decapsulated from, in which case the network and transport layers
+    /// are those of the encapsulated packet
+    pub tunnel: Option<Tunnel>,
     pub network_layer: NetworkLayer,
     pub transport_layer: TransportLayer,
+    /// Length of the payload after the transport layer header, according to the network layer
+    pub payload_length: usize,
+    /// Whether this is the dissection of an ICMP error message as the packet it quotes, see
+    /// [`Self::quoted_flow_dissection`]
+    pub attributed: bool,
+}
+
+/// Outcome of dissecting a captured packet
+pub enum Dissection {
+    Complete(PacketDissection),
+    /// Fragment of an IP datagram, which can only be dissected further once reassembled
+    Fragment(Fragment),
 }
 
 impl PacketDissection {
     pub fn from_packet(
         packet: &Packet,
-        endianness: Endianness,
         link_layer_type: LinkLayerType,
-    ) -> Result<Self, Error> {
-        let mut cursor = ReadOnlyEndiannessAwareCursor::new(packet.as_slice(), endianness);
+        decapsulate_tunnels: bool,
+    ) -> Result<Dissection, Error> {
+        Self::dissect(packet.as_slice(), link_layer_type, decapsulate_tunnels)
+            .map(|(dissection, _)| dissection)
+    }
+
+    /// Header and captured payload of a TCP segment, or `None` for packets that are not TCP
+    /// segments and for fragments of IP datagrams
+    ///
+    /// The payload ends where the IP header says it does, so it excludes link-layer padding, and
+    /// is shorter than the header says if the packet was cut short by the snapshot length.
+    pub fn tcp_segment<'a>(
+        packet: &'a Packet,
+        link_layer_type: LinkLayerType,
+        decapsulate_tunnels: bool,
+    ) -> Result<Option<(TcpHeader, &'a [u8])>, Error> {
+        let (dissection, rest) =
+            Self::dissect(packet.as_slice(), link_layer_type, decapsulate_tunnels)?;
+        let Dissection::Complete(dissection) = dissection else {
+            return Ok(None);
+        };
+
+        let segment = dissection.tcp_header().map(|header| {
+            let payload_length = rest.len().min(header.payload_length as usize);
+
+            (header, &rest[..payload_length])
+        });
+
+        Ok(segment)
+    }
+
+    /// Dissects a packet, also returning what follows its transport layer header
+    fn dissect(
+        packet: &[u8],
+        link_layer_type: LinkLayerType,
+        decapsulate_tunnels: bool,
+    ) -> Result<(Dissection, &[u8]), Error> {
+        let result = Self::dissect_layers(packet, link_layer_type, decapsulate_tunnels);
+        if result.is_err() && decapsulate_tunnels {
+            // VXLAN and GENEVE are only told apart from other UDP traffic by their port, so a
+            // datagram to one of their ports that does not encapsulate a valid packet stays UDP
+            if let Ok((Dissection::Complete(dissection), rest)) =
+                Self::dissect_layers(packet, link_layer_type, false)
+            {
+                if matches!(dissection.transport_layer, TransportLayer::Udp(..)) {
+                    return Ok((Dissection::Complete(dissection), rest));
+                }
+            }
+        }
+
+        result
+    }
+
+    fn dissect_layers(
+        packet: &[u8],
+        link_layer_type: LinkLayerType,
+        decapsulate_tunnels: bool,
+    ) -> Result<(Dissection, &[u8]), Error> {
+        // Protocol headers are in network byte order regardless of the capture file endianness
+        let mut cursor = ReadOnlyEndiannessAwareCursor::new(packet, Endianness::Identical);
 
         let link_layer = LinkLayer::parse(&mut cursor, link_layer_type)?;
-        let network_layer = NetworkLayer::parse(&mut cursor, link_layer.get_network_layer_type())?;
-        let transport_layer =
-            TransportLayer::parse(&mut cursor, network_layer.get_transport_layer_type())?;
+
+        let mut network_layer_type = link_layer.get_network_layer_type();
+        let mut tunnel = None;
+        if decapsulate_tunnels {
+            if let Some((outer_tunnel, inner_network_layer_type)) =
+                Tunnel::parse(&mut cursor, network_layer_type)?
+            {
+                tunnel = Some(outer_tunnel);
+                network_layer_type = inner_network_layer_type;
+            }
+        }
+
+        let header = NetworkLayer::parse_header(&mut cursor, network_layer_type)?;
+        let protocol = TransportLayerType::try_from(header.protocol)?;
+
+        if let Some(fragment_header) = header.fragment {
+            ensure_remaining(&cursor, header.payload_length)?;
+
+            let fragment = Fragment {
+                link_layer,
+                tunnel,
+                source: header.source,
+                destination: header.destination,
+                protocol,
+                identification: fragment_header.identification,
+                offset: fragment_header.offset,
+                more_fragments: fragment_header.more_fragments,
+                payload: cursor.get_slice(header.payload_length).to_vec(),
+            };
+
+            return Ok((Dissection::Fragment(fragment), &[]));
+        }
+
+        let network_layer = NetworkLayer::new(header.source, header.destination, protocol);
+        let transport_layer = TransportLayer::parse(&mut cursor, protocol, header.payload_length)?;
+        let payload_length = transport_layer.payload_length(header.payload_length);
 
         let packet_dissection = Self {
             link_layer,
+            tunnel,
             network_layer,
             transport_layer,
+            payload_length,
+            attributed: false,
+        };
+        let rest = cursor.get_slice(cursor.remaining());
+
+        Ok((Dissection::Complete(packet_dissection), rest))
+    }
+
+    /// Dissects the payload of a reassembled datagram, taking the headers below it from
+    /// `fragment`, one of the fragments of the datagram
+    pub fn from_reassembled(fragment: Fragment, payload: &[u8]) -> Result<Self, Error> {
+        let mut cursor = ReadOnlyEndiannessAwareCursor::new(payload, Endianness::Identical);
+
+        let network_layer =
+            NetworkLayer::new(fragment.source, fragment.destination, fragment.protocol);
+        let transport_layer = TransportLayer::parse(&mut cursor, fragment.protocol, payload.len())?;
+        let payload_length = transport_layer.payload_length(payload.len());
+
+        let packet_dissection = Self {
+            link_layer: fragment.link_layer,
+            tunnel: fragment.tunnel,
+            network_layer,
+            transport_layer,
+            payload_length,
+            attributed: false,
         };
 
         Ok(packet_dissection)
     }
 
+    /// Dissection of the TCP or UDP packet an ICMP error message was sent in response to, as
+    /// if it was sent back towards the source of the quoted packet
+    pub fn quoted_flow_dissection(&self) -> Option<Self> {
+        let TransportLayer::Icmp(IcmpMessage {
+            quoted: Some(quoted),
+            ..
+        }) = &self.transport_layer
+        else {
+            return None;
+        };
+
+        let (destination, source) = (quoted.source_port, quoted.destination_port);
+        let transport_layer = match quoted.network_layer.get_transport_layer_type() {
+            TransportLayerType::Tcp => TransportLayer::Tcp(
+                source,
+                destination,
+                ApplicationLayerType::OctetArray,
+                TcpHeader::default(),
+            ),
+            _ => TransportLayer::Udp(source, destination, ApplicationLayerType::OctetArray),
+        };
+
+        let dissection = Self {
+            link_layer: self.link_layer.clone(),
+            tunnel: self.tunnel.clone(),
+            network_layer: quoted.network_layer.reversed(),
+            transport_layer,
+            payload_length: 0,
+            attributed: true,
+        };
+
+        Some(dissection)
+    }
+
+    /// Header of the TCP segment, if the packet is one
+    ///
+    /// ICMP error messages attributed to a TCP flow have no header, as the segment they quote was
+    /// sent before and does not tell the state of the connection.
+    pub const fn tcp_header(&self) -> Option<TcpHeader> {
+        match &self.transport_layer {
+            TransportLayer::Tcp(.., header) if !self.attributed => Some(*header),
+            _ => None,
+        }
+    }
+
+    /// Source and destination of the packet, with zero ports for protocols without ports
     pub fn socket_addrs(&self) -> Result<(SocketAddr, SocketAddr), AddrParseError> {
-        let addrs = match (&self.network_layer, &self.transport_layer) {
-            (
-                NetworkLayer::IPv4(addr_a, addr_b, _),
-                TransportLayer::Tcp(port_a, port_b, _) | TransportLayer::Udp(port_a, port_b, _),
-            ) => (
-                SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(*addr_a), *port_a)),
-                SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(*addr_b), *port_b)),
+        let (port_a, port_b) = match &self.transport_layer {
+            TransportLayer::Tcp(port_a, port_b, ..) | TransportLayer::Udp(port_a, port_b, _) => {
+                (*port_a, *port_b)
+            }
+            TransportLayer::Icmp(_) => (0, 0),
+        };
+
+        let addrs = match &self.network_layer {
+            NetworkLayer::IPv4(addr_a, addr_b, _) => (
+                SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(*addr_a), port_a)),
+                SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(*addr_b), port_b)),
             ),
-            (
-                NetworkLayer::IPv6(addr_a, addr_b, _),
-                TransportLayer::Tcp(port_a, port_b, _) | TransportLayer::Udp(port_a, port_b, _),
-            ) => (
-                SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::from(*addr_a), *port_a, 0, 0)),
-                SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::from(*addr_b), *port_b, 0, 0)),
+            NetworkLayer::IPv6(addr_a, addr_b, _) => (
+                SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::from(*addr_a), port_a, 0, 0)),
+                SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::from(*addr_b), port_b, 0, 0)),
             ),
         };
 
@@ -61,28 +243,154 @@ impl PacketDissection {
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum LinkLayer {
-    Ethernet(NetworkLayerType),
+    /// Ethernet frame with the IDs of its VLAN tags and the labels of its MPLS label stack,
+    /// outermost first
+    Ethernet(NetworkLayerType, Vec<u16>, Vec<u32>),
+    Loopback(NetworkLayerType),
+    Raw(NetworkLayerType),
+    LinuxCooked(NetworkLayerType),
+    Ieee80211(NetworkLayerType),
 }
 
 impl LinkLayer {
+    const VLAN_TAG_PROTOCOL_IDS: [u16; 3] = [0x8100, 0x88A8, 0x9100];
+    const MPLS_ETHER_TYPES: [u16; 2] = [0x8847, 0x8848];
+    const MPLS_BOTTOM_OF_STACK: u32 = 0x100;
+
+    const LOOPBACK_IPV4_ADDRESS_FAMILY: u32 = 2;
+    /// Values of `AF_INET6` on Linux, NetBSD, OpenBSD, FreeBSD and macOS
+    const LOOPBACK_IPV6_ADDRESS_FAMILIES: [u32; 4] = [10, 24, 28, 30];
+
+    const IEEE_802_11_DATA_TYPE: u8 = 2;
+    const IEEE_802_11_NO_DATA_SUBTYPE_FLAG: u8 = 0x4;
+    const IEEE_802_11_QOS_SUBTYPE_FLAG: u8 = 0x8;
+    const IEEE_802_11_DISTRIBUTION_SYSTEM_FLAGS: u8 = 0x03;
+    const IEEE_802_11_PROTECTED_FLAG: u8 = 0x40;
+    const IEEE_802_11_ORDER_FLAG: u8 = 0x80;
+    const LLC_SNAP_SERVICE_ACCESS_POINT: u8 = 0xAA;
+
     pub fn parse(
         cursor: &mut ReadOnlyEndiannessAwareCursor,
         link_layer_type: LinkLayerType,
     ) -> Result<Self, Error> {
         let layer = match link_layer_type {
             LinkLayerType::En10Mb => {
+                ensure_remaining(cursor, 14)?;
                 cursor.advance(12);
 
-                let a = cursor.get_u16();
-                let next_layer_type = match a {
-                    0x0008 => NetworkLayerType::IPv4,
-                    0xDD86 => NetworkLayerType::IPv6,
-                    network_layer_type => {
-                        return Err(Error::UnknownNetworkLayerType(network_layer_type))
+                let mut ether_type = cursor.get_u16();
+
+                let mut vlan_ids = Vec::new();
+                while Self::VLAN_TAG_PROTOCOL_IDS.contains(&ether_type) {
+                    ensure_remaining(cursor, 4)?;
+                    vlan_ids.push(cursor.get_u16() & 0x0FFF);
+                    ether_type = cursor.get_u16();
+                }
+
+                let mut mpls_labels = Vec::new();
+                let next_layer_type = if Self::MPLS_ETHER_TYPES.contains(&ether_type) {
+                    loop {
+                        ensure_remaining(cursor, 4)?;
+                        let entry = cursor.get_u32();
+                        mpls_labels.push(entry >> 12);
+                        if entry & Self::MPLS_BOTTOM_OF_STACK != 0 {
+                            break;
+                        }
                     }
+
+                    // MPLS does not tell the type of its payload, so it is guessed from the IP
+                    // version
+                    guess_network_layer_type(cursor)?
+                } else {
+                    NetworkLayerType::try_from(ether_type)?
+                };
+
+                Self::Ethernet(next_layer_type, vlan_ids, mpls_labels)
+            }
+            LinkLayerType::Null => {
+                ensure_remaining(cursor, 4)?;
+
+                // Address family is in the byte order of the capturing host, which is not known
+                let address_family = match cursor.get_u32() {
+                    big_endian if big_endian <= 0xFFFF => big_endian,
+                    little_endian => little_endian.swap_bytes(),
                 };
+                let next_layer_type = match address_family {
+                    Self::LOOPBACK_IPV4_ADDRESS_FAMILY => NetworkLayerType::IPv4,
+                    a if Self::LOOPBACK_IPV6_ADDRESS_FAMILIES.contains(&a) => {
+                        NetworkLayerType::IPv6
+                    }
+                    address_family => return Err(Error::UnknownAddressFamily(address_family)),
+                };
+
+                Self::Loopback(next_layer_type)
+            }
+            LinkLayerType::Raw => {
+                let next_layer_type = guess_network_layer_type(cursor)?;
+
+                Self::Raw(next_layer_type)
+            }
+            LinkLayerType::Ipv4 => Self::Raw(NetworkLayerType::IPv4),
+            LinkLayerType::Ipv6 => Self::Raw(NetworkLayerType::IPv6),
+            LinkLayerType::LinuxSll => {
+                ensure_remaining(cursor, 16)?;
+                cursor.advance(14);
+
+                Self::LinuxCooked(NetworkLayerType::try_from(cursor.get_u16())?)
+            }
+            LinkLayerType::LinuxSll2 => {
+                ensure_remaining(cursor, 20)?;
+                let next_layer_type = NetworkLayerType::try_from(cursor.get_u16())?;
+                cursor.advance(18);
+
+                Self::LinuxCooked(next_layer_type)
+            }
+            LinkLayerType::Ieee80211Radiotap => {
+                ensure_remaining(cursor, 4)?;
+                cursor.advance(2);
+
+                // Radiotap header is always little-endian
+                let radiotap_length = usize::from(cursor.get_u16().swap_bytes());
+                let remaining_radiotap_length = radiotap_length
+                    .checked_sub(4)
+                    .ok_or(Error::TruncatedPacket)?;
+                ensure_remaining(cursor, remaining_radiotap_length + 24)?;
+                cursor.advance(remaining_radiotap_length);
+
+                let frame_control = cursor.get_u8();
+                let flags = cursor.get_u8();
+                let frame_type = (frame_control >> 2) & 0x3;
+                let frame_subtype = frame_control >> 4;
+                if frame_type != Self::IEEE_802_11_DATA_TYPE
+                    || frame_subtype & Self::IEEE_802_11_NO_DATA_SUBTYPE_FLAG != 0
+                    || flags & Self::IEEE_802_11_PROTECTED_FLAG != 0
+                {
+                    return Err(Error::UnsupportedWirelessFrame);
+                }
+
+                let mut remaining_header_length = 22;
+                if flags & Self::IEEE_802_11_DISTRIBUTION_SYSTEM_FLAGS
+                    == Self::IEEE_802_11_DISTRIBUTION_SYSTEM_FLAGS
+                {
+                    remaining_header_length += 6;
+                }
+                if frame_subtype & Self::IEEE_802_11_QOS_SUBTYPE_FLAG != 0 {
+                    remaining_header_length += 2;
+                    if flags & Self::IEEE_802_11_ORDER_FLAG != 0 {
+                        remaining_header_length += 4;
+                    }
+                }
+                ensure_remaining(cursor, remaining_header_length + 8)?;
+                cursor.advance(remaining_header_length);
 
-                Self::Ethernet(next_layer_type)
+                if cursor.get_u8() != Self::LLC_SNAP_SERVICE_ACCESS_POINT
+                    || cursor.get_u8() != Self::LLC_SNAP_SERVICE_ACCESS_POINT
+                {
+                    return Err(Error::UnsupportedWirelessFrame);
+                }
+                cursor.advance(4);
+
+                Self::Ieee80211(NetworkLayerType::try_from(cursor.get_u16())?)
             }
         };
 
@@ -91,11 +399,257 @@ impl LinkLayer {
 
     pub const fn get_network_layer_type(&self) -> NetworkLayerType {
         match self {
-            Self::Ethernet(next) => *next,
+            Self::Ethernet(next, _, _)
+            | Self::Loopback(next)
+            | Self::Raw(next)
+            | Self::LinuxCooked(next)
+            | Self::Ieee80211(next) => *next,
+        }
+    }
+
+    /// IDs of the VLAN tags of the frame, outermost first
+    pub fn vlan_ids(&self) -> &[u16] {
+        match self {
+            Self::Ethernet(_, vlan_ids, _) => vlan_ids,
+            Self::Loopback(_) | Self::Raw(_) | Self::LinuxCooked(_) | Self::Ieee80211(_) => &[],
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Tunnel {
+    pub protocol: TunnelProtocol,
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    /// VNI of VXLAN and GENEVE tunnels, key of GRE tunnels
+    pub identifier: Option<u32>,
+}
+
+impl Tunnel {
+    const IP_IN_IP_PROTOCOL: u8 = 4;
+    const IPV6_IN_IP_PROTOCOL: u8 = 41;
+    const GRE_PROTOCOL: u8 = 47;
+    const UDP_PROTOCOL: u8 = 17;
+
+    const VXLAN_PORT: u16 = 4789;
+    const GENEVE_PORT: u16 = 6081;
+
+    const GRE_CHECKSUM_FLAG: u16 = 0x8000;
+    const GRE_KEY_FLAG: u16 = 0x2000;
+    const GRE_SEQUENCE_NUMBER_FLAG: u16 = 0x1000;
+    const GRE_VERSION_MASK: u16 = 0x0007;
+
+    const TRANSPARENT_ETHERNET_BRIDGING: u16 = 0x6558;
+
+    /// Reads a tunnel written by [`Self::write_index`] from a flow index
+    pub fn read_index(cursor: &mut ReadOnlyIndexCursor) -> Result<Self, Error> {
+        let protocol = match cursor.get_u8()? {
+            0 => TunnelProtocol::Gre,
+            1 => TunnelProtocol::Vxlan,
+            2 => TunnelProtocol::Geneve,
+            3 => TunnelProtocol::IpInIp,
+            4 => TunnelProtocol::IPv6InIp,
+            _ => return Err(Error::InvalidIndexFile),
+        };
+
+        let tunnel = Self {
+            protocol,
+            source: cursor.get_ip_addr()?,
+            destination: cursor.get_ip_addr()?,
+            identifier: cursor.get_optional_u32()?,
+        };
+
+        Ok(tunnel)
+    }
+
+    pub fn write_index(&self, cursor: &mut WriteOnlyIndexCursor) {
+        cursor.put_u8(match self.protocol {
+            TunnelProtocol::Gre => 0,
+            TunnelProtocol::Vxlan => 1,
+            TunnelProtocol::Geneve => 2,
+            TunnelProtocol::IpInIp => 3,
+            TunnelProtocol::IPv6InIp => 4,
+        });
+        cursor.put_ip_addr(self.source);
+        cursor.put_ip_addr(self.destination);
+        cursor.put_optional_u32(self.identifier);
+    }
+
+    /// Parses the outer headers of a tunnelled packet, returning the tunnel and the type of the
+    /// encapsulated network layer
+    ///
+    /// The cursor is left untouched if the packet is not tunnelled.
+    fn parse(
+        cursor: &mut ReadOnlyEndiannessAwareCursor,
+        network_layer_type: NetworkLayerType,
+    ) -> Result<Option<(Self, NetworkLayerType)>, Error> {
+        let mut outer = cursor.clone();
+        let header = NetworkLayer::parse_header(&mut outer, network_layer_type)?;
+
+        // Fragmented tunnel packets are left to reassembly, which does not decapsulate them
+        if header.fragment.is_some() {
+            return Ok(None);
+        }
+
+        let (protocol, identifier, inner_network_layer_type) = match header.protocol {
+            Self::IP_IN_IP_PROTOCOL => (TunnelProtocol::IpInIp, None, NetworkLayerType::IPv4),
+            Self::IPV6_IN_IP_PROTOCOL => (TunnelProtocol::IPv6InIp, None, NetworkLayerType::IPv6),
+            Self::GRE_PROTOCOL => {
+                let (key, inner_network_layer_type) = Self::parse_gre(&mut outer)?;
+
+                (TunnelProtocol::Gre, key, inner_network_layer_type)
+            }
+            Self::UDP_PROTOCOL => {
+                ensure_remaining(&outer, 8)?;
+                outer.advance(2);
+                let destination_port = outer.get_u16();
+                outer.advance(4);
+
+                match destination_port {
+                    Self::VXLAN_PORT => {
+                        ensure_remaining(&outer, 8)?;
+                        outer.advance(4);
+                        let vni = outer.get_u32() >> 8;
+                        let inner_network_layer_type =
+                            LinkLayer::parse(&mut outer, LinkLayerType::En10Mb)?
+                                .get_network_layer_type();
+
+                        (TunnelProtocol::Vxlan, Some(vni), inner_network_layer_type)
+                    }
+                    Self::GENEVE_PORT => {
+                        ensure_remaining(&outer, 8)?;
+                        let option_length = usize::from(outer.get_u8() & 0x3F) * 4;
+                        outer.advance(1);
+                        let payload_type = outer.get_u16();
+                        let vni = outer.get_u32() >> 8;
+                        ensure_remaining(&outer, option_length)?;
+                        outer.advance(option_length);
+                        let inner_network_layer_type =
+                            Self::parse_payload_type(&mut outer, payload_type)?;
+
+                        (TunnelProtocol::Geneve, Some(vni), inner_network_layer_type)
+                    }
+                    _ => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        *cursor = outer;
+
+        let tunnel = Self {
+            protocol,
+            source: header.source,
+            destination: header.destination,
+            identifier,
+        };
+
+        Ok(Some((tunnel, inner_network_layer_type)))
+    }
+
+    /// Parses a GRE header, returning its key and the type of the encapsulated network layer
+    fn parse_gre(
+        cursor: &mut ReadOnlyEndiannessAwareCursor,
+    ) -> Result<(Option<u32>, NetworkLayerType), Error> {
+        ensure_remaining(cursor, 4)?;
+        let flags = cursor.get_u16();
+        let payload_type = cursor.get_u16();
+
+        // Version 1 is the enhanced GRE of PPTP, which carries PPP frames
+        let version = flags & Self::GRE_VERSION_MASK;
+        if version != 0 {
+            return Err(Error::UnsupportedGreVersion(version));
+        }
+
+        if flags & Self::GRE_CHECKSUM_FLAG != 0 {
+            ensure_remaining(cursor, 4)?;
+            cursor.advance(4);
+        }
+        let key = if flags & Self::GRE_KEY_FLAG != 0 {
+            ensure_remaining(cursor, 4)?;
+            Some(cursor.get_u32())
+        } else {
+            None
+        };
+        if flags & Self::GRE_SEQUENCE_NUMBER_FLAG != 0 {
+            ensure_remaining(cursor, 4)?;
+            cursor.advance(4);
+        }
+
+        let inner_network_layer_type = Self::parse_payload_type(cursor, payload_type)?;
+
+        Ok((key, inner_network_layer_type))
+    }
+
+    /// Maps the EtherType of a tunnel payload to a network layer type, parsing the Ethernet
+    /// header of bridged payloads
+    fn parse_payload_type(
+        cursor: &mut ReadOnlyEndiannessAwareCursor,
+        payload_type: u16,
+    ) -> Result<NetworkLayerType, Error> {
+        if payload_type == Self::TRANSPARENT_ETHERNET_BRIDGING {
+            let link_layer = LinkLayer::parse(cursor, LinkLayerType::En10Mb)?;
+            return Ok(link_layer.get_network_layer_type());
+        }
+
+        NetworkLayerType::try_from(payload_type)
+    }
+}
+
+impl Display for Tunnel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} → {}",
+            self.protocol, self.source, self.destination
+        )?;
+
+        match (self.protocol, self.identifier) {
+            (TunnelProtocol::Gre, Some(key)) => write!(f, " key {key}"),
+            (_, Some(vni)) => write!(f, " VNI {vni}"),
+            (_, None) => Ok(()),
         }
     }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum TunnelProtocol {
+    Gre,
+    Vxlan,
+    Geneve,
+    IpInIp,
+    IPv6InIp,
+}
+
+impl Display for TunnelProtocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Gre => "GRE",
+                Self::Vxlan => "VXLAN",
+                Self::Geneve => "GENEVE",
+                Self::IpInIp => "IPIP",
+                Self::IPv6InIp => "6in4",
+            }
+        )
+    }
+}
+
+/// Guesses the type of the network layer from the IP version of the next header
+fn guess_network_layer_type(
+    cursor: &ReadOnlyEndiannessAwareCursor,
+) -> Result<NetworkLayerType, Error> {
+    ensure_remaining(cursor, 1)?;
+
+    match cursor.peek_u8() >> 4 {
+        4 => Ok(NetworkLayerType::IPv4),
+        6 => Ok(NetworkLayerType::IPv6),
+        version => Err(Error::UnknownIpVersion(version)),
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum NetworkLayer {
     IPv4(u32, u32, TransportLayerType),
@@ -103,80 +657,147 @@ pub enum NetworkLayer {
 }
 
 impl NetworkLayer {
+    const IPV4_MORE_FRAGMENTS_FLAG: u16 = 0x2000;
+    const IPV4_FRAGMENT_OFFSET_MASK: u16 = 0x1FFF;
+
+    const IPV6_HOP_BY_HOP_OPTIONS_HEADER: u8 = 0;
+    const IPV6_ROUTING_HEADER: u8 = 43;
+    const IPV6_FRAGMENT_HEADER: u8 = 44;
+    const IPV6_AUTHENTICATION_HEADER: u8 = 51;
+    const IPV6_DESTINATION_OPTIONS_HEADER: u8 = 60;
+    const IPV6_MORE_FRAGMENTS_FLAG: u16 = 0x0001;
+    const IPV6_FRAGMENT_OFFSET_MASK: u16 = 0xFFF8;
+
     pub fn parse(
         cursor: &mut ReadOnlyEndiannessAwareCursor,
         network_layer_type: NetworkLayerType,
     ) -> Result<Self, Error> {
-        let layer = match network_layer_type {
+        let header = Self::parse_header(cursor, network_layer_type)?;
+        let protocol = TransportLayerType::try_from(header.protocol)?;
+
+        Ok(Self::new(header.source, header.destination, protocol))
+    }
+
+    fn new(source: IpAddr, destination: IpAddr, protocol: TransportLayerType) -> Self {
+        match (source, destination) {
+            (IpAddr::V4(source), IpAddr::V4(destination)) => {
+                Self::IPv4(source.into(), destination.into(), protocol)
+            }
+            (IpAddr::V6(source), IpAddr::V6(destination)) => {
+                Self::IPv6(source.into(), destination.into(), protocol)
+            }
+            _ => unreachable!("addresses of an IP header have the same version"),
+        }
+    }
+
+    /// Parses an IP header along with its IPv6 extension headers
+    fn parse_header(
+        cursor: &mut ReadOnlyEndiannessAwareCursor,
+        network_layer_type: NetworkLayerType,
+    ) -> Result<IpHeader, Error> {
+        let header = match network_layer_type {
             NetworkLayerType::IPv4 => {
-                let option_length: usize =
-                    cursor.get_u8().bitand(0x0F).mul(4).wrapping_sub(20).into();
-
-                cursor.advance(8);
-                let protocol = match cursor.get_u8() {
-                    6 => TransportLayerType::Tcp,
-                    17 => TransportLayerType::Udp,
-                    transport_layer_type => {
-                        return Err(Error::UnknownTransportLayerType(transport_layer_type))
-                    }
-                };
+                ensure_remaining(cursor, 20)?;
+                let header_length = usize::from(cursor.get_u8().bitand(0x0F).mul(4));
+                let option_length = header_length.wrapping_sub(20);
+
+                cursor.advance(1);
+                let total_length = usize::from(cursor.get_u16());
+                let identification = cursor.get_u16();
+                let flags_fragment_offset = cursor.get_u16();
+                cursor.advance(1);
+                let protocol = cursor.get_u8();
                 cursor.advance(2);
 
-                let layer = Self::IPv4(cursor.get_u32(), cursor.get_u32(), protocol);
+                let source = Ipv4Addr::from(cursor.get_u32());
+                let destination = Ipv4Addr::from(cursor.get_u32());
+                ensure_remaining(cursor, option_length)?;
                 cursor.advance(option_length);
 
-                layer
+                let more_fragments = flags_fragment_offset & Self::IPV4_MORE_FRAGMENTS_FLAG != 0;
+                let offset =
+                    usize::from(flags_fragment_offset & Self::IPV4_FRAGMENT_OFFSET_MASK) * 8;
+                let fragment = (more_fragments || offset != 0).then_some(FragmentHeader {
+                    identification: u32::from(identification),
+                    offset,
+                    more_fragments,
+                });
+
+                IpHeader {
+                    source: source.into(),
+                    destination: destination.into(),
+                    protocol,
+                    payload_length: total_length.saturating_sub(header_length),
+                    fragment,
+                }
             }
             NetworkLayerType::IPv6 => {
-                let ipv6_option_header_ids = [0u8, 43u8, 44u8, 51u8, 60u8];
+                let ipv6_extension_header_ids = [
+                    Self::IPV6_HOP_BY_HOP_OPTIONS_HEADER,
+                    Self::IPV6_ROUTING_HEADER,
+                    Self::IPV6_FRAGMENT_HEADER,
+                    Self::IPV6_AUTHENTICATION_HEADER,
+                    Self::IPV6_DESTINATION_OPTIONS_HEADER,
+                ];
 
-                cursor.advance(6);
+                ensure_remaining(cursor, 40)?;
+                cursor.advance(4);
 
+                let mut payload_length = usize::from(cursor.get_u16());
                 let mut next_header = cursor.get_u8();
-                let mut check_next_header = ipv6_option_header_ids.contains(&next_header);
 
                 cursor.advance(1);
 
                 let source = cursor.get_u128();
                 let destination = cursor.get_u128();
 
-                while check_next_header {
-                    let new_next_header = cursor.get_u8();
-                    match next_header {
-                        0 | 43 => cursor.advance(15),
-                        44 => cursor.advance(7),
-                        51 => {
-                            let advancement = cursor.get_u8().wrapping_sub(2).into();
-                            cursor.advance(advancement);
+                let mut fragment = None;
+                while ipv6_extension_header_ids.contains(&next_header) {
+                    ensure_remaining(cursor, 8)?;
+                    let extension_next_header = cursor.get_u8();
+                    let extension_header_length = match next_header {
+                        Self::IPV6_FRAGMENT_HEADER => {
+                            cursor.advance(1);
+                            let offset_flags = cursor.get_u16();
+                            fragment = Some(FragmentHeader {
+                                identification: cursor.get_u32(),
+                                offset: usize::from(offset_flags & Self::IPV6_FRAGMENT_OFFSET_MASK),
+                                more_fragments: offset_flags & Self::IPV6_MORE_FRAGMENTS_FLAG != 0,
+                            });
+
+                            8
                         }
-                        60 => {
-                            let advancement = cursor.get_u8().wrapping_add(6).into();
-                            cursor.advance(advancement);
+                        Self::IPV6_AUTHENTICATION_HEADER => {
+                            let extension_header_length = (usize::from(cursor.get_u8()) + 2) * 4;
+                            ensure_remaining(cursor, extension_header_length - 2)?;
+                            cursor.advance(extension_header_length - 2);
+
+                            extension_header_length
                         }
-                        next_header_length => {
-                            return Err(Error::UnknownIPv6AdditionalHeaderLength(
-                                next_header_length,
-                            ))
+                        _ => {
+                            let extension_header_length = (usize::from(cursor.get_u8()) + 1) * 8;
+                            ensure_remaining(cursor, extension_header_length - 2)?;
+                            cursor.advance(extension_header_length - 2);
+
+                            extension_header_length
                         }
-                    }
+                    };
 
-                    next_header = new_next_header;
-                    check_next_header = ipv6_option_header_ids.contains(&new_next_header);
+                    payload_length = payload_length.saturating_sub(extension_header_length);
+                    next_header = extension_next_header;
                 }
 
-                let protocol = match next_header {
-                    6 => TransportLayerType::Tcp,
-                    17 => TransportLayerType::Udp,
-                    transport_layer_type => {
-                        return Err(Error::UnknownTransportLayerType(transport_layer_type))
-                    }
-                };
-
-                Self::IPv6(source, destination, protocol)
+                IpHeader {
+                    source: Ipv6Addr::from(source).into(),
+                    destination: Ipv6Addr::from(destination).into(),
+                    protocol: next_header,
+                    payload_length,
+                    fragment,
+                }
             }
         };
 
-        Ok(layer)
+        Ok(header)
     }
 
     pub const fn get_transport_layer_type(&self) -> TransportLayerType {
@@ -184,32 +805,96 @@ impl NetworkLayer {
             Self::IPv4(_, _, next) | Self::IPv6(_, _, next) => *next,
         }
     }
+
+    /// Same layer with source and destination addresses swapped
+    const fn reversed(&self) -> Self {
+        match *self {
+            Self::IPv4(source, destination, next) => Self::IPv4(destination, source, next),
+            Self::IPv6(source, destination, next) => Self::IPv6(destination, source, next),
+        }
+    }
+}
+
+/// Fields of an IP header needed to dissect its payload
+struct IpHeader {
+    source: IpAddr,
+    destination: IpAddr,
+    protocol: u8,
+    /// Length of the payload after the header and its extension headers, according to the header
+    payload_length: usize,
+    fragment: Option<FragmentHeader>,
+}
+
+struct FragmentHeader {
+    identification: u32,
+    offset: usize,
+    more_fragments: bool,
+}
+
+/// Fragment of an IPv4 or IPv6 datagram
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Fragment {
+    pub link_layer: LinkLayer,
+    pub tunnel: Option<Tunnel>,
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    pub protocol: TransportLayerType,
+    pub identification: u32,
+    /// Offset of the payload of the fragment in the payload of the datagram
+    pub offset: usize,
+    pub more_fragments: bool,
+    pub payload: Vec<u8>,
 }
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum TransportLayer {
     Udp(u16, u16, ApplicationLayerType),
-    Tcp(u16, u16, ApplicationLayerType),
+    Tcp(u16, u16, ApplicationLayerType, TcpHeader),
+    Icmp(IcmpMessage),
 }
 
 impl TransportLayer {
+    /// Parses the header of a transport layer segment that is `segment_length` bytes long
+    /// according to the network layer
     pub fn parse(
         cursor: &mut ReadOnlyEndiannessAwareCursor,
         transport_layer_type: TransportLayerType,
+        segment_length: usize,
     ) -> Result<Self, Error> {
         let layer = match transport_layer_type {
             TransportLayerType::Tcp => {
+                ensure_remaining(cursor, 20)?;
                 let source = cursor.get_u16();
                 let destination = cursor.get_u16();
+                let sequence_number = cursor.get_u32();
+                let acknowledgement_number = cursor.get_u32();
+                let header_length = usize::from(cursor.get_u8().shr(4u8).mul(4));
+                let flags = TcpFlags(cursor.get_u8());
+                let window = cursor.get_u16();
+                cursor.advance(4);
 
-                cursor.advance(8);
-                let remaining_header_length: usize =
-                    cursor.get_u8().shr(4u8).mul(4).wrapping_sub(13).into();
-                cursor.advance(remaining_header_length);
+                let options_length = header_length.wrapping_sub(20);
+                ensure_remaining(cursor, options_length)?;
+                let options = cursor.get_slice(options_length);
+
+                let header = TcpHeader {
+                    sequence_number,
+                    acknowledgement_number,
+                    flags,
+                    window,
+                    window_scale: TcpHeader::parse_window_scale(options),
+                    payload_length: segment_length.saturating_sub(header_length) as u32,
+                };
 
-                Self::Tcp(source, destination, ApplicationLayerType::OctetArray)
+                Self::Tcp(
+                    source,
+                    destination,
+                    ApplicationLayerType::OctetArray,
+                    header,
+                )
             }
             TransportLayerType::Udp => {
+                ensure_remaining(cursor, 8)?;
                 let source = cursor.get_u16();
                 let destination = cursor.get_u16();
 
@@ -217,8 +902,562 @@ impl TransportLayer {
 
                 Self::Udp(source, destination, ApplicationLayerType::OctetArray)
             }
+            TransportLayerType::Icmp | TransportLayerType::IcmpV6 => {
+                Self::Icmp(IcmpMessage::parse(cursor, transport_layer_type)?)
+            }
         };
 
         Ok(layer)
     }
+
+    /// Length of the payload of a segment that is `segment_length` bytes long, where the packet
+    /// quoted by an ICMP error message counts as payload
+    fn payload_length(&self, segment_length: usize) -> usize {
+        match self {
+            Self::Tcp(.., header) => header.payload_length as usize,
+            Self::Udp(..) | Self::Icmp(_) => segment_length.saturating_sub(8),
+        }
+    }
+}
+
+/// Fields of a TCP header that describe the state of the connection
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct TcpHeader {
+    pub sequence_number: u32,
+    pub acknowledgement_number: u32,
+    pub flags: TcpFlags,
+    pub window: u16,
+    /// Shift count of the window scale option, which only SYN segments carry
+    pub window_scale: Option<u8>,
+    /// Length of the payload of the segment according to the IP header, even where less of it
+    /// was captured
+    pub payload_length: u32,
+}
+
+impl TcpHeader {
+    const END_OF_OPTIONS: u8 = 0;
+    const NO_OPERATION: u8 = 1;
+    const WINDOW_SCALE: u8 = 3;
+
+    /// Finds the window scale option among the options of a TCP header
+    fn parse_window_scale(mut options: &[u8]) -> Option<u8> {
+        loop {
+            match *options {
+                [] | [Self::END_OF_OPTIONS, ..] => return None,
+                [Self::NO_OPERATION, ref rest @ ..] => options = rest,
+                [Self::WINDOW_SCALE, 3, shift_count, ..] => return Some(shift_count),
+                [_, length, ..] if usize::from(length) >= 2 => {
+                    options = options.get(usize::from(length)..)?;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Control bits of a TCP header
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct TcpFlags(pub u8);
+
+impl TcpFlags {
+    pub const FIN: Self = Self(0x01);
+    pub const SYN: Self = Self(0x02);
+    pub const RST: Self = Self(0x04);
+    pub const PSH: Self = Self(0x08);
+    pub const ACK: Self = Self(0x10);
+    pub const URG: Self = Self(0x20);
+
+    const NAMES: [(Self, &'static str); 6] = [
+        (Self::SYN, "SYN"),
+        (Self::FIN, "FIN"),
+        (Self::RST, "RST"),
+        (Self::PSH, "PSH"),
+        (Self::ACK, "ACK"),
+        (Self::URG, "URG"),
+    ];
+
+    /// Whether every flag set in `flags` is also set in these flags
+    pub const fn contains(self, flags: Self) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// Whether the flags open a connection, i.e. SYN is set without ACK
+    pub const fn is_syn(self) -> bool {
+        self.contains(Self::SYN) && !self.contains(Self::ACK)
+    }
+}
+
+impl Display for TcpFlags {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let names = Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect::<Vec<&str>>();
+
+        write!(f, "{}", names.join(" "))
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct IcmpMessage {
+    pub is_v6: bool,
+    pub message_type: u8,
+    pub code: u8,
+    /// Identifier of query messages such as echo requests and replies, zero for other messages
+    pub identifier: u16,
+    /// Headers of the packet quoted by an error message, if they could be parsed
+    pub quoted: Option<QuotedHeaders>,
+}
+
+impl IcmpMessage {
+    const ICMP_QUERY_TYPES: [u8; 8] = [0, 8, 13, 14, 15, 16, 17, 18];
+    const ICMP_ERROR_TYPES: [u8; 5] = [3, 4, 5, 11, 12];
+    const ICMPV6_QUERY_TYPES: [u8; 2] = [128, 129];
+    const ICMPV6_ERROR_TYPES: [u8; 4] = [1, 2, 3, 4];
+
+    fn parse(
+        cursor: &mut ReadOnlyEndiannessAwareCursor,
+        transport_layer_type: TransportLayerType,
+    ) -> Result<Self, Error> {
+        ensure_remaining(cursor, 8)?;
+
+        let is_v6 = transport_layer_type == TransportLayerType::IcmpV6;
+        let message_type = cursor.get_u8();
+        let code = cursor.get_u8();
+        cursor.advance(2);
+        let identifier = cursor.get_u16();
+        cursor.advance(2);
+
+        let (query_types, error_types, quoted_network_layer_type) = if is_v6 {
+            (
+                &Self::ICMPV6_QUERY_TYPES[..],
+                &Self::ICMPV6_ERROR_TYPES[..],
+                NetworkLayerType::IPv6,
+            )
+        } else {
+            (
+                &Self::ICMP_QUERY_TYPES[..],
+                &Self::ICMP_ERROR_TYPES[..],
+                NetworkLayerType::IPv4,
+            )
+        };
+
+        let identifier = if query_types.contains(&message_type) {
+            identifier
+        } else {
+            0
+        };
+        let quoted = if error_types.contains(&message_type) {
+            QuotedHeaders::parse(cursor, quoted_network_layer_type)
+        } else {
+            None
+        };
+
+        let message = Self {
+            is_v6,
+            message_type,
+            code,
+            identifier,
+            quoted,
+        };
+
+        Ok(message)
+    }
+
+    /// Message type shared by requests and their replies, so that both belong to the same flow
+    pub const fn flow_message_type(&self) -> u8 {
+        match (self.is_v6, self.message_type) {
+            (false, 0) => 8,
+            (false, 14 | 16 | 18) => self.message_type - 1,
+            (true, 129) => 128,
+            _ => self.message_type,
+        }
+    }
+}
+
+/// Network layer and ports of the TCP or UDP packet quoted by an ICMP error message
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct QuotedHeaders {
+    pub network_layer: NetworkLayer,
+    pub source_port: u16,
+    pub destination_port: u16,
+}
+
+impl QuotedHeaders {
+    fn parse(
+        cursor: &mut ReadOnlyEndiannessAwareCursor,
+        network_layer_type: NetworkLayerType,
+    ) -> Option<Self> {
+        let network_layer = NetworkLayer::parse(cursor, network_layer_type).ok()?;
+        if !network_layer.get_transport_layer_type().has_ports() || cursor.remaining() < 4 {
+            return None;
+        }
+
+        let headers = Self {
+            network_layer,
+            source_port: cursor.get_u16(),
+            destination_port: cursor.get_u16(),
+        };
+
+        Some(headers)
+    }
+}
+
+/// Fails with [`Error::TruncatedPacket`] if fewer than `length` bytes are left in the packet
+fn ensure_remaining(cursor: &ReadOnlyEndiannessAwareCursor, length: usize) -> Result<(), Error> {
+    if cursor.remaining() < length {
+        return Err(Error::TruncatedPacket);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use crate::error::Error;
+    use crate::packet::Packet;
+    use crate::packet_dissection::{
+        Dissection, LinkLayer, NetworkLayer, PacketDissection, TransportLayer, Tunnel,
+        TunnelProtocol,
+    };
+    use crate::packet_layer::{
+        ApplicationLayerType, LinkLayerType, NetworkLayerType, TransportLayerType,
+    };
+
+    const IPV4_UDP: [u8; 28] = [
+        0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00,
+        0x01, 0x0a, 0x00, 0x00, 0x02, 0x14, 0xe9, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn dissecting_link_layer_types_succeeds() {
+        let linux_sll = [
+            &[
+                0x00, 0x00, 0x00, 0x01, 0x00, 0x06, 0, 0, 0, 0, 0, 1, 0, 0, 0x08, 0x00,
+            ][..],
+            &IPV4_UDP,
+        ]
+        .concat();
+        let linux_sll2 = [
+            &[
+                0x08, 0x00, 0, 0, 0, 0, 0, 2, 0x00, 0x01, 0x00, 0x06, 0, 0, 0, 0, 0, 1, 0, 0,
+            ][..],
+            &IPV4_UDP,
+        ]
+        .concat();
+        let radiotap_qos_data = [
+            &[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+            &[0x88, 0x01, 0x00, 0x00],
+            &[0; 18],
+            &[0x00, 0x00, 0x00, 0x00],
+            &[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00],
+            &IPV4_UDP,
+        ]
+        .concat();
+
+        for (link_layer_type, packet, link_layer) in [
+            (
+                LinkLayerType::Null,
+                [&[0x02, 0x00, 0x00, 0x00][..], &IPV4_UDP].concat(),
+                LinkLayer::Loopback(NetworkLayerType::IPv4),
+            ),
+            (
+                LinkLayerType::Null,
+                [&[0x00, 0x00, 0x00, 0x02][..], &IPV4_UDP].concat(),
+                LinkLayer::Loopback(NetworkLayerType::IPv4),
+            ),
+            (
+                LinkLayerType::Raw,
+                IPV4_UDP.to_vec(),
+                LinkLayer::Raw(NetworkLayerType::IPv4),
+            ),
+            (
+                LinkLayerType::Ipv4,
+                IPV4_UDP.to_vec(),
+                LinkLayer::Raw(NetworkLayerType::IPv4),
+            ),
+            (
+                LinkLayerType::LinuxSll,
+                linux_sll,
+                LinkLayer::LinuxCooked(NetworkLayerType::IPv4),
+            ),
+            (
+                LinkLayerType::LinuxSll2,
+                linux_sll2,
+                LinkLayer::LinuxCooked(NetworkLayerType::IPv4),
+            ),
+            (
+                LinkLayerType::Ieee80211Radiotap,
+                radiotap_qos_data,
+                LinkLayer::Ieee80211(NetworkLayerType::IPv4),
+            ),
+        ] {
+            let packet: Packet = packet.into();
+
+            let Dissection::Complete(dissection) =
+                PacketDissection::from_packet(&packet, link_layer_type, false).unwrap()
+            else {
+                panic!("unfragmented packet is dissected completely");
+            };
+
+            assert_eq!(dissection.link_layer, link_layer);
+            assert_eq!(
+                dissection.network_layer,
+                NetworkLayer::IPv4(0x0A_00_00_01, 0x0A_00_00_02, TransportLayerType::Udp)
+            );
+            assert_eq!(
+                dissection.transport_layer,
+                TransportLayer::Udp(5353, 53, ApplicationLayerType::OctetArray)
+            );
+        }
+    }
+
+    #[test]
+    fn decapsulating_tunnels_succeeds() {
+        let outer_ipv4 = |protocol: u8| {
+            [
+                0x45, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, protocol, 0x00, 0x00, 0xc0,
+                0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02,
+            ]
+        };
+        let inner_ethernet = [
+            &[
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00,
+            ][..],
+            &IPV4_UDP,
+        ]
+        .concat();
+
+        let vxlan = [
+            &outer_ipv4(17)[..],
+            &[0xc0, 0x00, 0x12, 0xb5, 0x00, 0x00, 0x00, 0x00],
+            &[0x08, 0x00, 0x00, 0x00, 0x00, 0x13, 0x89, 0x00],
+            &inner_ethernet,
+        ]
+        .concat();
+        let geneve = [
+            &outer_ipv4(17)[..],
+            &[0xc0, 0x00, 0x17, 0xc1, 0x00, 0x00, 0x00, 0x00],
+            &[0x01, 0x00, 0x65, 0x58, 0x00, 0x00, 0x2a, 0x00, 0, 0, 0, 0],
+            &inner_ethernet,
+        ]
+        .concat();
+        let gre = [
+            &outer_ipv4(47)[..],
+            &[0x20, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x07],
+            &IPV4_UDP,
+        ]
+        .concat();
+        let ipip = [&outer_ipv4(4)[..], &IPV4_UDP].concat();
+
+        for (packet, protocol, identifier) in [
+            (vxlan, TunnelProtocol::Vxlan, Some(5001)),
+            (geneve, TunnelProtocol::Geneve, Some(42)),
+            (gre, TunnelProtocol::Gre, Some(7)),
+            (ipip, TunnelProtocol::IpInIp, None),
+        ] {
+            let packet: Packet = packet.into();
+
+            let Dissection::Complete(dissection) =
+                PacketDissection::from_packet(&packet, LinkLayerType::Raw, true).unwrap()
+            else {
+                panic!("unfragmented packet is dissected completely");
+            };
+            let outer_dissection =
+                PacketDissection::from_packet(&packet, LinkLayerType::Raw, false);
+
+            assert_eq!(
+                dissection.tunnel,
+                Some(Tunnel {
+                    protocol,
+                    source: Ipv4Addr::new(192, 168, 0, 1).into(),
+                    destination: Ipv4Addr::new(192, 168, 0, 2).into(),
+                    identifier,
+                })
+            );
+            assert_eq!(
+                dissection.transport_layer,
+                TransportLayer::Udp(5353, 53, ApplicationLayerType::OctetArray)
+            );
+            assert!(!matches!(
+                outer_dissection,
+                Ok(Dissection::Complete(d)) if d.tunnel.is_some()
+            ));
+        }
+    }
+
+    #[test]
+    fn decapsulating_malformed_tunnels_succeeds() {
+        let outer_ipv4 = |protocol: u8| {
+            [
+                0x45, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, protocol, 0x00, 0x00, 0xc0,
+                0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02,
+            ]
+        };
+
+        // The encapsulated Ethernet header is cut short
+        let vxlan = [
+            &outer_ipv4(17)[..],
+            &[0xc0, 0x00, 0x12, 0xb5, 0x00, 0x00, 0x00, 0x00],
+            &[0x08, 0x00, 0x00, 0x00, 0x00, 0x13, 0x89, 0x00],
+            &[0x00, 0x00, 0x00, 0x00],
+        ]
+        .concat();
+        // The payload type is not one of a network layer
+        let geneve = [
+            &outer_ipv4(17)[..],
+            &[0xc0, 0x00, 0x17, 0xc1, 0x00, 0x00, 0x00, 0x00],
+            &[0x00, 0x00, 0x12, 0x34, 0x00, 0x00, 0x2a, 0x00],
+            &IPV4_UDP,
+        ]
+        .concat();
+        let enhanced_gre = [
+            &outer_ipv4(47)[..],
+            &[0x30, 0x01, 0x88, 0x0b, 0x00, 0x00, 0x00, 0x00],
+        ]
+        .concat();
+
+        for (packet, destination_port) in [(vxlan, 4789), (geneve, 6081)] {
+            let packet: Packet = packet.into();
+
+            let Dissection::Complete(dissection) =
+                PacketDissection::from_packet(&packet, LinkLayerType::Raw, true).unwrap()
+            else {
+                panic!("unfragmented packet is dissected completely");
+            };
+
+            assert_eq!(dissection.tunnel, None);
+            assert_eq!(
+                dissection.transport_layer,
+                TransportLayer::Udp(49152, destination_port, ApplicationLayerType::OctetArray)
+            );
+        }
+
+        let packet: Packet = enhanced_gre.into();
+        let result = PacketDissection::from_packet(&packet, LinkLayerType::Raw, true);
+
+        assert!(matches!(result, Err(Error::UnsupportedGreVersion(1))));
+    }
+
+    #[test]
+    fn dissecting_encrypted_wireless_frame_fails() {
+        let radiotap_protected_data = [
+            &[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+            &[0x08, 0x41, 0x00, 0x00],
+            &[0; 20],
+            &[0; 16],
+        ]
+        .concat();
+        let packet: Packet = radiotap_protected_data.into();
+
+        let result =
+            PacketDissection::from_packet(&packet, LinkLayerType::Ieee80211Radiotap, false);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn dissecting_fragments_succeeds() {
+        let ipv4_fragment = [
+            &[0x45, 0x00, 0x00, 0x1c, 0x12, 0x34, 0x00, 0x01][..],
+            &IPV4_UDP[8..20],
+            &[0xaa; 8],
+            &[0x00; 4],
+        ]
+        .concat();
+        let ipv6_fragment = [
+            &[0x60, 0x00, 0x00, 0x00, 0x00, 0x18, 0x00, 0x40][..],
+            &[0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+            &[0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+            &[44, 0x00, 0x01, 0x04, 0x00, 0x00, 0x00, 0x00],
+            &[17, 0x00, 0x00, 0x09, 0x00, 0x00, 0x56, 0x78],
+            &[0xbb; 8],
+        ]
+        .concat();
+
+        for (packet, identification, offset, more_fragments, payload) in [
+            (ipv4_fragment, 0x1234, 8, false, [0xaa; 8]),
+            (ipv6_fragment, 0x5678, 8, true, [0xbb; 8]),
+        ] {
+            let packet: Packet = packet.into();
+
+            let Dissection::Fragment(fragment) =
+                PacketDissection::from_packet(&packet, LinkLayerType::Raw, false).unwrap()
+            else {
+                panic!("fragment is not dissected beyond the IP header");
+            };
+
+            assert_eq!(fragment.protocol, TransportLayerType::Udp);
+            assert_eq!(fragment.identification, identification);
+            assert_eq!(fragment.offset, offset);
+            assert_eq!(fragment.more_fragments, more_fragments);
+            assert_eq!(fragment.payload, payload);
+        }
+    }
+
+    #[test]
+    fn dissecting_tcp_segments_succeeds() {
+        let options = [2, 4, 0x05, 0xb4, 1, 3, 3, 7, 0, 0, 0, 0];
+        let tcp = [
+            &[
+                0x04, 0xd2, 0x00, 0x50, 0, 0, 0, 100, 0, 0, 0, 0, 0x80, 0x02, 0xff, 0xff,
+            ][..],
+            &[0, 0, 0, 0],
+            &options,
+            b"data",
+        ]
+        .concat();
+        let packet = [
+            &[0x45, 0x00, 0x00, 20 + tcp.len() as u8][..],
+            &IPV4_UDP[4..9],
+            &[0x06],
+            &IPV4_UDP[10..20],
+            &tcp,
+            // Padding after the IP datagram
+            &[0; 4],
+        ]
+        .concat();
+
+        let packet = Packet::from(packet);
+        let (header, payload) = PacketDissection::tcp_segment(&packet, LinkLayerType::Raw, false)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(header.sequence_number, 100);
+        assert!(header.flags.is_syn());
+        assert_eq!(header.flags.to_string(), "SYN");
+        assert_eq!(header.window, 0xffff);
+        assert_eq!(header.window_scale, Some(7));
+        assert_eq!(header.payload_length, 4);
+        assert_eq!(payload, b"data");
+    }
+
+    #[test]
+    fn dissecting_headers_in_network_byte_order_succeeds() {
+        let packet = Packet::from(
+            [
+                &[0; 12][..],
+                &[0x08, 0x00],
+                &[
+                    0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0,
+                    0xa8, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+                ],
+                &[0x9c, 0x40, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00],
+            ]
+            .concat(),
+        );
+
+        let Dissection::Complete(dissection) =
+            PacketDissection::from_packet(&packet, LinkLayerType::En10Mb, false).unwrap()
+        else {
+            panic!("unfragmented packet is dissected completely");
+        };
+        let source: SocketAddr = "192.168.0.1:40000".parse().unwrap();
+        let destination: SocketAddr = "10.0.0.2:53".parse().unwrap();
+
+        assert_eq!(dissection.socket_addrs(), Ok((source, destination)));
+    }
 }
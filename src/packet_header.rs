@@ -1,5 +1,4 @@
 use std::fmt::{Display, Formatter};
-use std::ops::{Add, Mul};
 
 use crate::capture_header::TimestampPrecision;
 use crate::endianness_aware_cursor::{
@@ -23,7 +22,8 @@ impl PacketHeader {
     ) -> Self {
         let mut cursor = ReadOnlyEndiannessAwareCursor::new(buffer, endianness);
 
-        let timestamp = Timestamp(timestamp_precision, cursor.get_u32(), cursor.get_u32());
+        let timestamp =
+            Timestamp::from_fraction(cursor.get_u32(), cursor.get_u32(), timestamp_precision);
         let captured_length = PacketLength(cursor.get_u32());
         let actual_length = PacketLength(cursor.get_u32());
 
@@ -34,11 +34,25 @@ impl PacketHeader {
         }
     }
 
-    pub fn compose(&self, endianness: Endianness) -> Vec<u8> {
+    /// Composes the record header, with the fraction of a second of its timestamp in units of
+    /// `timestamp_precision`
+    ///
+    /// Timestamps before 1970 or after 2106, which a record header cannot hold, are clamped.
+    pub fn compose(
+        &self,
+        endianness: Endianness,
+        timestamp_precision: TimestampPrecision,
+    ) -> Vec<u8> {
         let mut cursor = WriteOnlyEndiannessAwareCursor::new(endianness);
 
-        cursor.put_u32(self.timestamp.1);
-        cursor.put_u32(self.timestamp.2);
+        let seconds = self.timestamp.seconds().clamp(0, i64::from(u32::MAX));
+        let fraction = if seconds == self.timestamp.seconds() {
+            self.timestamp.fraction(timestamp_precision)
+        } else {
+            0
+        };
+        cursor.put_u32(seconds as u32);
+        cursor.put_u32(fraction);
         cursor.put_u32(self.captured_length.0);
         cursor.put_u32(self.actual_length.0);
 
@@ -56,26 +70,100 @@ impl Display for PacketHeader {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
-pub struct Timestamp(pub TimestampPrecision, pub u32, pub u32);
+/// Capture time of a packet, as seconds and nanoseconds since the Unix epoch
+///
+/// Timestamps of every precision and resolution are kept in nanoseconds, so that timestamps read
+/// from different captures compare and subtract correctly. Seconds are signed and 64 bits wide,
+/// leaving room for timestamps before 1970 and after 2106.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct Timestamp {
+    seconds: i64,
+    /// Nanoseconds since the start of the second, always less than a second
+    nanos: u32,
+}
 
 impl Timestamp {
-    pub fn nanos(&self) -> u64 {
-        u64::from(self.1)
-            .mul(match self.0 {
-                TimestampPrecision::Micro => 1_000_000u64,
-                TimestampPrecision::Nano => 1_000_000_000u64,
-            })
-            .add(u64::from(self.2))
+    pub const NANOS_PER_SECOND: u32 = 1_000_000_000;
+
+    /// Timestamp `seconds` and `nanos` after the Unix epoch, carrying whole seconds in `nanos`
+    /// over into the seconds
+    pub const fn new(seconds: i64, nanos: u32) -> Self {
+        Self {
+            seconds: seconds.saturating_add((nanos / Self::NANOS_PER_SECOND) as i64),
+            nanos: nanos % Self::NANOS_PER_SECOND,
+        }
+    }
+
+    /// Timestamp of a pcap record, whose fraction of a second is in units of `precision`
+    pub fn from_fraction(seconds: u32, fraction: u32, precision: TimestampPrecision) -> Self {
+        let nanos = u64::from(fraction) * u64::from(precision.nanos_per_unit());
+        let nanos_per_second = u64::from(Self::NANOS_PER_SECOND);
+
+        Self::new(
+            i64::from(seconds) + (nanos / nanos_per_second) as i64,
+            (nanos % nanos_per_second) as u32,
+        )
+    }
+
+    /// Timestamp `nanos` nanoseconds after the Unix epoch, clamped to the range of timestamps
+    pub fn from_nanos(nanos: i128) -> Self {
+        let nanos_per_second = i128::from(Self::NANOS_PER_SECOND);
+        let seconds = nanos.div_euclid(nanos_per_second);
+
+        match i64::try_from(seconds) {
+            Ok(seconds) => Self::new(seconds, nanos.rem_euclid(nanos_per_second) as u32),
+            Err(_) if seconds < 0 => Self::new(i64::MIN, 0),
+            Err(_) => Self::new(i64::MAX, Self::NANOS_PER_SECOND - 1),
+        }
+    }
+
+    pub const fn seconds(self) -> i64 {
+        self.seconds
+    }
+
+    pub const fn subsec_nanos(self) -> u32 {
+        self.nanos
+    }
+
+    /// Fraction of a second in units of `precision`, as written in pcap records
+    pub const fn fraction(self, precision: TimestampPrecision) -> u32 {
+        self.nanos / precision.nanos_per_unit()
+    }
+
+    /// Nanoseconds since the Unix epoch
+    pub fn as_nanos(self) -> i128 {
+        i128::from(self.seconds) * i128::from(Self::NANOS_PER_SECOND) + i128::from(self.nanos)
+    }
+
+    /// Nanoseconds from `earlier` to this timestamp, negative if `earlier` is actually later and
+    /// clamped to the range of `i64`, which spans almost 300 years either way
+    pub fn nanos_since(self, earlier: Self) -> i64 {
+        let nanos = self.as_nanos() - earlier.as_nanos();
+
+        i64::try_from(nanos).unwrap_or(if nanos < 0 { i64::MIN } else { i64::MAX })
+    }
+
+    /// Timestamp `seconds` later, or earlier if `seconds` is negative
+    pub const fn add_seconds(self, seconds: i64) -> Self {
+        Self {
+            seconds: self.seconds.saturating_add(seconds),
+            nanos: self.nanos,
+        }
     }
 }
 
 impl Display for Timestamp {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self.0 {
-            TimestampPrecision::Micro => write!(f, "{}.{:0>6}", self.1, self.2),
-            TimestampPrecision::Nano => write!(f, "{}.{:0>9}", self.1, self.2),
-        }
+        let nanos = self.as_nanos();
+        let sign = if nanos < 0 { "-" } else { "" };
+        let nanos_per_second = u128::from(Self::NANOS_PER_SECOND);
+
+        write!(
+            f,
+            "{sign}{}.{:0>9}",
+            nanos.unsigned_abs() / nanos_per_second,
+            nanos.unsigned_abs() % nanos_per_second
+        )
     }
 }
 
@@ -128,7 +216,7 @@ mod tests {
 
         assert_eq!(
             packet_header.timestamp,
-            Timestamp(TimestampPrecision::Micro, 1_558_968_019, 69_732)
+            Timestamp::new(1_558_968_019, 69_732_000)
         );
         assert_eq!(packet_header.actual_length, PacketLength(52));
         assert_eq!(packet_header.captured_length, PacketLength(52));
@@ -137,16 +225,50 @@ mod tests {
     #[test]
     fn composing_packet_header_succeeds() {
         let header = PacketHeader {
-            timestamp: Timestamp(TimestampPrecision::Micro, 12345, 67890),
+            timestamp: Timestamp::new(12345, 67_890_000),
             captured_length: PacketLength(262_144),
             actual_length: PacketLength(262_144),
         };
 
-        let buffer = header.compose(Endianness::Identical);
+        let buffer = header.compose(Endianness::Identical, TimestampPrecision::Micro);
 
         assert_eq!(buffer.split_at(4).0, 12345u32.to_le_bytes());
         assert_eq!(buffer.split_at(4).1.split_at(4).0, 67890u32.to_le_bytes());
         assert_eq!(buffer.split_at(8).1.split_at(4).0, 262_144u32.to_le_bytes());
         assert_eq!(buffer.split_at(12).1, 262_144u32.to_le_bytes());
     }
+
+    #[test]
+    fn normalising_timestamps_of_every_precision_succeeds() {
+        let micro = Timestamp::from_fraction(1, 500_000, TimestampPrecision::Micro);
+        let nano = Timestamp::from_fraction(1, 500_000_000, TimestampPrecision::Nano);
+        assert_eq!(micro, nano);
+        assert_eq!(micro.as_nanos(), 1_500_000_000);
+        assert_eq!(micro.fraction(TimestampPrecision::Micro), 500_000);
+
+        let later = Timestamp::from_fraction(2, 250_000, TimestampPrecision::Micro);
+        assert_eq!(later.nanos_since(nano), 750_000_000);
+        assert_eq!(nano.nanos_since(later), -750_000_000);
+        assert_eq!(later.to_string(), "2.250000000");
+    }
+
+    #[test]
+    fn timestamps_outside_pcap_range_succeed() {
+        let after_2106 = Timestamp::new(i64::from(u32::MAX) + 1, 0);
+        assert!(after_2106 > Timestamp::new(i64::from(u32::MAX), 999_999_999));
+        assert_eq!(after_2106.to_string(), "4294967296.000000000");
+
+        let before_1970 = Timestamp::from_nanos(-1_500_000_000);
+        assert_eq!(before_1970, Timestamp::new(-2, 500_000_000));
+        assert_eq!(before_1970.to_string(), "-1.500000000");
+        assert_eq!(before_1970.add_seconds(2).to_string(), "0.500000000");
+
+        let header = PacketHeader {
+            timestamp: after_2106,
+            captured_length: PacketLength(0),
+            actual_length: PacketLength(0),
+        };
+        let buffer = header.compose(Endianness::Identical, TimestampPrecision::Nano);
+        assert_eq!(buffer[..8], [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+    }
 }
@@ -1,8 +1,52 @@
 use std::fmt::{Display, Formatter};
 
+use crate::error::Error;
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum LinkLayerType {
+    Null,
     En10Mb,
+    Raw,
+    Ieee80211Radiotap,
+    LinuxSll,
+    Ipv4,
+    Ipv6,
+    LinuxSll2,
+}
+
+impl TryFrom<u32> for LinkLayerType {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        let link_layer_type = match value {
+            0 => Self::Null,
+            1 => Self::En10Mb,
+            101 => Self::Raw,
+            113 => Self::LinuxSll,
+            127 => Self::Ieee80211Radiotap,
+            228 => Self::Ipv4,
+            229 => Self::Ipv6,
+            276 => Self::LinuxSll2,
+            link_layer_type => return Err(Error::UnknownLinkLayerType(link_layer_type)),
+        };
+
+        Ok(link_layer_type)
+    }
+}
+
+impl From<LinkLayerType> for u32 {
+    fn from(link_layer_type: LinkLayerType) -> Self {
+        match link_layer_type {
+            LinkLayerType::Null => 0,
+            LinkLayerType::En10Mb => 1,
+            LinkLayerType::Raw => 101,
+            LinkLayerType::LinuxSll => 113,
+            LinkLayerType::Ieee80211Radiotap => 127,
+            LinkLayerType::Ipv4 => 228,
+            LinkLayerType::Ipv6 => 229,
+            LinkLayerType::LinuxSll2 => 276,
+        }
+    }
 }
 
 impl Display for LinkLayerType {
@@ -11,7 +55,14 @@ impl Display for LinkLayerType {
             f,
             "{}",
             match self {
+                Self::Null => "Loopback",
                 Self::En10Mb => "Ethernet",
+                Self::Raw => "Raw IP",
+                Self::LinuxSll => "Linux cooked capture",
+                Self::Ieee80211Radiotap => "802.11 with radiotap",
+                Self::Ipv4 => "Raw IPv4",
+                Self::Ipv6 => "Raw IPv6",
+                Self::LinuxSll2 => "Linux cooked capture v2",
             }
         )
     }
@@ -23,6 +74,20 @@ pub enum NetworkLayerType {
     IPv6,
 }
 
+impl TryFrom<u16> for NetworkLayerType {
+    type Error = Error;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        let network_layer_type = match value {
+            0x0800 => Self::IPv4,
+            0x86DD => Self::IPv6,
+            network_layer_type => return Err(Error::UnknownNetworkLayerType(network_layer_type)),
+        };
+
+        Ok(network_layer_type)
+    }
+}
+
 impl Display for NetworkLayerType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -40,6 +105,44 @@ impl Display for NetworkLayerType {
 pub enum TransportLayerType {
     Tcp,
     Udp,
+    Icmp,
+    IcmpV6,
+}
+
+impl TransportLayerType {
+    /// Whether flows of the protocol are told apart by ports
+    pub const fn has_ports(self) -> bool {
+        matches!(self, Self::Tcp | Self::Udp)
+    }
+}
+
+impl TryFrom<u8> for TransportLayerType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let transport_layer_type = match value {
+            1 => Self::Icmp,
+            6 => Self::Tcp,
+            17 => Self::Udp,
+            58 => Self::IcmpV6,
+            transport_layer_type => {
+                return Err(Error::UnknownTransportLayerType(transport_layer_type))
+            }
+        };
+
+        Ok(transport_layer_type)
+    }
+}
+
+impl From<TransportLayerType> for u8 {
+    fn from(transport_layer_type: TransportLayerType) -> Self {
+        match transport_layer_type {
+            TransportLayerType::Icmp => 1,
+            TransportLayerType::Tcp => 6,
+            TransportLayerType::Udp => 17,
+            TransportLayerType::IcmpV6 => 58,
+        }
+    }
 }
 
 impl Display for TransportLayerType {
@@ -50,6 +153,8 @@ impl Display for TransportLayerType {
             match self {
                 Self::Udp => "UDP",
                 Self::Tcp => "TCP",
+                Self::Icmp => "ICMP",
+                Self::IcmpV6 => "ICMPv6",
             }
         )
     }
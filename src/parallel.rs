@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+
+use crate::capture::MappedRecords;
+use crate::error::Error;
+use crate::packet_dissection::{Dissection, PacketDissection};
+use crate::packet_header::PacketHeader;
+use crate::packet_layer::LinkLayerType;
+
+/// Length of the parts of a capture dissected by one thread at a time
+pub const CHUNK_LENGTH: u64 = 8 * 1024 * 1024;
+
+/// Number of chunks per thread that may be dissected ahead of the chunk handled next
+const CHUNKS_AHEAD_PER_THREAD: usize = 2;
+
+/// Record of a capture with the result of dissecting its packet
+pub struct DissectedRecord {
+    pub header: PacketHeader,
+    pub offset: u64,
+    pub dissection: Result<Dissection, Error>,
+}
+
+/// Records dissected from the part of a capture between two record boundaries
+struct DissectedChunk {
+    records: Vec<DissectedRecord>,
+    /// Error the capture ended with in the middle of a record, if it did
+    truncation: Option<Error>,
+}
+
+/// Dissects the records of a memory-mapped pcap capture on `threads` threads, passing the
+/// records of each chunk to `handle_batch` in capture order, and returns the number of records
+/// read before the capture ended in the middle of a record, if it did
+///
+/// The capture is split into chunks of about `chunk_length` bytes at the record boundaries
+/// found by walking the record headers. The threads take the next chunk to dissect as soon as
+/// they are done with one, and only get a few chunks ahead of the one handled next.
+pub fn dissect_records(
+    records: &MappedRecords,
+    link_layer_type: LinkLayerType,
+    decapsulate_tunnels: bool,
+    threads: usize,
+    chunk_length: u64,
+    mut handle_batch: impl FnMut(Vec<DissectedRecord>) -> Result<(), Error>,
+) -> Result<Option<usize>, Error> {
+    let boundaries = records.chunk_boundaries(chunk_length);
+    let chunks = boundaries
+        .windows(2)
+        .map(|boundary| (boundary[0], boundary[1]))
+        .collect::<Vec<(u64, u64)>>();
+
+    let threads = threads.max(1);
+    let chunks_ahead = threads * CHUNKS_AHEAD_PER_THREAD;
+
+    let (task_sender, tasks) = mpsc::channel();
+    let tasks = Mutex::new(tasks);
+    let (chunk_sender, dissected_chunks) = mpsc::channel();
+
+    thread::scope(|scope| {
+        // Moved into the scope, so that returning from it stops the threads waiting for a chunk,
+        // and so that receiving fails instead of blocking should every thread have panicked
+        let (task_sender, chunk_sender) = (task_sender, chunk_sender);
+
+        for _ in 0..threads {
+            let (tasks, chunks, chunk_sender) = (&tasks, &chunks, chunk_sender.clone());
+            scope.spawn(move || {
+                while let Ok(index) = tasks.lock().expect("no thread panics").recv() {
+                    let (start, end) = chunks[index];
+                    let chunk =
+                        dissect_chunk(records, start, end, link_layer_type, decapsulate_tunnels);
+                    if chunk_sender.send((index, chunk)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(chunk_sender);
+
+        for index in 0..chunks.len().min(chunks_ahead) {
+            let _ = task_sender.send(index);
+        }
+
+        // Chunks are dissected out of order, so those ahead of the next one to handle wait here
+        let mut waiting_chunks = HashMap::new();
+        let mut record_count = 0;
+        for index in 0..chunks.len() {
+            let chunk = loop {
+                if let Some(chunk) = waiting_chunks.remove(&index) {
+                    break chunk;
+                }
+                let (dissected_index, chunk) = dissected_chunks
+                    .recv()
+                    .expect("threads dissect every chunk they are given");
+                waiting_chunks.insert(dissected_index, chunk);
+            };
+            if index + chunks_ahead < chunks.len() {
+                let _ = task_sender.send(index + chunks_ahead);
+            }
+
+            let DissectedChunk {
+                records,
+                truncation,
+            } = chunk;
+            record_count += records.len();
+            handle_batch(records)?;
+
+            // Chunks end at record boundaries, so only the last one can end in a truncated record
+            if truncation.is_some() {
+                return Ok(Some(record_count));
+            }
+        }
+
+        Ok(None)
+    })
+}
+
+fn dissect_chunk(
+    records: &MappedRecords,
+    start: u64,
+    end: u64,
+    link_layer_type: LinkLayerType,
+    decapsulate_tunnels: bool,
+) -> DissectedChunk {
+    let mut dissected_records = Vec::new();
+    let mut position = start;
+    let mut truncation = None;
+
+    while position < end {
+        let (header, packet, next_position) = match records.get(position) {
+            Ok(record) => record,
+            Err(e) => {
+                truncation = Some(e);
+                break;
+            }
+        };
+
+        dissected_records.push(DissectedRecord {
+            header,
+            offset: position,
+            dissection: PacketDissection::from_packet(
+                &packet,
+                link_layer_type,
+                decapsulate_tunnels,
+            ),
+        });
+        position = next_position;
+    }
+
+    DissectedChunk {
+        records: dissected_records,
+        truncation,
+    }
+}
@@ -0,0 +1,927 @@
+use crate::capture_file::{on_end_of_file, ReadFile};
+use crate::capture_header::{CaptureHeader, MaximumPacketLength, TimestampPrecision, Version};
+use crate::endianness_aware_cursor::{
+    Endianness, ReadOnlyEndiannessAwareCursor, WriteOnlyEndiannessAwareCursor,
+};
+use crate::error::Error;
+use crate::packet::Packet;
+use crate::packet_header::{PacketHeader, PacketLength, Timestamp};
+use crate::packet_layer::LinkLayerType;
+
+/// Reads packets out of a pcapng file, keeping track of the sections and interfaces seen so far
+#[derive(Debug)]
+pub struct PcapngReader {
+    sections: Vec<Section>,
+    current_section: usize,
+    scanned_until: u64,
+    packet_interface: (usize, u32),
+    packet_options: Vec<BlockOption>,
+}
+
+impl PcapngReader {
+    pub fn is_section_header_block(buffer: &[u8]) -> bool {
+        buffer.starts_with(&SectionHeaderBlock::TYPE.to_be_bytes())
+    }
+
+    /// Reads blocks up to the first interface description, and derives a classic capture header
+    /// from the first section and interface
+    pub fn open(file: &mut ReadFile) -> Result<(CaptureHeader, Self), Error> {
+        let mut reader = Self {
+            sections: Vec::new(),
+            current_section: 0,
+            scanned_until: 0,
+            packet_interface: (0, 0),
+            packet_options: Vec::new(),
+        };
+
+        loop {
+            match reader.read_block(file)?.ok_or(Error::UnexpectedEndOfFile)? {
+                Block::InterfaceDescription => break,
+                Block::EnhancedPacket(block) => {
+                    return Err(Error::UnknownInterface(block.interface_id))
+                }
+                Block::SimplePacket(_) => return Err(Error::UnknownInterface(0)),
+                Block::SectionHeader | Block::Unknown => {}
+            }
+        }
+
+        reader.packet_interface = (reader.current_section, 0);
+
+        let section = &reader.sections[reader.current_section];
+        let interface = &section.interfaces[0];
+
+        let timestamp_precision = match interface.timestamp_resolution {
+            TimestampResolution::Decimal(6) => TimestampPrecision::Micro,
+            _ => TimestampPrecision::Nano,
+        };
+
+        let header = CaptureHeader {
+            endianness: section.header.endianness,
+            timestamp_precision,
+            version: section.header.version,
+            maximum_packet_length: MaximumPacketLength(interface.snap_length),
+            frame_cyclic_sequence: None,
+            link_layer_type: interface.link_layer_type,
+        };
+
+        Ok((header, reader))
+    }
+
+    /// Reads blocks until a packet is found, returning it with the link layer type of the
+    /// interface it was captured on
+    pub fn read_packet(
+        &mut self,
+        file: &mut ReadFile,
+    ) -> Result<Option<(PacketHeader, Packet<'static>, LinkLayerType)>, Error> {
+        loop {
+            let Some(block) = self.read_block(file)? else {
+                return Ok(None);
+            };
+
+            match block {
+                Block::EnhancedPacket(block) => {
+                    let interface = self.find_interface(block.interface_id)?;
+
+                    let packet_header = PacketHeader {
+                        timestamp: interface.timestamp(block.timestamp),
+                        captured_length: PacketLength(block.packet.len() as u32),
+                        actual_length: PacketLength(block.original_length),
+                    };
+                    let link_layer_type = interface.link_layer_type;
+
+                    self.packet_interface = (self.current_section, block.interface_id);
+                    self.packet_options = block.options;
+
+                    return Ok(Some((packet_header, block.packet.into(), link_layer_type)));
+                }
+                Block::SimplePacket(mut block) => {
+                    let interface = self.find_interface(0)?;
+
+                    // Simple packet blocks are padded, captured length has to be inferred from
+                    // the original length and the snapshot length of the interface
+                    let mut captured_length = block.original_length.min(block.packet.len() as u32);
+                    if interface.snap_length != 0 {
+                        captured_length = captured_length.min(interface.snap_length);
+                    }
+                    block.packet.truncate(captured_length as usize);
+
+                    let packet_header = PacketHeader {
+                        // Simple packet blocks have no timestamp
+                        timestamp: Timestamp::default(),
+                        captured_length: PacketLength(captured_length),
+                        actual_length: PacketLength(block.original_length),
+                    };
+                    let link_layer_type = interface.link_layer_type;
+
+                    self.packet_interface = (self.current_section, 0);
+                    self.packet_options = Vec::new();
+
+                    return Ok(Some((packet_header, block.packet.into(), link_layer_type)));
+                }
+                Block::SectionHeader | Block::InterfaceDescription | Block::Unknown => {}
+            }
+        }
+    }
+
+    /// Moves to the block starting at `position`, reading any sections and interfaces declared
+    /// between the furthest block read so far and `position`
+    pub fn seek(&mut self, file: &mut ReadFile, position: u64) -> Result<(), Error> {
+        if position > self.scanned_until {
+            file.seek(self.scanned_until)?;
+            while file.position() < position {
+                if self.read_block(file)?.is_none() {
+                    break;
+                }
+            }
+        }
+
+        file.seek(position)?;
+        self.current_section = self
+            .sections
+            .iter()
+            .rposition(|section| section.offset <= position)
+            .unwrap_or(0);
+
+        Ok(())
+    }
+
+    /// Section the last packet returned belongs to
+    pub fn section_header(&self) -> &SectionHeaderBlock {
+        &self.sections[self.packet_interface.0].header
+    }
+
+    /// Interface the last packet returned was captured on
+    pub fn interface(&self) -> &InterfaceDescriptionBlock {
+        let (section, interface_id) = self.packet_interface;
+
+        &self.sections[section].interfaces[interface_id as usize]
+    }
+
+    /// Options of the last packet returned, such as comments
+    pub fn packet_options(&self) -> &[BlockOption] {
+        self.packet_options.as_slice()
+    }
+
+    fn find_interface(&self, interface_id: u32) -> Result<&InterfaceDescriptionBlock, Error> {
+        self.sections
+            .get(self.current_section)
+            .and_then(|section| section.interfaces.get(interface_id as usize))
+            .ok_or(Error::UnknownInterface(interface_id))
+    }
+
+    fn endianness(&self) -> Endianness {
+        self.sections
+            .get(self.current_section)
+            .map_or(Endianness::Identical, |section| section.header.endianness)
+    }
+
+    /// Reads the next block, returning `None` if the file ends cleanly before it
+    fn read_block(&mut self, file: &mut ReadFile) -> Result<Option<Block>, Error> {
+        let offset = file.position();
+        let is_new = offset >= self.scanned_until;
+
+        let truncated_header = |e| on_end_of_file(e, Error::TruncatedRecordHeader(offset));
+        let truncated_body = |e| on_end_of_file(e, Error::TruncatedRecord(offset));
+
+        let Some(type_buffer) = file.read_or_eof(4).map_err(truncated_header)? else {
+            return Ok(None);
+        };
+        let type_buffer = type_buffer.into_owned();
+
+        let block = if Self::is_section_header_block(&type_buffer) {
+            // Byte order of a section is only known after reading the byte order magic
+            let length_buffer = file.read(8).map_err(truncated_header)?.into_owned();
+            let endianness = SectionHeaderBlock::parse_endianness(&length_buffer[4..])?;
+            let total_length =
+                ReadOnlyEndiannessAwareCursor::new(&length_buffer, endianness).get_u32();
+
+            let mut body = length_buffer[4..].to_vec();
+            body.extend_from_slice(
+                &file
+                    .read(remaining_block_length(total_length, 12)?)
+                    .map_err(truncated_body)?,
+            );
+            body.truncate(body.len() - 4);
+
+            check_body_length(&body, SectionHeaderBlock::MINIMUM_BODY_LENGTH, total_length)?;
+            let header = SectionHeaderBlock::parse(&body, endianness);
+            if is_new {
+                self.sections.push(Section {
+                    offset,
+                    header,
+                    interfaces: Vec::new(),
+                });
+                self.current_section = self.sections.len() - 1;
+            } else if let Some(index) = self.sections.iter().position(|s| s.offset == offset) {
+                self.current_section = index;
+            }
+
+            Block::SectionHeader
+        } else {
+            let endianness = self.endianness();
+            let block_type = ReadOnlyEndiannessAwareCursor::new(&type_buffer, endianness).get_u32();
+            let length_buffer = file.read(4).map_err(truncated_header)?;
+            let total_length =
+                ReadOnlyEndiannessAwareCursor::new(&length_buffer, endianness).get_u32();
+
+            let body = file
+                .read(remaining_block_length(total_length, 8)?)
+                .map_err(truncated_body)?;
+            let body = &body[..body.len() - 4];
+
+            match block_type {
+                InterfaceDescriptionBlock::TYPE => {
+                    check_body_length(
+                        body,
+                        InterfaceDescriptionBlock::MINIMUM_BODY_LENGTH,
+                        total_length,
+                    )?;
+                    let interface = InterfaceDescriptionBlock::parse(body, endianness)?;
+                    if is_new {
+                        if let Some(section) = self.sections.get_mut(self.current_section) {
+                            section.interfaces.push(interface);
+                        }
+                    }
+
+                    Block::InterfaceDescription
+                }
+                EnhancedPacketBlock::TYPE => {
+                    check_body_length(
+                        body,
+                        EnhancedPacketBlock::MINIMUM_BODY_LENGTH,
+                        total_length,
+                    )?;
+                    Block::EnhancedPacket(EnhancedPacketBlock::parse(body, endianness))
+                }
+                SimplePacketBlock::TYPE => {
+                    check_body_length(body, SimplePacketBlock::MINIMUM_BODY_LENGTH, total_length)?;
+                    Block::SimplePacket(SimplePacketBlock::parse(body, endianness))
+                }
+                _ => Block::Unknown,
+            }
+        };
+
+        self.scanned_until = self.scanned_until.max(file.position());
+
+        Ok(Some(block))
+    }
+}
+
+#[derive(Debug)]
+struct Section {
+    offset: u64,
+    header: SectionHeaderBlock,
+    interfaces: Vec<InterfaceDescriptionBlock>,
+}
+
+enum Block {
+    SectionHeader,
+    InterfaceDescription,
+    EnhancedPacket(EnhancedPacketBlock),
+    SimplePacket(SimplePacketBlock),
+    Unknown,
+}
+
+/// Returns the length of a block that is left to read after `read` bytes of it, including the
+/// trailing block total length
+fn remaining_block_length(total_length: u32, read: usize) -> Result<usize, Error> {
+    let total_length_usize = total_length as usize;
+    if !total_length.is_multiple_of(4) || total_length_usize < read + 4 {
+        return Err(Error::InvalidBlockLength(total_length));
+    }
+
+    Ok(total_length_usize - read)
+}
+
+fn check_body_length(body: &[u8], minimum_length: usize, total_length: u32) -> Result<(), Error> {
+    if body.len() < minimum_length {
+        return Err(Error::InvalidBlockLength(total_length));
+    }
+
+    Ok(())
+}
+
+/// Wraps `body` with the block type and the leading and trailing block total lengths
+fn compose_block(block_type: u32, body: &[u8], endianness: Endianness) -> Vec<u8> {
+    let total_length = (body.len() + 12) as u32;
+
+    let mut cursor = WriteOnlyEndiannessAwareCursor::new(endianness);
+    cursor.put_u32(block_type);
+    cursor.put_u32(total_length);
+    cursor.put_slice(body);
+    cursor.put_u32(total_length);
+
+    cursor.into_vec()
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SectionHeaderBlock {
+    pub endianness: Endianness,
+    pub version: Version,
+    pub options: Vec<BlockOption>,
+}
+
+impl SectionHeaderBlock {
+    pub const TYPE: u32 = 0x0A_0D_0D_0A;
+    const MINIMUM_BODY_LENGTH: usize = 16;
+
+    const BYTE_ORDER_MAGIC_IDENTICAL: u32 = 0x1A_2B_3C_4D;
+    const BYTE_ORDER_MAGIC_SWAPPED: u32 = 0x4D_3C_2B_1A;
+
+    pub fn parse_endianness(buffer: &[u8]) -> Result<Endianness, Error> {
+        let mut cursor = ReadOnlyEndiannessAwareCursor::new(buffer, Endianness::Identical);
+
+        match cursor.get_u32() {
+            Self::BYTE_ORDER_MAGIC_IDENTICAL => Ok(Endianness::Identical),
+            Self::BYTE_ORDER_MAGIC_SWAPPED => Ok(Endianness::Swapped),
+            byte_order_magic => Err(Error::UnknownByteOrderMagic(byte_order_magic)),
+        }
+    }
+
+    pub fn parse(body: &[u8], endianness: Endianness) -> Self {
+        let mut cursor = ReadOnlyEndiannessAwareCursor::new(body, endianness);
+
+        cursor.advance(4);
+        let version = Version(cursor.get_u16(), cursor.get_u16());
+        let _section_length = cursor.get_u64();
+        let options = BlockOption::parse_all(&mut cursor);
+
+        Self {
+            endianness,
+            version,
+            options,
+        }
+    }
+
+    pub fn compose(&self) -> Vec<u8> {
+        let mut cursor = WriteOnlyEndiannessAwareCursor::new(self.endianness);
+
+        cursor.put_u32(Self::BYTE_ORDER_MAGIC_IDENTICAL);
+        cursor.put_u16(self.version.0);
+        cursor.put_u16(self.version.1);
+        // Section length is not known while writing
+        cursor.put_u64(u64::MAX);
+        BlockOption::compose_all(&mut cursor, &self.options);
+
+        compose_block(Self::TYPE, &cursor.into_vec(), self.endianness)
+    }
+}
+
+impl From<CaptureHeader> for SectionHeaderBlock {
+    fn from(header: CaptureHeader) -> Self {
+        Self {
+            endianness: header.endianness,
+            version: Version(1, 0),
+            options: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct InterfaceDescriptionBlock {
+    pub link_layer_type: LinkLayerType,
+    pub snap_length: u32,
+    pub timestamp_resolution: TimestampResolution,
+    /// Seconds added to the timestamps of the interface, which may be negative
+    pub timestamp_offset: i64,
+    pub options: Vec<BlockOption>,
+}
+
+impl InterfaceDescriptionBlock {
+    pub const TYPE: u32 = 0x00_00_00_01;
+    const MINIMUM_BODY_LENGTH: usize = 8;
+
+    const TIMESTAMP_RESOLUTION_OPTION_CODE: u16 = 9;
+    const TIMESTAMP_OFFSET_OPTION_CODE: u16 = 14;
+
+    pub fn parse(body: &[u8], endianness: Endianness) -> Result<Self, Error> {
+        let mut cursor = ReadOnlyEndiannessAwareCursor::new(body, endianness);
+
+        let link_layer_type = LinkLayerType::try_from(u32::from(cursor.get_u16()))?;
+        cursor.advance(2);
+        let snap_length = cursor.get_u32();
+        let options = BlockOption::parse_all(&mut cursor);
+
+        let timestamp_resolution = options
+            .iter()
+            .find(|option| option.code == Self::TIMESTAMP_RESOLUTION_OPTION_CODE)
+            .and_then(|option| option.value.first())
+            .map_or(TimestampResolution::DEFAULT, |value| {
+                TimestampResolution::from(*value)
+            });
+        let timestamp_offset = options
+            .iter()
+            .find(|option| option.code == Self::TIMESTAMP_OFFSET_OPTION_CODE)
+            .filter(|option| option.value.len() == 8)
+            .map_or(0, |option| {
+                ReadOnlyEndiannessAwareCursor::new(&option.value, endianness).get_u64() as i64
+            });
+
+        let block = Self {
+            link_layer_type,
+            snap_length,
+            timestamp_resolution,
+            timestamp_offset,
+            options,
+        };
+
+        Ok(block)
+    }
+
+    pub fn compose(&self, endianness: Endianness) -> Vec<u8> {
+        let mut cursor = WriteOnlyEndiannessAwareCursor::new(endianness);
+
+        cursor.put_u16(u32::from(self.link_layer_type) as u16);
+        cursor.advance(2);
+        cursor.put_u32(self.snap_length);
+        BlockOption::compose_all(&mut cursor, &self.options);
+
+        compose_block(Self::TYPE, &cursor.into_vec(), endianness)
+    }
+
+    /// Converts a timestamp of a packet captured on the interface into a timestamp
+    pub fn timestamp(&self, units: u64) -> Timestamp {
+        self.timestamp_resolution
+            .timestamp(units)
+            .add_seconds(self.timestamp_offset)
+    }
+
+    /// Converts `timestamp` into a timestamp of a packet captured on the interface
+    pub fn units(&self, timestamp: Timestamp) -> u64 {
+        self.timestamp_resolution
+            .units(timestamp.add_seconds(self.timestamp_offset.saturating_neg()))
+    }
+
+    /// Copy of the interface whose timestamps are in units of `timestamp_resolution`
+    pub fn with_timestamp_resolution(&self, timestamp_resolution: TimestampResolution) -> Self {
+        let mut options = self
+            .options
+            .iter()
+            .filter(|option| option.code != Self::TIMESTAMP_RESOLUTION_OPTION_CODE)
+            .cloned()
+            .collect::<Vec<BlockOption>>();
+        if timestamp_resolution != TimestampResolution::DEFAULT {
+            options.push(BlockOption {
+                code: Self::TIMESTAMP_RESOLUTION_OPTION_CODE,
+                value: vec![timestamp_resolution.into()],
+            });
+        }
+
+        Self {
+            timestamp_resolution,
+            options,
+            ..self.clone()
+        }
+    }
+}
+
+impl From<CaptureHeader> for InterfaceDescriptionBlock {
+    fn from(header: CaptureHeader) -> Self {
+        let timestamp_resolution = match header.timestamp_precision {
+            TimestampPrecision::Micro => TimestampResolution::Decimal(6),
+            TimestampPrecision::Nano => TimestampResolution::Decimal(9),
+        };
+
+        let options = if timestamp_resolution == TimestampResolution::DEFAULT {
+            Vec::new()
+        } else {
+            vec![BlockOption {
+                code: Self::TIMESTAMP_RESOLUTION_OPTION_CODE,
+                value: vec![timestamp_resolution.into()],
+            }]
+        };
+
+        Self {
+            link_layer_type: header.link_layer_type,
+            snap_length: header.maximum_packet_length.0,
+            timestamp_resolution,
+            timestamp_offset: 0,
+            options,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct EnhancedPacketBlock {
+    pub interface_id: u32,
+    pub timestamp: u64,
+    pub original_length: u32,
+    pub packet: Vec<u8>,
+    pub options: Vec<BlockOption>,
+}
+
+impl EnhancedPacketBlock {
+    pub const TYPE: u32 = 0x00_00_00_06;
+    const MINIMUM_BODY_LENGTH: usize = 20;
+
+    pub fn parse(body: &[u8], endianness: Endianness) -> Self {
+        let mut cursor = ReadOnlyEndiannessAwareCursor::new(body, endianness);
+
+        let interface_id = cursor.get_u32();
+        let timestamp = u64::from(cursor.get_u32()) << 32 | u64::from(cursor.get_u32());
+        let captured_length = (cursor.get_u32() as usize).min(cursor.remaining().saturating_sub(4));
+        let original_length = cursor.get_u32();
+        let packet = cursor.get_slice(captured_length).to_vec();
+        cursor.advance(padding(captured_length).min(cursor.remaining()));
+        let options = BlockOption::parse_all(&mut cursor);
+
+        Self {
+            interface_id,
+            timestamp,
+            original_length,
+            packet,
+            options,
+        }
+    }
+
+    pub fn compose(&self, endianness: Endianness) -> Vec<u8> {
+        let mut cursor = WriteOnlyEndiannessAwareCursor::new(endianness);
+
+        cursor.put_u32(self.interface_id);
+        cursor.put_u32((self.timestamp >> 32) as u32);
+        cursor.put_u32(self.timestamp as u32);
+        cursor.put_u32(self.packet.len() as u32);
+        cursor.put_u32(self.original_length);
+        cursor.put_slice(&self.packet);
+        cursor.advance(padding(self.packet.len()));
+        BlockOption::compose_all(&mut cursor, &self.options);
+
+        compose_block(Self::TYPE, &cursor.into_vec(), endianness)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SimplePacketBlock {
+    pub original_length: u32,
+    pub packet: Vec<u8>,
+}
+
+impl SimplePacketBlock {
+    pub const TYPE: u32 = 0x00_00_00_03;
+    const MINIMUM_BODY_LENGTH: usize = 4;
+
+    pub fn parse(body: &[u8], endianness: Endianness) -> Self {
+        let mut cursor = ReadOnlyEndiannessAwareCursor::new(body, endianness);
+
+        let original_length = cursor.get_u32();
+        let packet = cursor.get_slice(cursor.remaining()).to_vec();
+
+        Self {
+            original_length,
+            packet,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct BlockOption {
+    pub code: u16,
+    pub value: Vec<u8>,
+}
+
+impl BlockOption {
+    const END_OF_OPTIONS_CODE: u16 = 0;
+    const COMMENT_CODE: u16 = 1;
+
+    pub fn comment(comment: &str) -> Self {
+        Self {
+            code: Self::COMMENT_CODE,
+            value: comment.as_bytes().to_vec(),
+        }
+    }
+
+    fn parse_all(cursor: &mut ReadOnlyEndiannessAwareCursor) -> Vec<Self> {
+        let mut options = Vec::new();
+
+        while cursor.remaining() >= 4 {
+            let code = cursor.get_u16();
+            let length = cursor.get_u16() as usize;
+            if code == Self::END_OF_OPTIONS_CODE || length > cursor.remaining() {
+                break;
+            }
+
+            let value = cursor.get_slice(length).to_vec();
+            cursor.advance(padding(length).min(cursor.remaining()));
+
+            options.push(Self { code, value });
+        }
+
+        options
+    }
+
+    fn compose_all(cursor: &mut WriteOnlyEndiannessAwareCursor, options: &[Self]) {
+        if options.is_empty() {
+            return;
+        }
+
+        for option in options {
+            cursor.put_u16(option.code);
+            cursor.put_u16(option.value.len() as u16);
+            cursor.put_slice(&option.value);
+            cursor.advance(padding(option.value.len()));
+        }
+
+        cursor.put_u16(Self::END_OF_OPTIONS_CODE);
+        cursor.put_u16(0);
+    }
+}
+
+/// Number of bytes needed to pad `length` to a 32-bit boundary
+const fn padding(length: usize) -> usize {
+    (4 - length % 4) % 4
+}
+
+/// Unit of the timestamps of an interface, as a negative power of 10 or 2
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum TimestampResolution {
+    Decimal(u8),
+    Binary(u8),
+}
+
+impl TimestampResolution {
+    pub const DEFAULT: Self = Self::Decimal(6);
+
+    const BINARY_FLAG: u8 = 0x80;
+
+    /// Converts a timestamp in units of this resolution into a timestamp, rounding down to
+    /// whole nanoseconds
+    pub fn timestamp(self, units: u64) -> Timestamp {
+        let units_per_second = self.units_per_second();
+        let nanos_per_second = u128::from(Timestamp::NANOS_PER_SECOND);
+
+        let units = u128::from(units);
+        let seconds = units / units_per_second;
+        let nanos = units % units_per_second * nanos_per_second / units_per_second;
+
+        Timestamp::new(seconds as i64, nanos as u32)
+    }
+
+    /// Converts `timestamp` into units of this resolution, clamping timestamps before the Unix
+    /// epoch to zero
+    pub fn units(self, timestamp: Timestamp) -> u64 {
+        let Ok(seconds) = u128::try_from(timestamp.seconds()) else {
+            return 0;
+        };
+        let units_per_second = self.units_per_second();
+        let nanos_per_second = u128::from(Timestamp::NANOS_PER_SECOND);
+
+        let units = seconds.saturating_mul(units_per_second).saturating_add(
+            u128::from(timestamp.subsec_nanos()) * units_per_second / nanos_per_second,
+        );
+
+        u64::try_from(units).unwrap_or(u64::MAX)
+    }
+
+    fn units_per_second(self) -> u128 {
+        match self {
+            Self::Decimal(exponent) => 10u128.checked_pow(exponent.into()),
+            Self::Binary(exponent) => 1u128.checked_shl(exponent.into()),
+        }
+        .unwrap_or(u128::MAX)
+    }
+}
+
+impl From<TimestampResolution> for u8 {
+    fn from(timestamp_resolution: TimestampResolution) -> Self {
+        match timestamp_resolution {
+            TimestampResolution::Decimal(exponent) => exponent,
+            TimestampResolution::Binary(exponent) => exponent | TimestampResolution::BINARY_FLAG,
+        }
+    }
+}
+
+impl From<u8> for TimestampResolution {
+    fn from(value: u8) -> Self {
+        if value & Self::BINARY_FLAG == 0 {
+            Self::Decimal(value)
+        } else {
+            Self::Binary(value & !Self::BINARY_FLAG)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::{ReadOnlyCapture, WriteOnlyPcapngCapture};
+    use crate::capture_header::{TimestampPrecision, Version};
+    use crate::endianness_aware_cursor::Endianness;
+    use crate::packet::Packet;
+    use crate::packet_header::{PacketHeader, PacketLength, Timestamp};
+    use crate::packet_layer::LinkLayerType;
+    use crate::pcapng::{
+        BlockOption, InterfaceDescriptionBlock, SectionHeaderBlock, TimestampResolution,
+    };
+    use crate::test_util::write_temp_file;
+
+    #[test]
+    fn converting_decimal_timestamp_succeeds() {
+        let timestamp = TimestampResolution::Decimal(6).timestamp(1_558_968_019_069_732);
+
+        assert_eq!(timestamp, Timestamp::new(1_558_968_019, 69_732_000));
+    }
+
+    #[test]
+    fn converting_binary_timestamp_succeeds() {
+        let timestamp = TimestampResolution::Binary(10).timestamp(3 * 1024 + 512);
+
+        assert_eq!(timestamp, Timestamp::new(3, 500_000_000));
+    }
+
+    #[test]
+    fn converting_timestamp_with_negative_offset_succeeds() {
+        let interface = InterfaceDescriptionBlock {
+            link_layer_type: LinkLayerType::En10Mb,
+            snap_length: 65535,
+            timestamp_resolution: TimestampResolution::Decimal(3),
+            timestamp_offset: -10,
+            options: Vec::new(),
+        };
+
+        let timestamp = interface.timestamp(2_500);
+
+        assert_eq!(timestamp, Timestamp::new(-8, 500_000_000));
+        assert_eq!(timestamp.to_string(), "-7.500000000");
+        assert_eq!(interface.units(timestamp), 2_500);
+    }
+
+    #[test]
+    fn converting_timestamp_after_2106_succeeds() {
+        let units = 5_000_000_000 * 1_000_000 + 250_000;
+
+        let timestamp = TimestampResolution::DEFAULT.timestamp(units);
+
+        assert_eq!(timestamp, Timestamp::new(5_000_000_000, 250_000_000));
+        assert_eq!(TimestampResolution::DEFAULT.units(timestamp), units);
+    }
+
+    #[test]
+    fn reading_sections_with_differing_endianness_succeeds() {
+        let mut buffer = Vec::new();
+        buffer.extend(section_header_block(Endianness::Swapped));
+        buffer.extend(interface_description_block(Endianness::Swapped, 6));
+        buffer.extend(block(Endianness::Swapped, 0x0BAD, &[0; 8]));
+        buffer.extend(enhanced_packet_block(
+            Endianness::Swapped,
+            1_000_001,
+            &[0xAA; 5],
+        ));
+        buffer.extend(section_header_block(Endianness::Identical));
+        buffer.extend(interface_description_block(Endianness::Identical, 9));
+        buffer.extend(enhanced_packet_block(
+            Endianness::Identical,
+            2_000_000_003,
+            &[0xBB; 8],
+        ));
+
+        let file_path = write_temp_file(&buffer);
+        let (header, mut capture) = ReadOnlyCapture::open(&file_path).unwrap();
+
+        assert_eq!(header.endianness, Endianness::Swapped);
+        assert_eq!(header.timestamp_precision, TimestampPrecision::Micro);
+        assert_eq!(header.link_layer_type, LinkLayerType::En10Mb);
+
+        let first_position = capture.position();
+        let (packet_header, packet) = capture.get().unwrap().unwrap();
+        assert_eq!(packet_header.timestamp, Timestamp::new(1, 1_000));
+        assert_eq!(packet_header.captured_length, PacketLength(5));
+        assert_eq!(packet.as_slice(), [0xAA; 5]);
+
+        let second_position = capture.position();
+        let (packet_header, packet) = capture.get().unwrap().unwrap();
+        assert_eq!(packet_header.timestamp, Timestamp::new(2, 3));
+        assert_eq!(packet.as_slice(), [0xBB; 8]);
+
+        assert!(capture.get().unwrap().is_none());
+
+        let (_, mut capture) = ReadOnlyCapture::open(&file_path).unwrap();
+        capture.record_at(second_position).unwrap();
+        assert_eq!(capture.packet().as_slice(), [0xBB; 8]);
+        capture.record_at(first_position).unwrap();
+        assert_eq!(capture.packet().as_slice(), [0xAA; 5]);
+    }
+
+    #[test]
+    fn writing_interfaces_and_comments_succeeds() {
+        let section_header = SectionHeaderBlock {
+            endianness: Endianness::Swapped,
+            version: Version(1, 0),
+            options: vec![BlockOption::comment("section")],
+        };
+        let interface = InterfaceDescriptionBlock {
+            link_layer_type: LinkLayerType::En10Mb,
+            snap_length: 65535,
+            timestamp_resolution: TimestampResolution::Binary(20),
+            timestamp_offset: 0,
+            options: vec![
+                BlockOption {
+                    code: 2,
+                    value: b"eth0".to_vec(),
+                },
+                BlockOption {
+                    code: 9,
+                    value: vec![0x80 | 20],
+                },
+            ],
+        };
+        let packet_header = PacketHeader {
+            timestamp: Timestamp::new(7, 500_000_000),
+            captured_length: PacketLength(3),
+            actual_length: PacketLength(60),
+        };
+        let packet = Packet::from(vec![1, 2, 3]);
+
+        let file_path = write_temp_file(&[]);
+        let mut capture = WriteOnlyPcapngCapture::create(&file_path, &section_header).unwrap();
+        for _ in 0..2 {
+            capture
+                .put(
+                    &interface,
+                    packet_header,
+                    &packet,
+                    vec![BlockOption::comment("[TCP] 10.0.0.1:1234 ↔ 10.0.0.2:80")],
+                )
+                .unwrap();
+        }
+        drop(capture);
+
+        let (header, mut capture) = ReadOnlyCapture::open(&file_path).unwrap();
+        assert_eq!(header.timestamp_precision, TimestampPrecision::Nano);
+        for _ in 0..2 {
+            let (read_packet_header, read_packet) = capture.get().unwrap().unwrap();
+            assert_eq!(read_packet_header, packet_header);
+            assert_eq!(read_packet, packet);
+
+            let (read_section_header, read_interface, read_options) =
+                capture.pcapng_context().unwrap();
+            assert_eq!(read_section_header.options, section_header.options);
+            assert_eq!(*read_interface, interface);
+            assert_eq!(
+                read_options,
+                [BlockOption::comment("[TCP] 10.0.0.1:1234 ↔ 10.0.0.2:80")]
+            );
+        }
+        assert!(capture.get().unwrap().is_none());
+    }
+
+    fn block(endianness: Endianness, block_type: u32, body: &[u8]) -> Vec<u8> {
+        let total_length = (body.len() + 12) as u32;
+
+        let mut buffer = Vec::new();
+        buffer.extend(to_bytes(endianness, block_type));
+        buffer.extend(to_bytes(endianness, total_length));
+        buffer.extend(body);
+        buffer.extend(to_bytes(endianness, total_length));
+
+        buffer
+    }
+
+    fn section_header_block(endianness: Endianness) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend(to_bytes(endianness, 0x1A_2B_3C_4D));
+        body.extend(to_bytes_u16(endianness, 1));
+        body.extend(to_bytes_u16(endianness, 0));
+        body.extend([0xFF; 8]);
+
+        block(endianness, 0x0A_0D_0D_0A, &body)
+    }
+
+    fn interface_description_block(endianness: Endianness, timestamp_resolution: u8) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend(to_bytes_u16(endianness, 1));
+        body.extend(to_bytes_u16(endianness, 0));
+        body.extend(to_bytes(endianness, 65535));
+        body.extend(to_bytes_u16(endianness, 9));
+        body.extend(to_bytes_u16(endianness, 1));
+        body.extend([timestamp_resolution, 0, 0, 0]);
+        body.extend([0; 4]);
+
+        block(endianness, 1, &body)
+    }
+
+    fn enhanced_packet_block(endianness: Endianness, timestamp: u64, packet: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend(to_bytes(endianness, 0));
+        body.extend(to_bytes(endianness, (timestamp >> 32) as u32));
+        body.extend(to_bytes(endianness, timestamp as u32));
+        body.extend(to_bytes(endianness, packet.len() as u32));
+        body.extend(to_bytes(endianness, packet.len() as u32));
+        body.extend(packet);
+        body.resize(body.len().next_multiple_of(4), 0);
+
+        block(endianness, 6, &body)
+    }
+
+    fn to_bytes_u16(endianness: Endianness, value: u16) -> [u8; 2] {
+        match endianness {
+            Endianness::Identical => value.to_be_bytes(),
+            Endianness::Swapped => value.to_le_bytes(),
+        }
+    }
+
+    fn to_bytes(endianness: Endianness, value: u32) -> [u8; 4] {
+        match endianness {
+            Endianness::Identical => value.to_be_bytes(),
+            Endianness::Swapped => value.to_le_bytes(),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+use clap::ValueEnum;
+
+use crate::packet_dissection::Fragment;
+use crate::packet_layer::TransportLayerType;
+
+/// Which data to keep where fragments of a datagram overlap
+#[derive(ValueEnum, Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub enum OverlapPolicy {
+    /// Keep the data of the fragment that arrived first
+    #[default]
+    First,
+    /// Keep the data of the fragment that arrived last
+    Last,
+}
+
+/// Collects fragments of IPv4 and IPv6 datagrams until they can be reassembled
+///
+/// Fragments are grouped by source, destination, protocol and identification. `T` is whatever
+/// the caller needs to remember about the record each fragment came from.
+pub struct Reassembler<T> {
+    datagrams: HashMap<DatagramKey, Datagram<T>>,
+    /// Keys of the incomplete datagrams by the time their first fragment arrived, so that
+    /// expiring them does not look at every datagram
+    first_seen: BTreeMap<i64, Vec<DatagramKey>>,
+    timeout: u32,
+    overlap_policy: OverlapPolicy,
+}
+
+type DatagramKey = (IpAddr, IpAddr, TransportLayerType, u32);
+
+struct Datagram<T> {
+    fragments: Vec<Fragment>,
+    records: Vec<T>,
+    first_seen: i64,
+}
+
+/// Datagram whose fragments have all arrived
+pub struct Reassembled<T> {
+    /// First fragment of the datagram, carrying the headers below the reassembled payload
+    pub fragment: Fragment,
+    pub payload: Vec<u8>,
+    /// Records of all fragments of the datagram, in arrival order
+    pub records: Vec<T>,
+}
+
+impl<T> Reassembler<T> {
+    /// Creates a reassembler that gives up on datagrams `timeout` seconds after their first
+    /// fragment
+    pub fn new(timeout: u32, overlap_policy: OverlapPolicy) -> Self {
+        Self {
+            datagrams: HashMap::new(),
+            first_seen: BTreeMap::new(),
+            timeout,
+            overlap_policy,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.datagrams.is_empty()
+    }
+
+    /// Adds a fragment that arrived at `time` seconds, returning its datagram if it is now
+    /// complete
+    pub fn insert(&mut self, fragment: Fragment, record: T, time: i64) -> Option<Reassembled<T>> {
+        let key = (
+            fragment.source,
+            fragment.destination,
+            fragment.protocol,
+            fragment.identification,
+        );
+
+        let datagram = self.datagrams.entry(key).or_insert_with(|| {
+            self.first_seen.entry(time).or_default().push(key);
+
+            Datagram {
+                fragments: Vec::new(),
+                records: Vec::new(),
+                first_seen: time,
+            }
+        });
+        datagram.fragments.push(fragment);
+        datagram.records.push(record);
+
+        let total_length = datagram.total_length()?;
+        let datagram = self.datagrams.remove(&key)?;
+        if let Some(keys) = self.first_seen.get_mut(&datagram.first_seen) {
+            keys.retain(|k| *k != key);
+            if keys.is_empty() {
+                self.first_seen.remove(&datagram.first_seen);
+            }
+        }
+
+        Some(datagram.reassemble(total_length, self.overlap_policy))
+    }
+
+    /// Removes the datagrams that are still incomplete `timeout` seconds after their first
+    /// fragment, returning the records of their fragments
+    pub fn expire(&mut self, time: i64) -> Vec<T> {
+        let timeout = i64::from(self.timeout);
+
+        let mut records = Vec::new();
+        while let Some(entry) = self.first_seen.first_entry() {
+            if time.saturating_sub(*entry.key()) <= timeout {
+                break;
+            }
+
+            for key in entry.remove() {
+                if let Some(datagram) = self.datagrams.remove(&key) {
+                    records.extend(datagram.records);
+                }
+            }
+        }
+
+        records
+    }
+
+    /// Removes all incomplete datagrams, returning the records of their fragments
+    pub fn drain(&mut self) -> Vec<T> {
+        self.first_seen.clear();
+        self.datagrams
+            .drain()
+            .flat_map(|(_, d)| d.records)
+            .collect()
+    }
+}
+
+impl<T> Datagram<T> {
+    /// Length of the reassembled payload, if the fragments cover all of it
+    fn total_length(&self) -> Option<usize> {
+        let total_length = self
+            .fragments
+            .iter()
+            .filter(|f| !f.more_fragments)
+            .map(|f| f.offset + f.payload.len())
+            .min()?;
+
+        let mut ranges = self
+            .fragments
+            .iter()
+            .map(|f| (f.offset, f.offset + f.payload.len()))
+            .collect::<Vec<(usize, usize)>>();
+        ranges.sort_unstable();
+
+        let mut covered = 0;
+        for (start, end) in ranges {
+            if start > covered {
+                break;
+            }
+            covered = covered.max(end);
+        }
+
+        (covered >= total_length).then_some(total_length)
+    }
+
+    fn reassemble(self, total_length: usize, overlap_policy: OverlapPolicy) -> Reassembled<T> {
+        let mut payload = vec![0u8; total_length];
+
+        // Fragments written later overwrite overlapping data of the ones written before them
+        let mut fragments = self.fragments;
+        if overlap_policy == OverlapPolicy::First {
+            fragments.reverse();
+        }
+        for fragment in &fragments {
+            let start = fragment.offset.min(total_length);
+            let end = (fragment.offset + fragment.payload.len()).min(total_length);
+            payload[start..end].copy_from_slice(&fragment.payload[..end - start]);
+        }
+
+        let first_fragment_index = match overlap_policy {
+            OverlapPolicy::First => fragments.iter().rposition(|f| f.offset == 0),
+            OverlapPolicy::Last => fragments.iter().position(|f| f.offset == 0),
+        }
+        .expect("complete datagrams have a fragment at offset zero");
+        let fragment = fragments.swap_remove(first_fragment_index);
+
+        Reassembled {
+            fragment,
+            payload,
+            records: self.records,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::packet_dissection::{Fragment, LinkLayer};
+    use crate::packet_layer::{NetworkLayerType, TransportLayerType};
+    use crate::reassembly::{OverlapPolicy, Reassembler};
+
+    fn fragment(
+        identification: u32,
+        offset: usize,
+        more_fragments: bool,
+        payload: &[u8],
+    ) -> Fragment {
+        Fragment {
+            link_layer: LinkLayer::Raw(NetworkLayerType::IPv4),
+            tunnel: None,
+            source: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            destination: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            protocol: TransportLayerType::Udp,
+            identification,
+            offset,
+            more_fragments,
+            payload: payload.to_vec(),
+        }
+    }
+
+    #[test]
+    fn reassembling_out_of_order_fragments_succeeds() {
+        let mut reassembler = Reassembler::new(30, OverlapPolicy::First);
+
+        assert!(reassembler
+            .insert(fragment(1, 8, false, &[2; 4]), 1, 0)
+            .is_none());
+        assert!(reassembler
+            .insert(fragment(2, 0, true, &[3; 8]), 2, 0)
+            .is_none());
+        let reassembled = reassembler
+            .insert(fragment(1, 0, true, &[1; 8]), 3, 0)
+            .unwrap();
+
+        assert_eq!(reassembled.payload, [1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2]);
+        assert_eq!(reassembled.records, [1, 3]);
+        assert_eq!(reassembled.fragment.offset, 0);
+        assert_eq!(reassembler.drain(), [2]);
+    }
+
+    #[test]
+    fn reassembling_overlapping_fragments_follows_policy() {
+        for (overlap_policy, expected) in [
+            (OverlapPolicy::First, [1, 1, 1, 1, 1, 1, 1, 1, 3, 3]),
+            (OverlapPolicy::Last, [1, 1, 1, 1, 2, 2, 2, 2, 3, 3]),
+        ] {
+            let mut reassembler = Reassembler::new(30, overlap_policy);
+
+            reassembler.insert(fragment(1, 0, true, &[1; 8]), 1, 0);
+            reassembler.insert(fragment(1, 4, true, &[2; 4]), 2, 0);
+            let reassembled = reassembler
+                .insert(fragment(1, 8, false, &[3; 2]), 3, 0)
+                .unwrap();
+
+            assert_eq!(reassembled.payload, expected);
+        }
+    }
+
+    #[test]
+    fn expiring_incomplete_datagrams_succeeds() {
+        let mut reassembler = Reassembler::new(30, OverlapPolicy::First);
+
+        reassembler.insert(fragment(1, 0, true, &[1; 8]), 1, 0);
+        reassembler.insert(fragment(2, 0, true, &[1; 8]), 2, 20);
+
+        assert!(reassembler.expire(30).is_empty());
+        assert_eq!(reassembler.expire(31), [1]);
+        assert!(reassembler
+            .insert(fragment(1, 8, false, &[2; 4]), 3, 31)
+            .is_none());
+        assert!(!reassembler.is_empty());
+
+        // Complete datagrams are no longer waiting to expire
+        reassembler.insert(fragment(4, 0, true, &[1; 8]), 4, 20);
+        assert!(reassembler
+            .insert(fragment(4, 8, false, &[2; 4]), 5, 21)
+            .is_some());
+        assert_eq!(reassembler.expire(51), [2]);
+        assert_eq!(reassembler.expire(62), [3]);
+        assert!(reassembler.is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use crate::error::Error;
+
+/// File that data is moved to when it takes up too much memory
+///
+/// Temporary spill files are removed when dropped. Existing files, such as flow indexes, can be
+/// opened to read data back from them.
+pub struct SpillFile {
+    path: PathBuf,
+    file: Mutex<File>,
+    is_temporary: bool,
+}
+
+impl SpillFile {
+    pub fn create() -> Result<Self, Error> {
+        static SPILL_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "pcap_flow_splitter_{}_{}.spill",
+            std::process::id(),
+            SPILL_FILE_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(Error::SpillFileCreate)?;
+
+        let spill_file = Self {
+            path,
+            file: Mutex::new(file),
+            is_temporary: true,
+        };
+
+        Ok(spill_file)
+    }
+
+    /// Opens the existing file at `path` for reading, leaving it in place when dropped
+    pub fn open(path: PathBuf) -> Result<Self, Error> {
+        let file = File::open(&path).map_err(Error::SpillFileRead)?;
+
+        let spill_file = Self {
+            path,
+            file: Mutex::new(file),
+            is_temporary: false,
+        };
+
+        Ok(spill_file)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `buffer` to the end of the file, returning the position it was written at
+    pub fn append(&self, buffer: &[u8]) -> Result<u64, Error> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let position = file.seek(SeekFrom::End(0)).map_err(Error::SpillFileWrite)?;
+        file.write_all(buffer).map_err(Error::SpillFileWrite)?;
+
+        Ok(position)
+    }
+
+    pub fn read(&self, position: u64, length: usize) -> Result<Vec<u8>, Error> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut buffer = vec![0u8; length];
+        file.seek(SeekFrom::Start(position))
+            .map_err(Error::SpillFileRead)?;
+        file.read_exact(&mut buffer).map_err(Error::SpillFileRead)?;
+
+        Ok(buffer)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if self.is_temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spill::SpillFile;
+
+    #[test]
+    fn reading_appended_data_succeeds() {
+        let spill_file = SpillFile::create().unwrap();
+
+        let first = spill_file.append(&[1, 2, 3]).unwrap();
+        let second = spill_file.append(&[4, 5]).unwrap();
+
+        assert_eq!(spill_file.read(second, 2).unwrap(), [4, 5]);
+        assert_eq!(spill_file.read(first, 3).unwrap(), [1, 2, 3]);
+
+        let path = spill_file.path.clone();
+        drop(spill_file);
+        assert!(!path.exists());
+    }
+}
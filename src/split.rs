@@ -0,0 +1,172 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::flow::{extract_flows, Flow, FlowOptions, Flows};
+use crate::merge::expand_paths;
+use crate::packet_layer::TransportLayerType;
+use crate::stream::TcpStream;
+
+pub const DEFAULT_FILE_NAME_TEMPLATE: &str =
+    "{protocol}_{src_addr}_{src_port}_{dst_addr}_{dst_port}_{timestamp}.{extension}";
+
+pub const DEFAULT_STREAM_FILE_NAME_TEMPLATE: &str =
+    "{src_addr}_{src_port}_{dst_addr}_{dst_port}_{timestamp}.{direction}.{extension}";
+
+/// Writes every flow in the capture files at `file_paths` into its own capture file inside
+/// `output_directory`, naming each file after `template`
+///
+/// Several capture files are merged into one capture before being split.
+pub fn run(
+    file_paths: &[OsString],
+    output_directory: impl AsRef<Path>,
+    template: &str,
+    options: FlowOptions,
+) -> Result<(), Error> {
+    let flows = load_flows(file_paths, options)?;
+    let mut source = flows.open_source()?;
+
+    std::fs::create_dir_all(&output_directory).map_err(Error::CreateOutputDirectory)?;
+
+    for (index, flow) in flows.values().enumerate() {
+        let file_name = render_file_name(template, index, flow, flows.file_extension());
+        let path = output_directory.as_ref().join(file_name);
+
+        flow.export(&mut source, flows.capture_header(), path)?;
+    }
+
+    for (reason, packet_count) in flows.unclassified().reasons() {
+        eprintln!("warning: packets not split into flows: {packet_count} ({reason})");
+    }
+
+    Ok(())
+}
+
+/// Writes the data each endpoint of every TCP flow in the capture files at `file_paths` sent into
+/// its own file inside `output_directory`, naming each file after `template`
+///
+/// Besides the placeholders of [`render_file_name`], `{direction}` is replaced with `client` or
+/// `server`, and `{extension}` with `bin`. Data that was not captured is left out.
+pub fn dump_streams(
+    file_paths: &[OsString],
+    output_directory: impl AsRef<Path>,
+    template: &str,
+    options: FlowOptions,
+) -> Result<(), Error> {
+    let flows = load_flows(file_paths, options)?;
+    let mut source = flows.open_source()?;
+
+    std::fs::create_dir_all(&output_directory).map_err(Error::CreateOutputDirectory)?;
+
+    for (index, flow) in flows.values().enumerate() {
+        if flow.protocol != TransportLayerType::Tcp {
+            continue;
+        }
+
+        let stream = TcpStream::reassemble(flow, &mut source)?;
+        for (from_initiator, direction) in [(true, "client"), (false, "server")] {
+            let file_name =
+                render_file_name(template, index, flow, "bin").replace("{direction}", direction);
+            let path = output_directory.as_ref().join(file_name);
+
+            std::fs::write(path, stream.bytes(from_initiator)).map_err(Error::WriteStream)?;
+
+            let missing_bytes = stream.missing_bytes(from_initiator);
+            if missing_bytes > 0 {
+                eprintln!(
+                    "warning: {missing_bytes} bytes missing from the {direction} stream of {}",
+                    flow.five_tuple
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Extracts the flows of the capture files at `file_paths`, merging them if there are several
+fn load_flows(file_paths: &[OsString], options: FlowOptions) -> Result<Flows, Error> {
+    let flows = match expand_paths(file_paths)?.as_slice() {
+        [file_path] => extract_flows(file_path, options)?,
+        file_paths => {
+            let mut flows = Flows::merge(file_paths, options)?;
+            flows.wait()?;
+
+            flows
+        }
+    };
+    if let Some(packet_count) = flows.truncated_after() {
+        eprintln!("warning: capture truncated after packet {packet_count}");
+    }
+
+    Ok(flows)
+}
+
+/// Replaces the placeholders in `template` with the properties of `flow`
+///
+/// Supported placeholders are `{index}`, `{protocol}`, `{src_addr}`, `{src_port}`, `{dst_addr}`,
+/// `{dst_port}`, `{timestamp}` and `{extension}`, where source is the initiator of the flow,
+/// timestamp is the capture time of its first packet and extension is `pcap` or `pcapng`
+/// depending on the format of the source capture. Ports of ICMP flows are zero.
+fn render_file_name(template: &str, index: usize, flow: &Flow, extension: &str) -> String {
+    template
+        .replace("{index}", &index.to_string())
+        .replace("{protocol}", &flow.protocol.to_string())
+        .replace("{src_addr}", &flow.initiator.ip().to_string())
+        .replace("{src_port}", &flow.initiator.port().to_string())
+        .replace("{dst_addr}", &flow.respondent.ip().to_string())
+        .replace("{dst_port}", &flow.respondent.port().to_string())
+        .replace("{timestamp}", &flow.first_timestamp.to_string())
+        .replace("{extension}", extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::five_tuple::FiveTuple;
+    use crate::flow::Flow;
+    use crate::packet_dissection::{
+        LinkLayer, NetworkLayer, PacketDissection, TcpHeader, TransportLayer,
+    };
+    use crate::packet_header::{PacketHeader, PacketLength, Timestamp};
+    use crate::packet_layer::{ApplicationLayerType, NetworkLayerType, TransportLayerType};
+    use crate::split::{render_file_name, DEFAULT_FILE_NAME_TEMPLATE};
+
+    #[test]
+    fn rendering_file_name_succeeds() {
+        let dissection = PacketDissection {
+            link_layer: LinkLayer::Ethernet(NetworkLayerType::IPv4, Vec::new(), Vec::new()),
+            tunnel: None,
+            network_layer: NetworkLayer::IPv4(
+                0x0A_00_00_01,
+                0x0A_00_00_02,
+                TransportLayerType::Tcp,
+            ),
+            transport_layer: TransportLayer::Tcp(
+                1234,
+                80,
+                ApplicationLayerType::OctetArray,
+                TcpHeader::default(),
+            ),
+            payload_length: 0,
+            attributed: false,
+        };
+        let header = PacketHeader {
+            timestamp: Timestamp::new(1_558_968_019, 69_732_000),
+            captured_length: PacketLength(52),
+            actual_length: PacketLength(52),
+        };
+        let five_tuple = FiveTuple::from_packet_dissection(&dissection, false);
+        let flow = Flow::new(five_tuple, &dissection, &header, 24);
+
+        let file_name = render_file_name(DEFAULT_FILE_NAME_TEMPLATE, 3, &flow, "pcap");
+
+        assert_eq!(
+            file_name,
+            "TCP_10.0.0.1_1234_10.0.0.2_80_1558968019.069732000.pcap"
+        );
+        assert_eq!(
+            render_file_name("{index}.{extension}", 3, &flow, "pcapng"),
+            "3.pcapng"
+        );
+    }
+}
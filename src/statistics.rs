@@ -0,0 +1,183 @@
+use crate::error::Error;
+use crate::flow_index::{ReadOnlyIndexCursor, WriteOnlyIndexCursor};
+
+/// Statistics of the packets one endpoint of a flow sent
+///
+/// Sizes are in bytes and include all headers, while payload sizes only count what follows the
+/// transport layer header. Inter-arrival times are in nanoseconds, between consecutive packets
+/// sent by the same endpoint.
+#[derive(Clone, Default, Debug)]
+pub struct DirectionStatistics {
+    pub packet_count: usize,
+    pub total_size: usize,
+    pub payload_size: usize,
+    pub minimum_size: usize,
+    pub maximum_size: usize,
+    pub minimum_interarrival_time: u64,
+    pub maximum_interarrival_time: u64,
+    sizes: Moments,
+    interarrival_times: Moments,
+    /// Offset from the first packet of the flow of the latest packet sent, in nanoseconds
+    previous_timestamp: i64,
+}
+
+impl DirectionStatistics {
+    /// Adds a packet of `size` bytes with `payload_size` bytes of payload, captured `timestamp`
+    /// nanoseconds after the first packet of the flow
+    ///
+    /// Like the statistics of the whole flow, a packet captured before the latest packet counts
+    /// as arriving at the same time as it.
+    pub fn update(&mut self, size: usize, payload_size: usize, timestamp: i64) {
+        if self.packet_count == 0 {
+            self.minimum_size = size;
+            self.previous_timestamp = timestamp;
+        } else {
+            let interarrival_time = timestamp.saturating_sub(self.previous_timestamp).max(0) as u64;
+            if self.interarrival_times.count == 0 {
+                self.minimum_interarrival_time = interarrival_time;
+            }
+            self.minimum_interarrival_time = self.minimum_interarrival_time.min(interarrival_time);
+            self.maximum_interarrival_time = self.maximum_interarrival_time.max(interarrival_time);
+            self.interarrival_times.push(interarrival_time as f64);
+            self.previous_timestamp = self.previous_timestamp.max(timestamp);
+        }
+
+        self.packet_count += 1;
+        self.total_size += size;
+        self.payload_size += payload_size;
+        self.minimum_size = self.minimum_size.min(size);
+        self.maximum_size = self.maximum_size.max(size);
+        self.sizes.push(size as f64);
+    }
+
+    /// Mean size of the packets, `None` if the endpoint sent none
+    pub fn average_size(&self) -> Option<f64> {
+        self.sizes.mean()
+    }
+
+    pub fn size_standard_deviation(&self) -> Option<f64> {
+        self.sizes.standard_deviation()
+    }
+
+    /// Mean inter-arrival time, `None` if the endpoint sent fewer than two packets
+    pub fn average_interarrival_time(&self) -> Option<u64> {
+        self.interarrival_times
+            .mean()
+            .map(|mean| mean.round() as u64)
+    }
+
+    pub fn interarrival_time_standard_deviation(&self) -> Option<u64> {
+        self.interarrival_times
+            .standard_deviation()
+            .map(|deviation| deviation.round() as u64)
+    }
+
+    /// Reads statistics written by [`Self::write_index`] from a flow index
+    pub fn read_index(cursor: &mut ReadOnlyIndexCursor) -> Result<Self, Error> {
+        let statistics = Self {
+            packet_count: cursor.get_usize()?,
+            total_size: cursor.get_usize()?,
+            payload_size: cursor.get_usize()?,
+            minimum_size: cursor.get_usize()?,
+            maximum_size: cursor.get_usize()?,
+            minimum_interarrival_time: cursor.get_u64()?,
+            maximum_interarrival_time: cursor.get_u64()?,
+            sizes: Moments::read_index(cursor)?,
+            interarrival_times: Moments::read_index(cursor)?,
+            previous_timestamp: cursor.get_i64()?,
+        };
+
+        Ok(statistics)
+    }
+
+    pub fn write_index(&self, cursor: &mut WriteOnlyIndexCursor) {
+        cursor.put_usize(self.packet_count);
+        cursor.put_usize(self.total_size);
+        cursor.put_usize(self.payload_size);
+        cursor.put_usize(self.minimum_size);
+        cursor.put_usize(self.maximum_size);
+        cursor.put_u64(self.minimum_interarrival_time);
+        cursor.put_u64(self.maximum_interarrival_time);
+        self.sizes.write_index(cursor);
+        self.interarrival_times.write_index(cursor);
+        cursor.put_i64(self.previous_timestamp);
+    }
+}
+
+/// Running mean and variance of a series of values, kept with Welford's algorithm so that
+/// neither overflows nor loses precision on long flows
+#[derive(Copy, Clone, Default, Debug)]
+struct Moments {
+    count: u64,
+    mean: f64,
+    /// Sum of the squared deviations of the values from their mean
+    squared_deviations: f64,
+}
+
+impl Moments {
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        let deviation = value - self.mean;
+        self.mean += deviation / self.count as f64;
+        self.squared_deviations += deviation * (value - self.mean);
+    }
+
+    fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    /// Population standard deviation of the values
+    fn standard_deviation(&self) -> Option<f64> {
+        (self.count > 0).then(|| (self.squared_deviations / self.count as f64).sqrt())
+    }
+
+    fn read_index(cursor: &mut ReadOnlyIndexCursor) -> Result<Self, Error> {
+        let moments = Self {
+            count: cursor.get_u64()?,
+            mean: f64::from_bits(cursor.get_u64()?),
+            squared_deviations: f64::from_bits(cursor.get_u64()?),
+        };
+
+        Ok(moments)
+    }
+
+    fn write_index(&self, cursor: &mut WriteOnlyIndexCursor) {
+        cursor.put_u64(self.count);
+        cursor.put_u64(self.mean.to_bits());
+        cursor.put_u64(self.squared_deviations.to_bits());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::statistics::DirectionStatistics;
+
+    #[test]
+    fn computing_direction_statistics_succeeds() {
+        let mut statistics = DirectionStatistics::default();
+        assert_eq!(statistics.average_size(), None);
+
+        statistics.update(100, 40, 1_000);
+        assert_eq!(statistics.average_interarrival_time(), None);
+
+        statistics.update(60, 0, 3_000);
+        // Captured before the previous packet, so it counts as arriving at the same time
+        statistics.update(200, 140, 2_000);
+        statistics.update(40, 0, 9_000);
+
+        assert_eq!(statistics.packet_count, 4);
+        assert_eq!(statistics.total_size, 400);
+        assert_eq!(statistics.payload_size, 180);
+        assert_eq!(statistics.minimum_size, 40);
+        assert_eq!(statistics.maximum_size, 200);
+        assert_eq!(statistics.average_size(), Some(100.0));
+        assert_eq!(statistics.size_standard_deviation(), Some(3_800f64.sqrt()));
+        assert_eq!(statistics.minimum_interarrival_time, 0);
+        assert_eq!(statistics.maximum_interarrival_time, 6_000);
+        assert_eq!(statistics.average_interarrival_time(), Some(2_667));
+        assert_eq!(
+            statistics.interarrival_time_standard_deviation(),
+            Some(2_494)
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+
+use crate::capture::ReadOnlyCapture;
+use crate::error::Error;
+use crate::flow::Flow;
+use crate::packet_dissection::{PacketDissection, TcpFlags, TcpHeader};
+
+/// Data of one direction of a TCP connection, in stream order
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum StreamData {
+    Bytes(Vec<u8>),
+    /// Bytes of the stream that were not captured
+    Gap(u64),
+}
+
+/// Data one endpoint of a TCP connection sent without the other sending any in between
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct StreamChunk {
+    pub from_initiator: bool,
+    pub data: StreamData,
+}
+
+/// Both directions of a TCP connection reassembled from the segments of its flow
+///
+/// Chunks are in the order their data became contiguous, which is the order the endpoints took
+/// turns in as far as the capture tells. Data missing from a direction when the flow ends is
+/// left as a gap, followed by whatever data arrived after it.
+#[derive(Clone, Default, Debug)]
+pub struct TcpStream {
+    pub chunks: Vec<StreamChunk>,
+    /// Bytes received again after they were already part of a stream, in either direction
+    pub retransmitted_bytes: u64,
+}
+
+impl TcpStream {
+    /// Reassembles the TCP segments of `flow` read from `source`, the capture it was extracted
+    /// from
+    pub fn reassemble(flow: &Flow, source: &mut ReadOnlyCapture) -> Result<Self, Error> {
+        // Packets of flows with a tunnel were dissected from inside that tunnel
+        let decapsulate_tunnels = flow.tunnel.is_some();
+
+        let mut segments = Vec::new();
+        for packet in flow.packets()?.iter() {
+            source.record_at(packet.offset)?;
+            let record = source.packet();
+            if let Some((header, payload)) = PacketDissection::tcp_segment(
+                &record,
+                source.link_layer_type(),
+                decapsulate_tunnels,
+            )? {
+                segments.push((
+                    packet.from_initiator_to_respondent,
+                    header,
+                    payload.to_vec(),
+                ));
+            }
+        }
+
+        let mut directions = [false, true].map(|from_initiator| {
+            StreamReassembler::new(
+                segments
+                    .iter()
+                    .filter(|(sender, ..)| *sender == from_initiator)
+                    .map(|(_, header, payload)| (*header, payload.as_slice())),
+            )
+        });
+
+        let mut stream = Self::default();
+        for (from_initiator, header, payload) in &segments {
+            let direction = &mut directions[usize::from(*from_initiator)];
+            if let Some(data) = direction.push(*header, payload) {
+                stream.push(*from_initiator, data);
+            }
+        }
+
+        for (from_initiator, direction) in [false, true].into_iter().zip(directions) {
+            let (remaining_data, retransmitted_bytes) = direction.finish();
+            stream.retransmitted_bytes += retransmitted_bytes;
+            for data in remaining_data {
+                stream.push(from_initiator, data);
+            }
+        }
+
+        Ok(stream)
+    }
+
+    /// Appends data to the stream, merging it into the last chunk if it has the same direction
+    fn push(&mut self, from_initiator: bool, data: StreamData) {
+        if let (
+            Some(StreamChunk {
+                from_initiator: last_from_initiator,
+                data: StreamData::Bytes(last_bytes),
+            }),
+            StreamData::Bytes(bytes),
+        ) = (self.chunks.last_mut(), &data)
+        {
+            if *last_from_initiator == from_initiator {
+                last_bytes.extend_from_slice(bytes);
+                return;
+            }
+        }
+
+        self.chunks.push(StreamChunk {
+            from_initiator,
+            data,
+        });
+    }
+
+    /// Bytes sent by the initiator, or by the respondent, with the gaps left out
+    pub fn bytes(&self, from_initiator: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for chunk in self.direction(from_initiator) {
+            if let StreamData::Bytes(chunk_bytes) = &chunk.data {
+                bytes.extend_from_slice(chunk_bytes);
+            }
+        }
+
+        bytes
+    }
+
+    /// Number of bytes sent by the initiator, or by the respondent, that were not captured
+    pub fn missing_bytes(&self, from_initiator: bool) -> u64 {
+        self.direction(from_initiator)
+            .map(|chunk| match chunk.data {
+                StreamData::Bytes(_) => 0,
+                StreamData::Gap(length) => length,
+            })
+            .sum()
+    }
+
+    fn direction(&self, from_initiator: bool) -> impl Iterator<Item = &StreamChunk> {
+        self.chunks
+            .iter()
+            .filter(move |chunk| chunk.from_initiator == from_initiator)
+    }
+}
+
+/// Puts the segments of one direction of a TCP connection in sequence number order
+///
+/// Offsets in the stream are counted from the byte after the SYN, or from the lowest sequence
+/// number carrying data if the SYN was not captured, and can go past the 4 GiB the sequence
+/// numbers wrap around at. Where segments overlap, the data that arrived first is kept.
+#[derive(Default)]
+struct StreamReassembler {
+    /// Sequence number of the first byte of the stream, once known
+    initial_sequence_number: Option<u32>,
+    /// Offset of the first byte of the stream that has not been received
+    next_offset: u64,
+    /// Segments received ahead of the next offset, by their offset
+    pending: BTreeMap<u64, Vec<u8>>,
+    retransmitted_bytes: u64,
+}
+
+impl StreamReassembler {
+    /// Starts the stream of a direction with `segments`, all the segments it sent in capture
+    /// order, so that data arriving before data sent earlier is not taken for a retransmission
+    fn new<'a>(segments: impl IntoIterator<Item = (TcpHeader, &'a [u8])>) -> Self {
+        let mut first_sequence_number = None;
+        let mut lowest_distance = 0;
+        for (header, payload) in segments {
+            if header.flags.contains(TcpFlags::SYN) {
+                first_sequence_number = Some(header.sequence_number.wrapping_add(1));
+                lowest_distance = 0;
+                break;
+            }
+            if payload.is_empty() {
+                continue;
+            }
+
+            let first_sequence_number =
+                *first_sequence_number.get_or_insert(header.sequence_number);
+            let distance = header.sequence_number.wrapping_sub(first_sequence_number) as i32;
+            lowest_distance = lowest_distance.min(distance);
+        }
+
+        Self {
+            initial_sequence_number: first_sequence_number
+                .map(|sequence_number| sequence_number.wrapping_add_signed(lowest_distance)),
+            ..Self::default()
+        }
+    }
+
+    /// Adds a segment, returning the data it made contiguous with the stream so far
+    fn push(&mut self, header: TcpHeader, payload: &[u8]) -> Option<StreamData> {
+        // Data sent with a SYN, as with TCP Fast Open, follows the sequence number of the SYN
+        let mut sequence_number = header.sequence_number;
+        if header.flags.contains(TcpFlags::SYN) {
+            sequence_number = sequence_number.wrapping_add(1);
+            self.initial_sequence_number.get_or_insert(sequence_number);
+        }
+        if payload.is_empty() {
+            return None;
+        }
+
+        let initial_sequence_number = *self.initial_sequence_number.get_or_insert(sequence_number);
+
+        // The offset closest to the next offset with the sequence number of the segment
+        let relative_sequence_number = sequence_number.wrapping_sub(initial_sequence_number);
+        let distance = relative_sequence_number.wrapping_sub(self.next_offset as u32) as i32;
+        let Some(offset) = self.next_offset.checked_add_signed(i64::from(distance)) else {
+            // Data from before the start of the stream
+            self.retransmitted_bytes += payload.len() as u64;
+            return None;
+        };
+
+        self.insert(offset, payload);
+
+        let mut data = Vec::new();
+        while let Some(entry) = self.pending.first_entry() {
+            if *entry.key() > self.next_offset {
+                break;
+            }
+
+            let (offset, bytes) = entry.remove_entry();
+            let skipped = (self.next_offset - offset) as usize;
+            if skipped >= bytes.len() {
+                self.retransmitted_bytes += bytes.len() as u64;
+                continue;
+            }
+
+            self.retransmitted_bytes += skipped as u64;
+            data.extend_from_slice(&bytes[skipped..]);
+            self.next_offset = offset + bytes.len() as u64;
+        }
+
+        (!data.is_empty()).then_some(StreamData::Bytes(data))
+    }
+
+    /// Keeps a segment at `offset` until the data before it arrives, extending a segment at the
+    /// same offset if it is longer
+    fn insert(&mut self, offset: u64, payload: &[u8]) {
+        let bytes = self.pending.entry(offset).or_default();
+        let overlap = bytes.len().min(payload.len());
+
+        self.retransmitted_bytes += overlap as u64;
+        bytes.extend_from_slice(&payload[overlap..]);
+    }
+
+    /// Returns the segments that never became contiguous with the stream, with the gaps before
+    /// them, and the number of retransmitted bytes of the stream
+    fn finish(self) -> (Vec<StreamData>, u64) {
+        let mut retransmitted_bytes = self.retransmitted_bytes;
+        let mut next_offset = self.next_offset;
+
+        let mut data = Vec::new();
+        for (offset, bytes) in self.pending {
+            let skipped = next_offset.saturating_sub(offset);
+            if skipped >= bytes.len() as u64 {
+                retransmitted_bytes += bytes.len() as u64;
+                continue;
+            }
+            if offset > next_offset {
+                data.push(StreamData::Gap(offset - next_offset));
+            }
+
+            retransmitted_bytes += skipped;
+            data.push(StreamData::Bytes(bytes[skipped as usize..].to_vec()));
+            next_offset = offset + bytes.len() as u64;
+        }
+
+        (data, retransmitted_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packet_dissection::{TcpFlags, TcpHeader};
+    use crate::stream::{StreamData, StreamReassembler};
+
+    #[test]
+    fn reassembling_out_of_order_and_retransmitted_segments_succeeds() {
+        let mut reassembler = StreamReassembler::default();
+
+        assert_eq!(
+            reassembler.push(segment(u32::MAX - 1, TcpFlags::SYN), b""),
+            None
+        );
+        assert_eq!(
+            reassembler.push(segment(u32::MAX, TcpFlags::ACK), b"ab"),
+            bytes(b"ab")
+        );
+        // Out of order after the sequence numbers wrapped around
+        assert_eq!(reassembler.push(segment(5, TcpFlags::ACK), b"gh"), None);
+        assert_eq!(
+            reassembler.push(segment(1, TcpFlags::ACK), b"cdef"),
+            bytes(b"cdefgh")
+        );
+        // Retransmissions, partly and wholly overlapping data already received
+        assert_eq!(
+            reassembler.push(segment(5, TcpFlags::ACK), b"XXij"),
+            bytes(b"ij")
+        );
+        assert_eq!(
+            reassembler.push(segment(u32::MAX, TcpFlags::ACK), b"ab"),
+            None
+        );
+        // Data after a gap that is never filled, received twice
+        assert_eq!(reassembler.push(segment(11, TcpFlags::ACK), b"mn"), None);
+        assert_eq!(reassembler.push(segment(11, TcpFlags::ACK), b"XXop"), None);
+
+        assert_eq!(
+            reassembler.finish(),
+            (
+                vec![StreamData::Gap(2), StreamData::Bytes(b"mnop".to_vec())],
+                6
+            )
+        );
+    }
+
+    #[test]
+    fn reassembling_streams_without_syn_and_with_syn_data_succeeds() {
+        let segments = [
+            (segment(105, TcpFlags::ACK), &b"fg"[..]),
+            (segment(100, TcpFlags::ACK), b"abc"),
+            (segment(103, TcpFlags::ACK), b"de"),
+        ];
+        let mut reassembler = StreamReassembler::new(segments);
+        // The stream starts at the lowest sequence number, not at the first segment captured
+        let contiguous_data = [None, bytes(b"abc"), bytes(b"defg")];
+        for ((header, payload), data) in segments.into_iter().zip(contiguous_data) {
+            assert_eq!(reassembler.push(header, payload), data);
+        }
+        assert_eq!(reassembler.finish(), (Vec::new(), 0));
+
+        // Data sent with the SYN, followed by data acknowledging the SYN-ACK
+        let segments = [
+            (segment(99, TcpFlags::SYN), &b"ab"[..]),
+            (segment(102, TcpFlags::ACK), b"cd"),
+        ];
+        let mut reassembler = StreamReassembler::new(segments);
+        for ((header, payload), data) in segments.into_iter().zip([bytes(b"ab"), bytes(b"cd")]) {
+            assert_eq!(reassembler.push(header, payload), data);
+        }
+    }
+
+    fn segment(sequence_number: u32, flags: TcpFlags) -> TcpHeader {
+        TcpHeader {
+            sequence_number,
+            flags,
+            ..TcpHeader::default()
+        }
+    }
+
+    fn bytes(bytes: &[u8]) -> Option<StreamData> {
+        Some(StreamData::Bytes(bytes.to_vec()))
+    }
+}
@@ -0,0 +1,547 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+use crate::error::Error;
+use crate::flow_index::{ReadOnlyIndexCursor, WriteOnlyIndexCursor};
+use crate::packet_dissection::{TcpFlags, TcpHeader};
+
+/// How a TCP connection ended, as far as its packets in the capture tell
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub enum ConnectionEnd {
+    /// Neither endpoint closed the connection before the capture ended
+    #[default]
+    Open,
+    /// Only one endpoint sent a FIN
+    HalfClosed,
+    /// Both endpoints sent a FIN
+    Closed,
+    /// An endpoint sent a RST
+    Reset,
+    /// The flow ended after being idle for longer than the idle timeout
+    TimedOut,
+    /// A new connection with the same five tuple started while this one was open
+    Superseded,
+}
+
+impl Display for ConnectionEnd {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Open => "open",
+                Self::HalfClosed => "half-closed",
+                Self::Closed => "closed",
+                Self::Reset => "reset",
+                Self::TimedOut => "timed out",
+                Self::Superseded => "superseded",
+            }
+        )
+    }
+}
+
+/// Lifecycle of a TCP connection, followed through the flags of its segments
+#[derive(Copy, Clone, Default, Debug)]
+pub struct TcpConnection {
+    /// Sequence number of the SYN that opened the connection, if it was captured
+    syn_sequence_number: Option<u32>,
+    fin_from_initiator: bool,
+    fin_from_respondent: bool,
+    end: ConnectionEnd,
+}
+
+impl TcpConnection {
+    /// Follows a segment of the connection sent by the initiator if `from_initiator` is set
+    pub fn update(&mut self, header: TcpHeader, from_initiator: bool) {
+        if header.flags.is_syn() && self.syn_sequence_number.is_none() {
+            self.syn_sequence_number = Some(header.sequence_number);
+        }
+
+        if header.flags.contains(TcpFlags::FIN) {
+            if from_initiator {
+                self.fin_from_initiator = true;
+            } else {
+                self.fin_from_respondent = true;
+            }
+        }
+
+        self.end = match self.end {
+            ConnectionEnd::Reset => ConnectionEnd::Reset,
+            _ if header.flags.contains(TcpFlags::RST) => ConnectionEnd::Reset,
+            _ if self.fin_from_initiator && self.fin_from_respondent => ConnectionEnd::Closed,
+            _ if self.fin_from_initiator || self.fin_from_respondent => ConnectionEnd::HalfClosed,
+            end => end,
+        };
+    }
+
+    /// Whether a segment arriving `idle_seconds` after the latest one of the connection opens a
+    /// new connection with the same five tuple
+    ///
+    /// That is a SYN other than a retransmission of the opening one, arriving after the
+    /// connection was closed or reset, or after it was idle for at least `reuse_timeout` seconds.
+    pub fn is_reopened_by(&self, header: TcpHeader, idle_seconds: i64, reuse_timeout: u32) -> bool {
+        let is_closed = matches!(
+            self.end,
+            ConnectionEnd::HalfClosed | ConnectionEnd::Closed | ConnectionEnd::Reset
+        );
+
+        header.flags.is_syn()
+            && self.syn_sequence_number != Some(header.sequence_number)
+            && (is_closed || idle_seconds >= i64::from(reuse_timeout))
+    }
+
+    /// Records that the flow of the connection ended for `end`, unless the connection had
+    /// already been closed
+    pub fn finish(&mut self, end: ConnectionEnd) {
+        if self.end == ConnectionEnd::Open {
+            self.end = end;
+        }
+    }
+
+    pub const fn end(&self) -> ConnectionEnd {
+        self.end
+    }
+
+    /// Reads a connection written by [`Self::write_index`] from a flow index
+    pub fn read_index(cursor: &mut ReadOnlyIndexCursor) -> Result<Self, Error> {
+        let syn_sequence_number = cursor.get_optional_u32()?;
+        let fin_from_initiator = cursor.get_bool()?;
+        let fin_from_respondent = cursor.get_bool()?;
+        let end = match cursor.get_u8()? {
+            0 => ConnectionEnd::Open,
+            1 => ConnectionEnd::HalfClosed,
+            2 => ConnectionEnd::Closed,
+            3 => ConnectionEnd::Reset,
+            4 => ConnectionEnd::TimedOut,
+            5 => ConnectionEnd::Superseded,
+            _ => return Err(Error::InvalidIndexFile),
+        };
+
+        let connection = Self {
+            syn_sequence_number,
+            fin_from_initiator,
+            fin_from_respondent,
+            end,
+        };
+
+        Ok(connection)
+    }
+
+    pub fn write_index(&self, cursor: &mut WriteOnlyIndexCursor) {
+        cursor.put_optional_u32(self.syn_sequence_number);
+        cursor.put_bool(self.fin_from_initiator);
+        cursor.put_bool(self.fin_from_respondent);
+        cursor.put_u8(match self.end {
+            ConnectionEnd::Open => 0,
+            ConnectionEnd::HalfClosed => 1,
+            ConnectionEnd::Closed => 2,
+            ConnectionEnd::Reset => 3,
+            ConnectionEnd::TimedOut => 4,
+            ConnectionEnd::Superseded => 5,
+        });
+    }
+}
+
+/// Health of a TCP connection, as far as its packets in the capture tell
+///
+/// Counts are of segments in both directions. Round-trip times are in nanoseconds and measured at
+/// the capture point, from a segment to the acknowledgement of its data.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct TcpMetrics {
+    /// Segments carrying data that was sent before
+    pub retransmissions: usize,
+    /// Segments carrying data that arrived after data following it
+    pub out_of_order: usize,
+    /// Acknowledgements repeating the previous one without carrying data or updating the window
+    pub duplicate_acks: usize,
+    /// Times an endpoint started advertising a zero window
+    pub zero_windows: usize,
+    /// Time from the SYN to the acknowledgement of the SYN-ACK
+    pub handshake_rtt: Option<u64>,
+    /// Sums of the round-trip times sampled for the data sent by the respondent and by the
+    /// initiator, in that order
+    rtt_sums: [u64; 2],
+    rtt_sample_counts: [u64; 2],
+    /// Window scale options of the SYNs of the respondent and of the initiator, in that order
+    window_scales: [Option<u8>; 2],
+}
+
+impl TcpMetrics {
+    /// Average round-trip time of the data sent by the initiator, or by the respondent
+    pub fn average_rtt(&self, from_initiator: bool) -> Option<u64> {
+        let direction = usize::from(from_initiator);
+
+        self.rtt_sums[direction].checked_div(self.rtt_sample_counts[direction])
+    }
+
+    /// Shift counts the windows of the initiator and of the respondent are scaled by, if both
+    /// endpoints agreed on window scaling in their SYNs
+    pub fn window_scaling(&self) -> Option<(u8, u8)> {
+        match self.window_scales {
+            [Some(respondent), Some(initiator)] => Some((initiator, respondent)),
+            _ => None,
+        }
+    }
+
+    /// Reads metrics written by [`Self::write_index`] from a flow index
+    pub fn read_index(cursor: &mut ReadOnlyIndexCursor) -> Result<Self, Error> {
+        let mut metrics = Self {
+            retransmissions: cursor.get_usize()?,
+            out_of_order: cursor.get_usize()?,
+            duplicate_acks: cursor.get_usize()?,
+            zero_windows: cursor.get_usize()?,
+            ..Self::default()
+        };
+        if cursor.get_bool()? {
+            metrics.handshake_rtt = Some(cursor.get_u64()?);
+        }
+        for direction in 0..2 {
+            metrics.rtt_sums[direction] = cursor.get_u64()?;
+            metrics.rtt_sample_counts[direction] = cursor.get_u64()?;
+            if cursor.get_bool()? {
+                metrics.window_scales[direction] = Some(cursor.get_u8()?);
+            }
+        }
+
+        Ok(metrics)
+    }
+
+    pub fn write_index(&self, cursor: &mut WriteOnlyIndexCursor) {
+        cursor.put_usize(self.retransmissions);
+        cursor.put_usize(self.out_of_order);
+        cursor.put_usize(self.duplicate_acks);
+        cursor.put_usize(self.zero_windows);
+        cursor.put_bool(self.handshake_rtt.is_some());
+        if let Some(handshake_rtt) = self.handshake_rtt {
+            cursor.put_u64(handshake_rtt);
+        }
+        for direction in 0..2 {
+            cursor.put_u64(self.rtt_sums[direction]);
+            cursor.put_u64(self.rtt_sample_counts[direction]);
+            cursor.put_bool(self.window_scales[direction].is_some());
+            if let Some(window_scale) = self.window_scales[direction] {
+                cursor.put_u8(window_scale);
+            }
+        }
+    }
+}
+
+/// Follows the segments of a TCP connection in capture order to work out its [`TcpMetrics`]
+///
+/// Times are in nanoseconds from any fixed point, such as the first packet of the flow.
+#[derive(Clone, Default, Debug)]
+pub struct TcpAnalysis {
+    metrics: TcpMetrics,
+    /// Time the SYN of the initiator was captured
+    syn_time: Option<i64>,
+    syn_ack_seen: bool,
+    /// State of the respondent's and of the initiator's half of the connection, in that order
+    directions: [DirectionState; 2],
+}
+
+/// What is known about the segments one endpoint sent
+#[derive(Clone, Default, Debug)]
+struct DirectionState {
+    /// Sequence number following the latest data sent
+    next_sequence_number: Option<u32>,
+    /// Ranges of sequence numbers skipped by data sent after them, with the time they were
+    /// skipped
+    holes: Vec<(u32, u32, i64)>,
+    /// Sequence numbers following data that has not been acknowledged, with the time the data was
+    /// sent, oldest first
+    unacknowledged: VecDeque<(u32, i64)>,
+    /// Acknowledgement number and window of the latest acknowledgement sent
+    last_acknowledgement: Option<(u32, u16)>,
+    zero_window: bool,
+}
+
+impl TcpAnalysis {
+    /// Largest number of holes and unacknowledged segments remembered per direction
+    const MAX_TRACKED: usize = 256;
+    /// Time within which data filling a hole counts as out of order rather than retransmitted,
+    /// when no round-trip time is known, in nanoseconds
+    const DEFAULT_REORDERING_WINDOW: u64 = 3_000_000;
+
+    pub const fn metrics(&self) -> &TcpMetrics {
+        &self.metrics
+    }
+
+    /// Follows a segment sent by the initiator if `from_initiator` is set, captured at `time`
+    pub fn update(&mut self, header: TcpHeader, from_initiator: bool, time: i64) {
+        let sender = usize::from(from_initiator);
+
+        if header.flags.contains(TcpFlags::SYN) {
+            self.metrics.window_scales[sender] = header.window_scale;
+        }
+        self.update_handshake(header, from_initiator, time);
+        self.update_sequence_numbers(header, sender, time);
+        if header.flags.contains(TcpFlags::ACK) {
+            self.update_acknowledgements(header, sender, time);
+        }
+
+        let direction = &mut self.directions[sender];
+        if !header.flags.contains(TcpFlags::RST) {
+            let zero_window = header.window == 0;
+            if zero_window && !direction.zero_window {
+                self.metrics.zero_windows += 1;
+            }
+            direction.zero_window = zero_window;
+        }
+    }
+
+    fn update_handshake(&mut self, header: TcpHeader, from_initiator: bool, time: i64) {
+        if self.metrics.handshake_rtt.is_some() {
+            return;
+        }
+
+        match (from_initiator, header.flags.contains(TcpFlags::SYN)) {
+            (true, true) if header.flags.is_syn() => {
+                self.syn_time.get_or_insert(time);
+            }
+            (false, true) => self.syn_ack_seen = true,
+            (true, false) if self.syn_ack_seen && header.flags.contains(TcpFlags::ACK) => {
+                if let Some(syn_time) = self.syn_time {
+                    self.metrics.handshake_rtt = Some(time.saturating_sub(syn_time).max(0) as u64);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Tells whether a segment carries new, retransmitted or reordered data
+    fn update_sequence_numbers(&mut self, header: TcpHeader, sender: usize, time: i64) {
+        let length = header.payload_length
+            + u32::from(header.flags.contains(TcpFlags::SYN))
+            + u32::from(header.flags.contains(TcpFlags::FIN));
+        if length == 0 {
+            return;
+        }
+
+        let reordering_window = self
+            .metrics
+            .average_rtt(sender == 1)
+            .or(self.metrics.handshake_rtt)
+            .unwrap_or(Self::DEFAULT_REORDERING_WINDOW);
+
+        let start = header.sequence_number;
+        let end = start.wrapping_add(length);
+        let direction = &mut self.directions[sender];
+        let Some(next) = direction.next_sequence_number else {
+            direction.next_sequence_number = Some(end);
+            direction.track_unacknowledged(end, time);
+            return;
+        };
+
+        if is_after(end, next) {
+            if is_after(start, next) {
+                direction.track_hole(next, start, time);
+            } else if is_after(next, start) {
+                self.metrics.retransmissions += 1;
+                direction.forget_unacknowledged(start);
+            }
+            direction.next_sequence_number = Some(end);
+            direction.track_unacknowledged(end, time);
+        } else if direction.fill_hole(start, end, time, reordering_window) {
+            self.metrics.out_of_order += 1;
+        } else {
+            self.metrics.retransmissions += 1;
+            direction.forget_unacknowledged(start);
+        }
+    }
+
+    /// Samples the round-trip time of the data an acknowledgement covers, and tells whether it
+    /// duplicates the previous one
+    fn update_acknowledgements(&mut self, header: TcpHeader, sender: usize, time: i64) {
+        let acknowledgement_number = header.acknowledgement_number;
+
+        let receiver = &mut self.directions[1 - sender];
+        let mut sent = None;
+        while let Some(&(end, sent_time)) = receiver.unacknowledged.front() {
+            if is_after(end, acknowledgement_number) {
+                break;
+            }
+            receiver.unacknowledged.pop_front();
+            sent = Some(sent_time);
+        }
+        if let Some(sent) = sent {
+            self.metrics.rtt_sums[1 - sender] += time.saturating_sub(sent).max(0) as u64;
+            self.metrics.rtt_sample_counts[1 - sender] += 1;
+        }
+
+        let direction = &mut self.directions[sender];
+        let acknowledgement = (acknowledgement_number, header.window);
+        let carries_nothing = header.payload_length == 0
+            && !header.flags.contains(TcpFlags::SYN)
+            && !header.flags.contains(TcpFlags::FIN)
+            && !header.flags.contains(TcpFlags::RST);
+        if carries_nothing && direction.last_acknowledgement == Some(acknowledgement) {
+            self.metrics.duplicate_acks += 1;
+        }
+        direction.last_acknowledgement = Some(acknowledgement);
+    }
+}
+
+impl DirectionState {
+    fn track_hole(&mut self, start: u32, end: u32, time: i64) {
+        if self.holes.len() == TcpAnalysis::MAX_TRACKED {
+            self.holes.remove(0);
+        }
+        self.holes.push((start, end, time));
+    }
+
+    /// Removes the data from `start` to `end` arriving at `time` from the hole it falls into,
+    /// returning whether that hole was made less than `reordering_window` before
+    fn fill_hole(&mut self, start: u32, end: u32, time: i64, reordering_window: u64) -> bool {
+        let Some(index) = self.holes.iter().position(|&(hole_start, hole_end, _)| {
+            !is_after(hole_start, start) && is_after(hole_end, start)
+        }) else {
+            return false;
+        };
+
+        let (hole_start, hole_end, hole_time) = self.holes.remove(index);
+        if is_after(start, hole_start) {
+            self.holes.push((hole_start, start, hole_time));
+        }
+        if is_after(hole_end, end) {
+            self.holes.push((end, hole_end, hole_time));
+        }
+
+        time.saturating_sub(hole_time) < reordering_window as i64
+    }
+
+    fn track_unacknowledged(&mut self, end: u32, time: i64) {
+        if self.unacknowledged.len() == TcpAnalysis::MAX_TRACKED {
+            self.unacknowledged.pop_front();
+        }
+        self.unacknowledged.push_back((end, time));
+    }
+
+    /// Stops timing data from `start` on, since an acknowledgement of it could be for the
+    /// retransmission as well as the original
+    fn forget_unacknowledged(&mut self, start: u32) {
+        self.unacknowledged
+            .retain(|&(end, _)| !is_after(end, start));
+    }
+}
+
+/// Whether sequence number `a` comes after `b`, taking wrapping around into account
+const fn is_after(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packet_dissection::{TcpFlags, TcpHeader};
+    use crate::tcp::{ConnectionEnd, TcpAnalysis, TcpConnection};
+
+    #[test]
+    fn following_connection_lifecycle_succeeds() {
+        let mut connection = TcpConnection::default();
+        connection.update(segment(100, TcpFlags::SYN), true);
+        connection.update(segment(500, TcpFlags(0x12)), false);
+        connection.update(segment(101, TcpFlags::ACK), true);
+        assert_eq!(connection.end(), ConnectionEnd::Open);
+
+        // A retransmitted SYN belongs to the same connection
+        assert!(!connection.is_reopened_by(segment(100, TcpFlags::SYN), 120, 60));
+        // A new SYN only opens a new connection once the old one is idle for long enough
+        assert!(!connection.is_reopened_by(segment(900, TcpFlags::SYN), 10, 60));
+        assert!(connection.is_reopened_by(segment(900, TcpFlags::SYN), 60, 60));
+
+        connection.update(segment(101, TcpFlags(0x11)), true);
+        assert_eq!(connection.end(), ConnectionEnd::HalfClosed);
+        connection.update(segment(501, TcpFlags(0x11)), false);
+        assert_eq!(connection.end(), ConnectionEnd::Closed);
+
+        assert!(connection.is_reopened_by(segment(900, TcpFlags::SYN), 0, 60));
+        assert!(!connection.is_reopened_by(segment(900, TcpFlags(0x12)), 0, 60));
+
+        connection.finish(ConnectionEnd::Superseded);
+        assert_eq!(connection.end(), ConnectionEnd::Closed);
+
+        connection.update(segment(102, TcpFlags::RST), true);
+        assert_eq!(connection.end(), ConnectionEnd::Reset);
+    }
+
+    #[test]
+    fn analysing_connection_metrics_succeeds() {
+        const MILLISECOND: i64 = 1_000_000;
+        let mut analysis = TcpAnalysis::default();
+
+        let mut syn = data(100, 0, TcpFlags::SYN, 0, 1000);
+        syn.window_scale = Some(7);
+        analysis.update(syn, true, 0);
+        let mut syn_ack = data(500, 101, TcpFlags(0x12), 0, 1000);
+        syn_ack.window_scale = Some(8);
+        analysis.update(syn_ack, false, 10 * MILLISECOND);
+        analysis.update(
+            data(101, 501, TcpFlags::ACK, 0, 1000),
+            true,
+            20 * MILLISECOND,
+        );
+
+        analysis.update(
+            data(101, 501, TcpFlags::ACK, 10, 1000),
+            true,
+            30 * MILLISECOND,
+        );
+        analysis.update(
+            data(121, 501, TcpFlags::ACK, 10, 1000),
+            true,
+            31 * MILLISECOND,
+        );
+        // Fills the hole within the round-trip time
+        analysis.update(
+            data(111, 501, TcpFlags::ACK, 10, 1000),
+            true,
+            32 * MILLISECOND,
+        );
+        analysis.update(data(501, 111, TcpFlags::ACK, 0, 0), false, 40 * MILLISECOND);
+        analysis.update(data(501, 111, TcpFlags::ACK, 0, 0), false, 41 * MILLISECOND);
+        analysis.update(
+            data(121, 501, TcpFlags::ACK, 10, 1000),
+            true,
+            100 * MILLISECOND,
+        );
+        analysis.update(
+            data(501, 131, TcpFlags::ACK, 0, 500),
+            false,
+            110 * MILLISECOND,
+        );
+
+        let metrics = analysis.metrics();
+        assert_eq!(metrics.retransmissions, 1);
+        assert_eq!(metrics.out_of_order, 1);
+        assert_eq!(metrics.duplicate_acks, 1);
+        assert_eq!(metrics.zero_windows, 1);
+        assert_eq!(metrics.handshake_rtt, Some(20_000_000));
+        assert_eq!(metrics.average_rtt(true), Some(10_000_000));
+        assert_eq!(metrics.average_rtt(false), Some(10_000_000));
+        assert_eq!(metrics.window_scaling(), Some((7, 8)));
+    }
+
+    fn data(
+        sequence_number: u32,
+        acknowledgement_number: u32,
+        flags: TcpFlags,
+        payload_length: u32,
+        window: u16,
+    ) -> TcpHeader {
+        TcpHeader {
+            sequence_number,
+            acknowledgement_number,
+            flags,
+            window,
+            payload_length,
+            ..TcpHeader::default()
+        }
+    }
+
+    fn segment(sequence_number: u32, flags: TcpFlags) -> TcpHeader {
+        TcpHeader {
+            sequence_number,
+            flags,
+            ..TcpHeader::default()
+        }
+    }
+}
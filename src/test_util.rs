@@ -0,0 +1,20 @@
+use std::fs::File;
+use std::io::Write;
+
+use rand::distr::{Alphanumeric, DistString};
+
+/// Random path in `/tmp` that no file is likely to have
+pub fn get_path_for_new_temp_file() -> String {
+    format!(
+        "/tmp/{}",
+        Alphanumeric.sample_string(&mut rand::thread_rng(), 20)
+    )
+}
+
+/// Writes `buffer` into a new temporary file, returning its path
+pub fn write_temp_file(buffer: &[u8]) -> String {
+    let file_path = get_path_for_new_temp_file();
+    File::create(&file_path).unwrap().write_all(buffer).unwrap();
+
+    file_path
+}
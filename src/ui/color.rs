@@ -29,6 +29,15 @@ pub mod tabs {
     pub const UNSELECTED: ColorSet = ColorSet::new(Color::Rgb(0xBC, 0xBE, 0xE4), Color::Black);
 }
 
+pub mod payload {
+    use ratatui::prelude::Color;
+
+    use crate::ui::color_set::ColorSet;
+
+    pub const INITIATOR: ColorSet = ColorSet::new(Color::LightRed, Color::Black);
+    pub const RESPONDENT: ColorSet = ColorSet::new(Color::LightBlue, Color::Black);
+}
+
 pub const HEADER: ColorSet = ColorSet::new(Color::White, Color::Black);
 pub const FOOTER: ColorSet = ColorSet::new(Color::White, Color::Black);
 pub const TABLE: ColorSet = ColorSet::new(Color::White, Color::Black);
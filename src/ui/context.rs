@@ -6,17 +6,22 @@ use crossterm::event;
 use crossterm::event::{Event, KeyCode};
 use ratatui::widgets::TableState;
 
+use crate::capture_file::STDIN_PATH;
+use crate::compression::Compression;
 use crate::error::Error;
+use crate::flow::{open_flows, FlowOptions, Flows};
+use crate::packet_layer::TransportLayerType;
 use crate::ui::directory::{DirectoryContent, DirectoryEntryType};
-use crate::ui::flow::{extract_flows, Flows};
+use crate::ui::payload::PayloadView;
 
 pub struct Context {
     pub state: State,
     pub table_state: TableState,
+    flow_options: FlowOptions,
 }
 
 impl Context {
-    pub fn new() -> Result<Self, Error> {
+    pub fn new(flow_options: FlowOptions) -> Result<Self, Error> {
         let current_directory = std::env::current_dir().map_err(Error::ReadEnv)?;
         let current_directory_content = DirectoryContent::read(&current_directory)?;
 
@@ -27,13 +32,22 @@ impl Context {
                 index: 0,
             },
             table_state: TableState::default(),
+            flow_options,
         })
     }
 
-    pub fn new_view(path: OsString) -> Result<Self, Error> {
+    pub fn new_view(path: OsString, flow_options: FlowOptions) -> Result<Self, Error> {
         let current_directory = std::env::current_dir().map_err(Error::ReadEnv)?;
-        let file_name = Path::new(&path).file_name().ok_or(Error::NoFileNameInPath)?.to_string_lossy().to_string();
-        let flows = extract_flows(path)?;
+        let file_name = if path == STDIN_PATH {
+            "stdin".to_string()
+        } else {
+            Path::new(&path)
+                .file_name()
+                .ok_or(Error::NoFileNameInPath)?
+                .to_string_lossy()
+                .to_string()
+        };
+        let flows = open_flows(path, flow_options)?;
 
         Ok(Self {
             state: State::View {
@@ -41,12 +55,70 @@ impl Context {
                 current_file: file_name,
                 index: 0,
                 flow_index: 0,
-                flows,
+                flows: Box::new(flows),
+                export_path: None,
+                export_target: ExportTarget::SelectedTab,
+                message: None,
+                payload: None,
+            },
+            table_state: TableState::default(),
+            flow_options,
+        })
+    }
+
+    pub fn new_live(interface: String, flow_options: FlowOptions) -> Result<Self, Error> {
+        let current_directory = std::env::current_dir().map_err(Error::ReadEnv)?;
+        let flows = Flows::live(&interface, flow_options)?;
+
+        Ok(Self {
+            state: State::View {
+                current_directory,
+                current_file: interface,
+                index: 0,
+                flow_index: 0,
+                flows: Box::new(flows),
+                export_path: None,
+                export_target: ExportTarget::SelectedTab,
+                message: None,
+                payload: None,
+            },
+            table_state: TableState::default(),
+            flow_options,
+        })
+    }
+
+    /// Opens the captures at `paths` merged into one capture, in the order of their timestamps
+    pub fn new_merged(paths: &[PathBuf], flow_options: FlowOptions) -> Result<Self, Error> {
+        let current_directory = std::env::current_dir().map_err(Error::ReadEnv)?;
+        let flows = Flows::merge(paths, flow_options)?;
+
+        Ok(Self {
+            state: State::View {
+                current_directory,
+                current_file: "merged".to_string(),
+                index: 0,
+                flow_index: 0,
+                flows: Box::new(flows),
+                export_path: None,
+                export_target: ExportTarget::SelectedTab,
+                message: None,
+                payload: None,
             },
             table_state: TableState::default(),
+            flow_options,
         })
     }
 
+    /// Adds the packets that arrived since the last update to the flows of a capture that is
+    /// still being read
+    pub fn update(&mut self) {
+        if let State::View { flows, message, .. } = &mut self.state {
+            if let Err(e) = flows.update() {
+                *message = Some(format!("reading capture failed: {e}"));
+            }
+        }
+    }
+
     pub const fn should_exit(&self) -> bool {
         matches!(self.state, State::Exit)
     }
@@ -64,6 +136,15 @@ impl Context {
             return Ok(());
         }
 
+        if let State::View {
+            export_path: Some(_),
+            ..
+        } = self.state
+        {
+            self.handle_export_prompt_input(key.code);
+            return Ok(());
+        }
+
         match key.code {
             KeyCode::Up => {
                 self.cursor_up();
@@ -86,12 +167,47 @@ impl Context {
             KeyCode::Backspace => {
                 self.backspace();
             }
+            KeyCode::Char('e' | 'E') => {
+                self.start_export();
+            }
+            KeyCode::Char('s' | 'S') => {
+                self.start_save();
+            }
+            KeyCode::Char('p' | 'P') => {
+                self.toggle_payload();
+            }
             _ => return Ok(()),
         };
 
         Ok(())
     }
 
+    fn handle_export_prompt_input(&mut self, key_code: KeyCode) {
+        let State::View {
+            export_path: Some(export_path),
+            ..
+        } = &mut self.state
+        else {
+            return;
+        };
+
+        match key_code {
+            KeyCode::Char(c) => {
+                export_path.push(c);
+            }
+            KeyCode::Backspace => {
+                export_path.pop();
+            }
+            KeyCode::Enter => {
+                self.export();
+            }
+            KeyCode::Esc => {
+                self.cancel_export();
+            }
+            _ => {}
+        }
+    }
+
     fn cursor_up(&mut self) {
         match &mut self.state {
             State::Browse { index: 0, .. } | State::View { index: 0, .. } | State::Exit => {}
@@ -112,6 +228,15 @@ impl Context {
                     index.add_assign(1);
                 }
             }
+            State::View {
+                index,
+                payload: Some(payload),
+                ..
+            } => {
+                if payload.len() - 1 > *index {
+                    index.add_assign(1);
+                }
+            }
             State::View { index, .. } => {
                 index.add_assign(1);
             }
@@ -121,12 +246,16 @@ impl Context {
 
     fn tab_left(&mut self) {
         if let State::View {
-            flow_index, index, ..
+            flow_index,
+            index,
+            payload,
+            ..
         } = &mut self.state
         {
             if *flow_index > 0usize {
                 flow_index.sub_assign(1);
                 index.bitxor_assign(*index);
+                *payload = None;
             }
         }
     }
@@ -136,16 +265,45 @@ impl Context {
             flow_index,
             flows,
             index,
+            payload,
             ..
         } = &mut self.state
         {
-            if *flow_index < (flows.len() - 1) {
+            if *flow_index + 1 < flows.tab_count() {
                 flow_index.add_assign(1);
                 index.bitxor_assign(*index);
+                *payload = None;
             }
         }
     }
 
+    /// Switches between the packets of the selected TCP flow and its reassembled conversation
+    fn toggle_payload(&mut self) {
+        if let State::View {
+            index,
+            flow_index,
+            flows,
+            message,
+            payload,
+            ..
+        } = &mut self.state
+        {
+            if payload.take().is_none() {
+                if *flow_index == flows.len()
+                    || flows.get(*flow_index).protocol != TransportLayerType::Tcp
+                {
+                    return;
+                }
+
+                match flows.reassemble(*flow_index) {
+                    Ok(stream) => *payload = Some(PayloadView::new(&stream)),
+                    Err(e) => *message = Some(format!("reassembly failed: {e}")),
+                }
+            }
+            index.bitxor_assign(*index);
+        }
+    }
+
     fn enter(&mut self) {
         match &self.state {
             State::Browse {
@@ -168,7 +326,7 @@ impl Context {
                         }
                     }
                     DirectoryEntryType::File | DirectoryEntryType::SymbolicLink => {
-                        let Ok(flows) = extract_flows(new_path) else {
+                        let Ok(flows) = open_flows(new_path, self.flow_options) else {
                             return;
                         };
 
@@ -177,7 +335,11 @@ impl Context {
                             current_file: entry.display_name(),
                             index: 0,
                             flow_index: 0,
-                            flows,
+                            flows: Box::new(flows),
+                            export_path: None,
+                            export_target: ExportTarget::SelectedTab,
+                            message: None,
+                            payload: None,
                         }
                     }
                 }
@@ -186,6 +348,90 @@ impl Context {
         }
     }
 
+    fn start_export(&mut self) {
+        if let State::View {
+            current_directory,
+            current_file,
+            flow_index,
+            flows,
+            export_path,
+            export_target,
+            message,
+            ..
+        } = &mut self.state
+        {
+            let file_stem = capture_file_stem(current_file);
+            let tab_name = if *flow_index == flows.len() {
+                "unclassified".to_string()
+            } else {
+                format!("flow_{}", *flow_index + 1)
+            };
+            let default_path = current_directory
+                .join(format!("{file_stem}_{tab_name}.{}", flows.file_extension()));
+
+            *export_path = Some(default_path.to_string_lossy().to_string());
+            *export_target = ExportTarget::SelectedTab;
+            *message = None;
+        }
+    }
+
+    /// Prompts for the path to save the whole capture to, if it is only kept in a temporary file
+    fn start_save(&mut self) {
+        if let State::View {
+            current_directory,
+            current_file,
+            flows,
+            export_path,
+            export_target,
+            message,
+            ..
+        } = &mut self.state
+        {
+            if !flows.is_temporary() {
+                return;
+            }
+
+            let file_stem = capture_file_stem(current_file);
+            let default_path =
+                current_directory.join(format!("{file_stem}.{}", flows.file_extension()));
+
+            *export_path = Some(default_path.to_string_lossy().to_string());
+            *export_target = ExportTarget::WholeCapture;
+            *message = None;
+        }
+    }
+
+    fn export(&mut self) {
+        if let State::View {
+            flow_index,
+            flows,
+            export_path,
+            export_target,
+            message,
+            ..
+        } = &mut self.state
+        {
+            let Some(path) = export_path.take() else {
+                return;
+            };
+
+            let result = match export_target {
+                ExportTarget::SelectedTab => flows.export(*flow_index, &path),
+                ExportTarget::WholeCapture => flows.save(&path),
+            };
+            *message = match result {
+                Ok(packet_count) => Some(format!("exported {packet_count} packets to {path}")),
+                Err(e) => Some(format!("export failed: {e}")),
+            };
+        }
+    }
+
+    fn cancel_export(&mut self) {
+        if let State::View { export_path, .. } = &mut self.state {
+            *export_path = None;
+        }
+    }
+
     fn exit(&mut self) {
         self.state = State::Exit;
     }
@@ -220,6 +466,19 @@ impl Context {
     }
 }
 
+/// Name of the capture file `file_name` without its extensions, including that of its compression
+fn capture_file_stem(file_name: &str) -> String {
+    let mut path = Path::new(file_name);
+    if Compression::from_extension(path).is_some() {
+        path = Path::new(path.file_stem().unwrap_or_default());
+    }
+
+    path.file_stem().map_or_else(
+        || file_name.to_string(),
+        |s| s.to_string_lossy().to_string(),
+    )
+}
+
 pub enum State {
     Browse {
         current_directory: PathBuf,
@@ -231,7 +490,21 @@ pub enum State {
         current_file: String,
         index: usize,
         flow_index: usize,
-        flows: Flows,
+        flows: Box<Flows>,
+        export_path: Option<String>,
+        export_target: ExportTarget,
+        message: Option<String>,
+        /// Reassembled conversation of the selected TCP flow, shown instead of its packets
+        payload: Option<PayloadView>,
     },
     Exit,
 }
+
+/// What the export prompt writes to the entered path
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ExportTarget {
+    /// Packets of the selected flow, or the unclassified packets
+    SelectedTab,
+    /// Every packet read so far, for captures only kept in a temporary file
+    WholeCapture,
+}
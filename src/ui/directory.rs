@@ -6,6 +6,7 @@ use std::path::Path;
 use ratatui::prelude::Style;
 use ratatui::widgets::Row;
 
+use crate::compression::is_capture_file_name;
 use crate::error::Error;
 use crate::ui::style;
 
@@ -32,7 +33,7 @@ impl DirectoryContent {
         Ok(Self { content })
     }
 
-    pub const fn iter(&self) -> DirectoryContentIterator {
+    pub const fn iter(&self) -> DirectoryContentIterator<'_> {
         DirectoryContentIterator {
             inner: self,
             index: 0,
@@ -61,7 +62,10 @@ impl<'a> Iterator for DirectoryContentIterator<'a> {
 
         let file_type_style = directory_entry.entry_type.style();
         let order_style = style::table::get_row_style_by_index(self.index, false);
-        let style = order_style.patch(file_type_style);
+        let mut style = order_style.patch(file_type_style);
+        if directory_entry.is_capture {
+            style = style.patch(style::capture());
+        }
 
         let row = Row::new([directory_entry.display_name.clone()]).style(style);
 
@@ -77,6 +81,8 @@ pub struct DirectoryEntry {
     file_name: OsString,
     sort_name: OsString,
     display_name: String,
+    /// Whether the file name is that of a capture, possibly a compressed one
+    is_capture: bool,
 }
 
 impl DirectoryEntry {
@@ -95,22 +101,20 @@ impl DirectoryEntry {
 
 impl PartialOrd for DirectoryEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let ordering = match (
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DirectoryEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (
             self.entry_type.cmp(&other.entry_type),
             self.sort_name.cmp(&other.sort_name),
         ) {
             (Ordering::Greater, _) => Ordering::Less,
             (Ordering::Equal, ordering) => ordering,
             (Ordering::Less, _) => Ordering::Greater,
-        };
-
-        Some(ordering)
-    }
-}
-
-impl Ord for DirectoryEntry {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        }
     }
 }
 
@@ -118,11 +122,13 @@ impl From<(DirectoryEntryType, OsString)> for DirectoryEntry {
     fn from(value: (DirectoryEntryType, OsString)) -> Self {
         let sort_name = value.1.to_ascii_lowercase();
         let display_name = value.1.to_string_lossy().to_string();
+        let is_capture = value.0 != DirectoryEntryType::Directory && is_capture_file_name(&value.1);
         Self {
             entry_type: value.0,
             file_name: value.1,
             sort_name,
             display_name,
+            is_capture,
         }
     }
 }
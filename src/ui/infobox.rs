@@ -1,89 +1,277 @@
+use std::net::SocketAddr;
+
 use ratatui::buffer::Buffer;
-use ratatui::Frame;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::Stylize;
 use ratatui::text::Line;
 use ratatui::widgets::{Block, Paragraph, Widget};
+use ratatui::Frame;
 
+use crate::flow::{Flow, Unclassified};
+use crate::tcp::TcpMetrics;
 use crate::ui::color;
-use crate::ui::flow::Flow;
 use crate::ui::timestamp_display::TimestampDisplay;
 
+/// Number of lines of the statistics of each direction of a flow, below a line of arrows
+const DIRECTION_STATISTICS_HEIGHT: u16 = 8;
+/// Number of lines of TCP metrics shown below the statistics of TCP flows
+const TCP_METRICS_HEIGHT: u16 = 4;
+
+/// Height of the infobox of `data`, including its borders
+pub fn height(data: &Flow) -> u16 {
+    let tcp_metrics_height = if data.tcp_metrics().is_some() {
+        TCP_METRICS_HEIGHT
+    } else {
+        0
+    };
+
+    2 + DIRECTION_STATISTICS_HEIGHT + tcp_metrics_height
+}
+
 pub fn draw(frame: &mut Frame, area: Rect, data: &Flow) {
     let buffer = frame.buffer_mut();
 
     let block = Block::bordered().bg(color::TABLE.bg());
-    let inner = block.inner(area);
+    let mut inner = block.inner(area);
 
     block.render(area, buffer);
 
-    let constraints = [Constraint::Min(0); 6];
+    if let Some(tcp_metrics) = data.tcp_metrics() {
+        let constraints = [Constraint::Min(0), Constraint::Length(TCP_METRICS_HEIGHT)];
+        let rows = Layout::new(Direction::Vertical, constraints).split(inner);
+        inner = rows[0];
+
+        draw_tcp_metrics(buffer, rows[1], tcp_metrics);
+    }
+
+    let constraints = [
+        Constraint::Length(15),
+        Constraint::Min(0),
+        Constraint::Length(16),
+        Constraint::Min(0),
+        Constraint::Min(0),
+        Constraint::Length(25),
+        Constraint::Min(0),
+        Constraint::Min(0),
+    ];
     let areas = Layout::new(Direction::Horizontal, constraints).split(inner);
 
+    // Endpoints of flows whose start was not captured are guessed from their ports
+    let (initiator_label, respondent_label) = if data.start_observed {
+        ("Initiator:", "Respondent:")
+    } else {
+        ("Initiator?:", "Respondent?:")
+    };
+    let mut labels = vec![
+        initiator_label.to_string(),
+        respondent_label.to_string(),
+        "Protocol:".to_string(),
+        "Packet count:".to_string(),
+        "Total size:".to_string(),
+        "Flow duration:".to_string(),
+    ];
+    let mut values = vec![
+        endpoint(data, data.initiator),
+        endpoint(data, data.respondent),
+        data.protocol.to_string(),
+        data.packet_count.to_string(),
+        data.total_size.to_string(),
+        TimestampDisplay(data.flow_duration.into()).to_string(),
+    ];
+    if let Some(tunnel) = &data.tunnel {
+        labels.push("Tunnel:".to_string());
+        values.push(tunnel.to_string());
+    }
+    if let Some(connection_end) = data.connection_end() {
+        labels.push("Connection:".to_string());
+        values.push(connection_end.to_string());
+    }
+
+    draw_paragraph(buffer, areas[0], &labels);
+    draw_paragraph(buffer, areas[1], &values);
+
     draw_paragraph(
         buffer,
-        areas[0],
+        areas[2],
         &[
-            "Initiator:".to_string(),
-            "Respondent:".to_string(),
-            "Protocol:".to_string(),
-            "Packet count:".to_string(),
+            String::new(),
+            "Packets:".to_string(),
+            "Bytes:".to_string(),
+            "Payload bytes:".to_string(),
+            "Minimum size:".to_string(),
+            "Maximum size:".to_string(),
+            "Average size:".to_string(),
+            "Size std. dev.:".to_string(),
         ],
     );
     draw_paragraph(
         buffer,
-        areas[1],
+        areas[5],
         &[
-            data.initiator.to_string(),
-            data.respondent.to_string(),
-            data.protocol.to_string(),
-            data.packet_count.to_string(),
+            String::new(),
+            "Minimum inter-arrival:".to_string(),
+            "Maximum inter-arrival:".to_string(),
+            "Average inter-arrival:".to_string(),
+            "Inter-arrival std. dev.:".to_string(),
         ],
     );
 
+    for (from_initiator, size_area, interarrival_area) in
+        [(true, areas[3], areas[6]), (false, areas[4], areas[7])]
+    {
+        let statistics = data.direction_statistics(from_initiator);
+        let arrow = if from_initiator { "→" } else { "←" };
+
+        draw_paragraph(
+            buffer,
+            size_area,
+            &[
+                arrow.to_string(),
+                statistics.packet_count.to_string(),
+                statistics.total_size.to_string(),
+                statistics.payload_size.to_string(),
+                sent(statistics.packet_count, statistics.minimum_size),
+                sent(statistics.packet_count, statistics.maximum_size),
+                decimal(statistics.average_size()),
+                decimal(statistics.size_standard_deviation()),
+            ],
+        );
+
+        // Inter-arrival times need two packets in the direction
+        let interarrival_time = |time: u64| (statistics.packet_count > 1).then_some(time);
+        draw_paragraph(
+            buffer,
+            interarrival_area,
+            &[
+                arrow.to_string(),
+                duration(interarrival_time(statistics.minimum_interarrival_time)),
+                duration(interarrival_time(statistics.maximum_interarrival_time)),
+                duration(statistics.average_interarrival_time()),
+                duration(statistics.interarrival_time_standard_deviation()),
+            ],
+        );
+    }
+}
+
+/// A size of the packets sent in a direction, `-` if none were sent
+fn sent(packet_count: usize, size: usize) -> String {
+    if packet_count == 0 {
+        "-".to_string()
+    } else {
+        size.to_string()
+    }
+}
+
+fn decimal(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |value| format!("{value:.1}"))
+}
+
+fn duration(nanos: Option<u64>) -> String {
+    nanos.map_or("-".to_string(), |nanos| {
+        TimestampDisplay(nanos.into()).to_string()
+    })
+}
+
+/// Draws the health metrics of a TCP connection
+fn draw_tcp_metrics(buffer: &mut Buffer, area: Rect, data: &TcpMetrics) {
+    let constraints = [
+        Constraint::Length(23),
+        Constraint::Min(0),
+        Constraint::Length(15),
+        Constraint::Min(0),
+    ];
+    let areas = Layout::new(Direction::Horizontal, constraints).split(area);
+
+    let window_scaling = data
+        .window_scaling()
+        .map_or("off".to_string(), |(initiator, respondent)| {
+            format!("→ {initiator}, ← {respondent}")
+        });
+
     draw_paragraph(
         buffer,
-        areas[2],
+        areas[0],
         &[
-            "Total size:".to_string(),
-            "Average size:".to_string(),
-            "Minimum size:".to_string(),
-            "Maximum size:".to_string(),
+            "Retransmissions:".to_string(),
+            "Out-of-order segments:".to_string(),
+            "Duplicate ACKs:".to_string(),
+            "Zero windows:".to_string(),
         ],
     );
     draw_paragraph(
         buffer,
-        areas[3],
+        areas[1],
         &[
-            data.total_size.to_string(),
-            data.average_size.to_string(),
-            data.minimum_size.to_string(),
-            data.maximum_size.to_string(),
+            data.retransmissions.to_string(),
+            data.out_of_order.to_string(),
+            data.duplicate_acks.to_string(),
+            data.zero_windows.to_string(),
         ],
     );
 
     draw_paragraph(
         buffer,
-        areas[4],
+        areas[2],
         &[
-            "Flow duration:".to_string(),
-            "Average inter-arrival time:".to_string(),
-            "Minimum inter-arrival time:".to_string(),
-            "Maximum inter-arrival time:".to_string(),
+            "Handshake RTT:".to_string(),
+            "Average RTT →:".to_string(),
+            "Average RTT ←:".to_string(),
+            "Window scale:".to_string(),
         ],
     );
     draw_paragraph(
         buffer,
-        areas[5],
+        areas[3],
         &[
-            TimestampDisplay(data.flow_duration).to_string(),
-            TimestampDisplay(data.average_interarrival_time).to_string(),
-            TimestampDisplay(data.minimum_interarrival_time).to_string(),
-            TimestampDisplay(data.maximum_interarrival_time).to_string(),
+            duration(data.handshake_rtt),
+            duration(data.average_rtt(true)),
+            duration(data.average_rtt(false)),
+            window_scaling,
         ],
     );
 }
 
+/// Draws the number of unclassified packets per reason, four reasons per column
+pub fn draw_unclassified(frame: &mut Frame, area: Rect, data: &Unclassified) {
+    let buffer = frame.buffer_mut();
+
+    let block = Block::bordered().bg(color::TABLE.bg());
+    let inner = block.inner(area);
+
+    block.render(area, buffer);
+
+    let constraints = [Constraint::Min(0); 6];
+    let areas = Layout::new(Direction::Horizontal, constraints).split(inner);
+
+    for (reasons, areas) in data.reasons().chunks(4).zip(areas.chunks(2)) {
+        draw_paragraph(
+            buffer,
+            areas[0],
+            &reasons
+                .iter()
+                .map(|(reason, _)| format!("{reason}:"))
+                .collect::<Vec<String>>(),
+        );
+        draw_paragraph(
+            buffer,
+            areas[1],
+            &reasons
+                .iter()
+                .map(|(_, count)| count.to_string())
+                .collect::<Vec<String>>(),
+        );
+    }
+}
+
+/// Address of an endpoint of `data`, with its port if the protocol has ports
+fn endpoint(data: &Flow, addr: SocketAddr) -> String {
+    if data.protocol.has_ports() {
+        addr.to_string()
+    } else {
+        addr.ip().to_string()
+    }
+}
+
 pub fn draw_paragraph(buffer: &mut Buffer, area: Rect, text: &[String]) {
     let lines = text
         .iter()
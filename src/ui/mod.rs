@@ -1,16 +1,18 @@
 use std::borrow::Cow;
 use std::ffi::OsString;
 
-use crossterm::ExecutableCommand;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
+use crossterm::ExecutableCommand;
 use ratatui::prelude::*;
-use ratatui::Terminal;
 use ratatui::widgets::{Block, Borders};
+use ratatui::Terminal;
 
 use crate::error::Error;
-use crate::ui::context::{Context, State};
+use crate::flow::FlowOptions;
+use crate::merge::expand_paths;
+use crate::ui::context::{Context, ExportTarget, State};
 
 mod color;
 mod color_set;
@@ -18,15 +20,24 @@ mod context;
 mod directory;
 mod flow;
 mod infobox;
+mod payload;
 mod style;
 mod table;
 mod tabs;
 mod timestamp_display;
 
-pub fn run(path: Option<OsString>) -> Result<(), Error> {
-    let mut context = match path {
-        None => Context::new(),
-        Some(p) => Context::new_view(p)
+pub fn run(
+    paths: Vec<OsString>,
+    interface: Option<String>,
+    flow_options: FlowOptions,
+) -> Result<(), Error> {
+    let mut context = match (paths.as_slice(), interface) {
+        ([], Some(i)) => Context::new_live(i, flow_options),
+        ([], None) => Context::new(flow_options),
+        (paths, _) => match expand_paths(paths)?.as_slice() {
+            [p] => Context::new_view(p.clone().into_os_string(), flow_options),
+            paths => Context::new_merged(paths, flow_options),
+        },
     }?;
 
     enable_raw_mode().map_err(Error::TuiSetup)?;
@@ -38,6 +49,7 @@ pub fn run(path: Option<OsString>) -> Result<(), Error> {
         Terminal::new(CrosstermBackend::new(std::io::stdout())).map_err(Error::TuiSetup)?;
 
     while !context.should_exit() {
+        context.update();
         terminal
             .draw(|frame| {
                 draw_ui(frame, &mut context);
@@ -72,7 +84,27 @@ fn draw_header(frame: &mut Frame, context: &Context, area: Rect) {
         State::Browse {
             current_directory, ..
         } => current_directory.to_string_lossy(),
-        State::View { current_file, .. } => Cow::from(current_file),
+        State::View {
+            current_file,
+            flows,
+            message,
+            ..
+        } => {
+            let mut title = Cow::from(current_file);
+            if flows.is_streaming() {
+                title = Cow::from(format!("{title} (reading)"));
+            }
+            if let Some(packet_count) = flows.truncated_after() {
+                title = Cow::from(format!(
+                    "{title} (capture truncated after packet {packet_count})"
+                ));
+            }
+            if let Some(message) = message {
+                title = Cow::from(format!("{title}: {message}"));
+            }
+
+            title
+        }
         State::Exit => Cow::from(""),
     };
 
@@ -112,18 +144,58 @@ fn draw_body(frame: &mut Frame, context: &mut Context, table_area: Rect) {
             index,
             flow_index,
             flows,
+            payload,
             ..
         } => {
             context.table_state.select(Some(*index));
 
+            let infobox_height = if *flow_index < flows.len() {
+                infobox::height(flows.get(*flow_index))
+            } else {
+                6
+            };
             let constraints = [
                 Constraint::Length(1),
                 Constraint::Min(0),
-                Constraint::Length(6),
+                Constraint::Length(infobox_height),
             ];
             let areas = Layout::new(Direction::Vertical, constraints).split(table_area);
 
-            tabs::draw(frame, areas[0], *flow_index, flows.keys());
+            tabs::draw(frame, areas[0], *flow_index, flows.names());
+
+            // Packets of merged captures are listed with the capture they were read from
+            let source_width = flows.is_merged().then_some(Constraint::Min(1));
+            let source_header = flows.is_merged().then_some("SOURCE");
+
+            if *flow_index == flows.len() {
+                table::draw(
+                    frame,
+                    areas[1],
+                    [
+                        Constraint::Length(4),
+                        Constraint::Min(1),
+                        Constraint::Min(1),
+                        Constraint::Min(1),
+                    ]
+                    .into_iter()
+                    .chain(source_width),
+                    ["#", "REASON", "TIMESTAMP", "LENGTH"]
+                        .into_iter()
+                        .chain(source_header),
+                    flows.unclassified_iter(),
+                    *index,
+                    &mut context.table_state,
+                );
+                infobox::draw_unclassified(frame, areas[2], flows.unclassified());
+                return;
+            }
+
+            if let Some(payload) = payload {
+                payload.draw(frame, areas[1], *index);
+                infobox::draw(frame, areas[2], flows.get(*flow_index));
+                return;
+            }
+
             table::draw(
                 frame,
                 areas[1],
@@ -133,8 +205,11 @@ fn draw_body(frame: &mut Frame, context: &mut Context, table_area: Rect) {
                     Constraint::Min(1),
                     Constraint::Min(1),
                 ]
-                .into_iter(),
-                ["#", "DIRECTION", "TIMESTAMP", "LENGTH"].into_iter(),
+                .into_iter()
+                .chain(source_width),
+                ["#", "DIRECTION", "TIMESTAMP", "LENGTH"]
+                    .into_iter()
+                    .chain(source_header),
                 flows.iter(*flow_index),
                 *index,
                 &mut context.table_state,
@@ -147,15 +222,35 @@ fn draw_body(frame: &mut Frame, context: &mut Context, table_area: Rect) {
 
 fn draw_footer(frame: &mut Frame, state: &State, area: Rect) {
     let instructions = match state {
-        State::Browse { .. } => " [↑] UP [↓] DOWN [ESC] EXIT [↵] OPEN [BACKSP] GO UP ",
-        State::View { .. } => {
-            " [↑] UP [↓] DOWN [←] PREVIOUS [→] NEXT [ESC] EXIT [BACKSP] CLOSE FILE "
+        State::Browse { .. } => Cow::from(" [↑] UP [↓] DOWN [ESC] EXIT [↵] OPEN [BACKSP] GO UP "),
+        State::View {
+            export_path: Some(export_path),
+            export_target,
+            ..
+        } => {
+            let prompt = match export_target {
+                ExportTarget::SelectedTab => "EXPORT TO",
+                ExportTarget::WholeCapture => "SAVE CAPTURE TO",
+            };
+
+            Cow::from(format!(" {prompt}: {export_path}█ [↵] SAVE [ESC] CANCEL "))
         }
-        State::Exit => "",
+        State::View {
+            payload: Some(_), ..
+        } => Cow::from(
+            " [↑] UP [↓] DOWN [←] PREVIOUS [→] NEXT [P] PACKETS [ESC] EXIT [BACKSP] CLOSE FILE ",
+        ),
+        State::View { flows, .. } if flows.is_temporary() => Cow::from(
+            " [↑] UP [↓] DOWN [←] PREVIOUS [→] NEXT [E] EXPORT [P] PAYLOAD [S] SAVE CAPTURE [ESC] EXIT [BACKSP] CLOSE FILE ",
+        ),
+        State::View { .. } => Cow::from(
+            " [↑] UP [↓] DOWN [←] PREVIOUS [→] NEXT [E] EXPORT [P] PAYLOAD [ESC] EXIT [BACKSP] CLOSE FILE ",
+        ),
+        State::Exit => Cow::from(""),
     };
 
     let header = Block::new()
-        .title(instructions)
+        .title(instructions.as_ref())
         .style(style::FOOTER)
         .borders(Borders::TOP);
 
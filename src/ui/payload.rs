@@ -0,0 +1,94 @@
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::widgets::Paragraph;
+use ratatui::Frame;
+
+use crate::stream::{StreamData, TcpStream};
+use crate::ui::style;
+
+/// Reassembled conversation of a TCP flow as hex and ASCII, the initiator's data coloured
+/// differently from the respondent's
+pub struct PayloadView {
+    lines: Vec<Line<'static>>,
+}
+
+impl PayloadView {
+    const BYTES_PER_LINE: usize = 16;
+
+    pub fn new(stream: &TcpStream) -> Self {
+        // Offsets count the bytes sent in each direction so far, missing ones included
+        let mut offsets = [0u64; 2];
+
+        let mut lines = Vec::new();
+        for chunk in &stream.chunks {
+            let (arrow, style) = if chunk.from_initiator {
+                ("→", style::payload::INITIATOR)
+            } else {
+                ("←", style::payload::RESPONDENT)
+            };
+            let offset = &mut offsets[usize::from(chunk.from_initiator)];
+
+            match &chunk.data {
+                StreamData::Bytes(bytes) => {
+                    for bytes in bytes.chunks(Self::BYTES_PER_LINE) {
+                        lines.push(Self::hex_line(arrow, *offset, bytes, style));
+                        *offset += bytes.len() as u64;
+                    }
+                }
+                StreamData::Gap(length) => {
+                    let text = format!("{arrow} {offset:08X}  [{length} bytes not captured]");
+                    lines.push(Line::styled(text, style));
+                    *offset += length;
+                }
+            }
+        }
+        if lines.is_empty() {
+            lines.push(Line::from("[no payload captured]"));
+        }
+
+        Self { lines }
+    }
+
+    fn hex_line(arrow: &str, offset: u64, bytes: &[u8], style: Style) -> Line<'static> {
+        let hex = bytes
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let ascii = bytes
+            .iter()
+            .map(|byte| {
+                if byte.is_ascii_graphic() || *byte == b' ' {
+                    char::from(*byte)
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+
+        let text = format!(
+            "{arrow} {offset:08X}  {hex:<width$}  {ascii}",
+            width = Self::BYTES_PER_LINE * 3 - 1
+        );
+
+        Line::styled(text, style)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Draws the lines of the conversation starting with the line at `first_line`
+    pub fn draw(&self, frame: &mut Frame, area: Rect, first_line: usize) {
+        let lines = self
+            .lines
+            .iter()
+            .skip(first_line)
+            .take(usize::from(area.height))
+            .cloned()
+            .collect::<Vec<Line>>();
+
+        frame.render_widget(Paragraph::new(lines).style(style::TABLE), area);
+    }
+}
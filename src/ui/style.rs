@@ -60,6 +60,19 @@ pub mod tabs {
         .bg(color::tabs::UNSELECTED.bg());
 }
 
+pub mod payload {
+    use ratatui::style::Style;
+
+    use crate::ui::color;
+
+    pub const INITIATOR: Style = Style::new()
+        .fg(color::payload::INITIATOR.fg())
+        .bg(color::payload::INITIATOR.bg());
+    pub const RESPONDENT: Style = Style::new()
+        .fg(color::payload::RESPONDENT.fg())
+        .bg(color::payload::RESPONDENT.bg());
+}
+
 pub const fn file() -> Style {
     Style::new()
 }
@@ -71,3 +84,7 @@ pub fn directory() -> Style {
 pub fn symbolic_link() -> Style {
     Style::new().underlined()
 }
+
+pub fn capture() -> Style {
+    Style::new().italic()
+}
@@ -1,19 +1,22 @@
 use std::fmt::{Display, Formatter};
 use std::ops::{Div, Rem};
 
-pub struct TimestampDisplay(pub u64);
+/// Length of time in nanoseconds, displayed in seconds, which may be negative
+pub struct TimestampDisplay(pub i128);
 
 impl TimestampDisplay {
-    const NANOS_DIVISOR: u64 = 1_000_000_000;
+    const NANOS_DIVISOR: u128 = 1_000_000_000;
 }
 
 impl Display for TimestampDisplay {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+
         write!(
             f,
-            "{}.{:0>9}",
-            self.0.div(Self::NANOS_DIVISOR),
-            self.0.rem(Self::NANOS_DIVISOR)
+            "{sign}{}.{:0>9}",
+            self.0.unsigned_abs().div(Self::NANOS_DIVISOR),
+            self.0.unsigned_abs().rem(Self::NANOS_DIVISOR)
         )
     }
 }